@@ -0,0 +1,402 @@
+use crate::config::{AccountsConfig, PasswordConfig};
+use crate::error::{AppError, Result};
+use crate::period::TIMESTAMP_FORMAT;
+use crate::smtp::{Email, is_valid_address, send_email};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Version};
+use axum::http::{HeaderMap, header};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool};
+use time::OffsetDateTime;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// Cookie holding the session token
+pub const SESSION_COOKIE: &str = "splits_session";
+
+/// Shortest password accepted for a new account
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Account {
+    pub id: i64,
+    pub user: String,
+    pub email: Option<String>,
+    pub created_at: String,
+}
+
+/// A new random token, for the client to keep
+pub(crate) fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// What's stored instead of a token, so a leaked database can't be used to log in
+pub(crate) fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Hash a password with a fresh random salt, as a PHC string such as `$argon2id$v=19$m=19456,t=2,p=1$...`
+fn hash_password(password: &str, config: &PasswordConfig) -> Result<String> {
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, config.params()?);
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = argon2.hash_password(password.as_bytes(), &salt).map_err(|e| AppError::Other(e.to_string()))?;
+    Ok(hash.to_string())
+}
+
+/// Check a password against a stored hash, using the cost parameters it was made with
+fn verify_password(password: &str, hash: &str) -> Result<bool> {
+    let hash = PasswordHash::new(hash).map_err(|e| AppError::Other(format!("Invalid password hash: {}", e)))?;
+    Ok(Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+/// Checked against when there's no such account, at the default cost
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    hash_password("not a real password", &PasswordConfig::default()).expect("default Argon2 parameters are valid")
+});
+
+fn timestamp(at: OffsetDateTime) -> Result<String> {
+    at.format(TIMESTAMP_FORMAT).map_err(|e| AppError::Other(e.to_string()))
+}
+
+async fn get_account_where(pool: &SqlitePool, condition: &str, value: &str) -> Result<Option<(Account, String)>> {
+    let row = sqlx::query(&format!("SELECT id, user, email, created_at, password_hash FROM accounts WHERE {} = ?1", condition))
+        .bind(value)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|row| {
+        let account = Account { id: row.get(0), user: row.get(1), email: row.get(2), created_at: row.get(3) };
+        (account, row.get(4))
+    }))
+}
+
+/// Create an account. Fails with `AlreadyTaken` if the name or email is in use, including
+/// a name with splits already under it, which would hand its runner's history to whoever
+/// registered first. The caller checks the name against the validation rules.
+pub async fn create_account(
+    pool: &SqlitePool,
+    config: &AccountsConfig,
+    user: &str,
+    password: &str,
+    email: Option<&str>,
+) -> Result<Account> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::Invalid(format!("Password must be at least {} characters", MIN_PASSWORD_LENGTH)));
+    }
+    if email.is_some_and(|email| !is_valid_address(email)) {
+        return Err(AppError::Invalid("Invalid email address".to_string()));
+    }
+    if get_account_where(pool, "user", user).await?.is_some() {
+        return Err(AppError::AlreadyTaken(user.to_string()));
+    }
+    if let Some(email) = email
+        && get_account_where(pool, "email", email).await?.is_some()
+    {
+        return Err(AppError::AlreadyTaken(email.to_string()));
+    }
+
+    // Hashing is deliberately slow, so keep it off the async workers
+    let (password, password_config) = (password.to_string(), config.password.clone());
+    let password_hash = tokio::task::spawn_blocking(move || hash_password(&password, &password_config))
+        .await
+        .map_err(|e| AppError::Other(e.to_string()))??;
+
+    // Checked in the insert, so a split submitted while hashing still counts
+    let row = sqlx::query(
+        "INSERT INTO accounts (user, email, password_hash) SELECT ?1, ?2, ?3
+         WHERE NOT EXISTS (SELECT 1 FROM splits WHERE user = ?1) AND NOT EXISTS (SELECT 1 FROM hidden_splits WHERE user = ?1)
+         RETURNING id, created_at"
+    )
+    .bind(user)
+    .bind(email)
+    .bind(password_hash)
+    .fetch_optional(pool)
+    .await?;
+    let Some(row) = row else {
+        return Err(AppError::AlreadyTaken(user.to_string()));
+    };
+    info!("Created account for {}", user);
+    Ok(Account { id: row.get(0), user: user.to_string(), email: email.map(str::to_string), created_at: row.get(1) })
+}
+
+/// Check a name and password, returning the account if they match
+pub async fn authenticate(pool: &SqlitePool, user: &str, password: &str) -> Result<Option<Account>> {
+    let (account, password_hash) = match get_account_where(pool, "user", user).await? {
+        Some((account, password_hash)) => (Some(account), Some(password_hash)),
+        None => (None, None),
+    };
+    let password = password.to_string();
+    // Without an account there's still a hash to check, so the time taken doesn't reveal whether the name is registered
+    let matches = tokio::task::spawn_blocking(move || verify_password(&password, password_hash.as_deref().unwrap_or(&DUMMY_HASH)))
+        .await
+        .map_err(|e| AppError::Other(e.to_string()))??;
+    Ok(account.filter(|_| matches))
+}
+
+/// Whether a name belongs to an account, so only its owner can submit splits under it
+pub async fn is_registered(pool: &SqlitePool, user: &str) -> Result<bool> {
+    Ok(get_account_where(pool, "user", user).await?.is_some())
+}
+
+/// Password checks allowed from one address per `LOGIN_WINDOW`, since each costs a slow hash
+pub const LOGIN_ATTEMPTS: u32 = 20;
+pub const LOGIN_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// Counts password checks per address, so one client can't keep the server hashing
+#[derive(Clone, Default)]
+pub struct LoginThrottle {
+    attempts: Arc<Mutex<HashMap<IpAddr, (Instant, u32)>>>,
+}
+
+impl LoginThrottle {
+    /// Count an attempt from an address, returning how long it has to wait if it's made too many
+    pub fn check(&self, addr: IpAddr, now: Instant) -> Option<Duration> {
+        let mut attempts = self.attempts.lock().unwrap();
+        attempts.retain(|_, (started, _)| now.duration_since(*started) < LOGIN_WINDOW);
+        let (started, count) = attempts.entry(addr).or_insert((now, 0));
+        if *count >= LOGIN_ATTEMPTS {
+            return Some(LOGIN_WINDOW - now.duration_since(*started));
+        }
+        *count += 1;
+        None
+    }
+}
+
+/// Start a session for an account, returning its token. Expired sessions are cleared out on the way.
+pub async fn create_session(pool: &SqlitePool, config: &AccountsConfig, account_id: i64) -> Result<String> {
+    let now = OffsetDateTime::now_utc();
+    sqlx::query("DELETE FROM sessions WHERE expires_at <= ?1").bind(timestamp(now)?).execute(pool).await?;
+
+    let token = new_token();
+    sqlx::query("INSERT INTO sessions (token_hash, account_id, expires_at) VALUES (?1, ?2, ?3)")
+        .bind(hash_token(&token))
+        .bind(account_id)
+        .bind(timestamp(now + time::Duration::days(config.session_days as i64))?)
+        .execute(pool)
+        .await?;
+    Ok(token)
+}
+
+/// The account a session token belongs to, if the session hasn't expired
+pub async fn get_session_account(pool: &SqlitePool, token: &str) -> Result<Option<Account>> {
+    let row = sqlx::query(
+        "SELECT a.id, a.user, a.email, a.created_at FROM sessions s JOIN accounts a ON a.id = s.account_id
+         WHERE s.token_hash = ?1 AND s.expires_at > ?2"
+    )
+    .bind(hash_token(token))
+    .bind(timestamp(OffsetDateTime::now_utc())?)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| Account { id: row.get(0), user: row.get(1), email: row.get(2), created_at: row.get(3) }))
+}
+
+/// End a session
+pub async fn delete_session(pool: &SqlitePool, token: &str) -> Result<()> {
+    sqlx::query("DELETE FROM sessions WHERE token_hash = ?1").bind(hash_token(token)).execute(pool).await?;
+    Ok(())
+}
+
+/// Email a single-use login link to the account with this address. The email is sent in the
+/// background, so the time taken doesn't reveal whether the address has an account.
+/// Returns false without sending anything if there's no such account, which callers shouldn't reveal.
+pub async fn send_login_link(pool: &SqlitePool, config: &AccountsConfig, email: &str) -> Result<bool> {
+    if config.smtp.host.is_empty() {
+        return Err(AppError::Config("Email login is disabled, set accounts.smtp.host to enable it".to_string()));
+    }
+    let Some((account, _)) = get_account_where(pool, "email", email).await? else {
+        return Ok(false);
+    };
+
+    let token = new_token();
+    let expires_at = OffsetDateTime::now_utc() + time::Duration::minutes(config.magic_link_minutes as i64);
+    sqlx::query("INSERT INTO login_links (token_hash, account_id, expires_at) VALUES (?1, ?2, ?3)")
+        .bind(hash_token(&token))
+        .bind(account.id)
+        .bind(timestamp(expires_at)?)
+        .execute(pool)
+        .await?;
+
+    let link = format!("{}/api/v1/login/email/{}", config.public_url.trim_end_matches('/'), token);
+    let body = format!(
+        "Hi {},\n\nOpen this link to log in to Splits:\n\n{}\n\nIt works once and expires in {} minutes. If you didn't ask to log in, you can ignore this email.",
+        account.user, link, config.magic_link_minutes
+    );
+    let (smtp, email) = (config.smtp.clone(), email.to_string());
+    tokio::spawn(async move {
+        match send_email(&smtp, &Email { to: &email, subject: "Your Splits login link", body: &body }).await {
+            Ok(()) => info!("Sent a login link to {}", account.user),
+            Err(e) => error!("Error sending a login link to {}: {}", account.user, e),
+        }
+    });
+    Ok(true)
+}
+
+/// Use up a login link, returning its account if the link was valid
+pub async fn redeem_login_link(pool: &SqlitePool, token: &str) -> Result<Option<Account>> {
+    let now = timestamp(OffsetDateTime::now_utc())?;
+    // Marking it used in the same statement means two clicks can't both log in
+    let account_id: Option<i64> = sqlx::query_scalar(
+        "UPDATE login_links SET used_at = ?2 WHERE token_hash = ?1 AND used_at IS NULL AND expires_at > ?2 RETURNING account_id"
+    )
+    .bind(hash_token(token))
+    .bind(&now)
+    .fetch_optional(pool)
+    .await?;
+
+    match account_id {
+        Some(id) => Ok(get_account_where(pool, "id", &id.to_string()).await?.map(|(account, _)| account)),
+        None => Ok(None),
+    }
+}
+
+/// The session token from a request's cookies
+pub fn session_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| cookie.trim().strip_prefix(SESSION_COOKIE)?.strip_prefix('=').map(str::to_string))
+}
+
+/// `Set-Cookie` value starting a session
+pub fn session_cookie(config: &AccountsConfig, token: &str) -> String {
+    let secure = if config.secure_cookies { "; Secure" } else { "" };
+    format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        SESSION_COOKIE,
+        token,
+        config.session_days as u64 * 24 * 60 * 60,
+        secure
+    )
+}
+
+/// `Set-Cookie` value ending a session
+pub fn clear_session_cookie() -> String {
+    format!("{}=; Path=/; Max-Age=0; HttpOnly; SameSite=Lax", SESSION_COOKIE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SmtpTls;
+    use crate::database::initialize_database;
+    use crate::smtp::fake_smtp_server;
+
+    fn config() -> AccountsConfig {
+        AccountsConfig {
+            password: PasswordConfig { memory_kib: 64, iterations: 1, parallelism: 1 },
+            public_url: "https://splits.example.com/".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_accounts_and_sessions() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let config = config();
+
+        let alice = create_account(&pool, &config, "alice", "hunter22", Some("alice@example.com")).await.unwrap();
+        assert!(matches!(create_account(&pool, &config, "alice", "different", None).await, Err(AppError::AlreadyTaken(_))));
+        assert!(matches!(create_account(&pool, &config, "alicia", "different", Some("alice@example.com")).await, Err(AppError::AlreadyTaken(_))));
+        assert!(matches!(create_account(&pool, &config, "bob", "short", None).await, Err(AppError::Invalid(_))));
+        assert!(is_registered(&pool, "alice").await.unwrap() && !is_registered(&pool, "bob").await.unwrap());
+
+        // Names with splits, shown or hidden, belong to whoever submitted them
+        sqlx::query("INSERT INTO splits (user, is_down, is_elevator, duration_ms) VALUES ('carol', 1, 0, 5000)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO hidden_splits (id, user, is_down, is_elevator, duration_ms) VALUES (99, 'dave', 1, 0, 5000)").execute(&pool).await.unwrap();
+        assert!(matches!(create_account(&pool, &config, "carol", "hunter22", None).await, Err(AppError::AlreadyTaken(_))));
+        assert!(matches!(create_account(&pool, &config, "dave", "hunter22", None).await, Err(AppError::AlreadyTaken(_))));
+        assert!(!is_registered(&pool, "carol").await.unwrap());
+
+        let stored: String = sqlx::query_scalar("SELECT password_hash FROM accounts WHERE id = ?1").bind(alice.id).fetch_one(&pool).await.unwrap();
+        assert!(stored.starts_with("$argon2id$") && !stored.contains("hunter22"));
+        assert!(authenticate(&pool, "alice", "hunter23").await.unwrap().is_none());
+        assert!(authenticate(&pool, "nobody", "hunter22").await.unwrap().is_none());
+        assert_eq!(authenticate(&pool, "alice", "hunter22").await.unwrap().unwrap().id, alice.id);
+
+        let token = create_session(&pool, &config, alice.id).await.unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, format!("theme=dark; {}={}", SESSION_COOKIE, token).parse().unwrap());
+        assert_eq!(session_token(&headers).as_deref(), Some(token.as_str()));
+        assert_eq!(get_session_account(&pool, &token).await.unwrap().unwrap().user, "alice");
+        assert!(session_cookie(&config, &token).contains("HttpOnly; SameSite=Lax"));
+
+        delete_session(&pool, &token).await.unwrap();
+        assert!(get_session_account(&pool, &token).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_login_throttle() {
+        let throttle = LoginThrottle::default();
+        let (alice, bob) = ("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap());
+        let start = Instant::now();
+        for _ in 0..LOGIN_ATTEMPTS {
+            assert!(throttle.check(alice, start).is_none());
+        }
+        let later = start + Duration::from_secs(60);
+        assert_eq!(throttle.check(alice, later), Some(LOGIN_WINDOW - Duration::from_secs(60)));
+        assert!(throttle.check(bob, later).is_none());
+        // The count starts over once the window has passed
+        assert!(throttle.check(alice, start + LOGIN_WINDOW).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_login_links() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let (port, received) = fake_smtp_server().await;
+        let mut config = config();
+        assert!(send_login_link(&pool, &config, "alice@example.com").await.is_err());
+        config.smtp.host = "127.0.0.1".to_string();
+        config.smtp.port = port;
+        config.smtp.tls = SmtpTls::None;
+        create_account(&pool, &config, "alice", "hunter22", Some("alice@example.com")).await.unwrap();
+
+        // Emails go out in the background
+        let wait_for = |count: usize| {
+            let received = received.clone();
+            async move {
+                let arrived = async {
+                    while received.lock().unwrap().len() < count {
+                        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                    }
+                };
+                tokio::time::timeout(std::time::Duration::from_secs(5), arrived).await.expect("login email never arrived");
+                // Undo quoted-printable's soft line breaks, which split the long link
+                received.lock().unwrap()[count - 1].replace("=\n", "")
+            }
+        };
+        assert!(!send_login_link(&pool, &config, "mallory@example.com").await.unwrap());
+        assert!(send_login_link(&pool, &config, "alice@example.com").await.unwrap());
+        let message = wait_for(1).await;
+        assert_eq!(received.lock().unwrap().len(), 1);
+        let prefix = "https://splits.example.com/api/v1/login/email/";
+        let link = message.lines().find(|line| line.starts_with(prefix)).unwrap();
+        let token = &link[prefix.len()..];
+
+        assert_eq!(redeem_login_link(&pool, token).await.unwrap().unwrap().user, "alice");
+        // Links work once
+        assert!(redeem_login_link(&pool, token).await.unwrap().is_none());
+        assert!(redeem_login_link(&pool, "made-up").await.unwrap().is_none());
+
+        // and not after they expire
+        send_login_link(&pool, &config, "alice@example.com").await.unwrap();
+        let message = wait_for(2).await;
+        let token = message.lines().find_map(|line| line.strip_prefix(prefix)).unwrap().to_string();
+        sqlx::query("UPDATE login_links SET expires_at = '2000-01-01 00:00:00'").execute(&pool).await.unwrap();
+        assert!(redeem_login_link(&pool, &token).await.unwrap().is_none());
+    }
+}
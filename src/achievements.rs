@@ -0,0 +1,283 @@
+use crate::config::{AchievementsConfig, Config};
+use crate::database::get_world_records;
+use crate::error::Result;
+use crate::models::{Category, Split};
+use crate::streaks::get_streaks;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use time::OffsetDateTime;
+use tracing::info;
+
+/// An achievement users can unlock, declared in configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AchievementDefinition {
+    /// Stable identifier stored with unlocks, must be unique
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub rule: Rule,
+}
+
+/// Condition a user's runs must meet to unlock an achievement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Rule {
+    /// Log at least this many runs
+    RunCount { runs: i64 },
+    /// Hold the world record in every category at once
+    WrInEveryCategory,
+    /// Finish a run faster than the given duration, optionally limited to matching categories
+    Under {
+        duration_ms: i32,
+        is_down: Option<bool>,
+        is_elevator: Option<bool>,
+        is_encumbered: Option<bool>,
+    },
+    /// Log runs on this many consecutive days
+    Streak { days: i64 },
+    /// Overtake another user's personal best in a category where they were ahead of you
+    BeatRival,
+}
+
+/// An achievement a user has unlocked
+#[derive(Debug, Clone, Serialize)]
+pub struct UnlockedAchievement {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub split_id: i32,
+    pub unlocked_at: String,
+}
+
+/// Achievements available out of the box
+pub fn default_achievements() -> Vec<AchievementDefinition> {
+    let achievement = |id: &str, name: &str, description: &str, rule: Rule| AchievementDefinition {
+        id: id.to_string(),
+        name: name.to_string(),
+        description: description.to_string(),
+        rule,
+    };
+
+    vec![
+        achievement("first_run", "First Steps", "Log your first run", Rule::RunCount { runs: 1 }),
+        achievement("runs_10", "Regular", "Log 10 runs", Rule::RunCount { runs: 10 }),
+        achievement("runs_100", "Stair Master", "Log 100 runs", Rule::RunCount { runs: 100 }),
+        achievement(
+            "wr_every_category",
+            "Untouchable",
+            "Hold the world record in every category",
+            Rule::WrInEveryCategory,
+        ),
+        achievement(
+            "sub_30s_climb",
+            "Mountain Goat",
+            "Climb the stairs in under 30 seconds",
+            Rule::Under { duration_ms: 30_000, is_down: Some(false), is_elevator: Some(false), is_encumbered: None },
+        ),
+        achievement("streak_7", "Habit Forming", "Log runs on seven days in a row", Rule::Streak { days: 7 }),
+        achievement("rival_beaten", "Rivalry", "Overtake someone's personal best", Rule::BeatRival),
+    ]
+}
+
+/// Evaluate every achievement the user hasn't unlocked yet against their latest split,
+/// storing and returning the ones that were just unlocked
+pub async fn evaluate_achievements(pool: &SqlitePool, config: &Config, split: &Split) -> Result<Vec<AchievementDefinition>> {
+    let unlocked_ids: Vec<String> = sqlx::query_scalar("SELECT achievement_id FROM user_achievements WHERE user = ?1")
+        .bind(&split.user)
+        .fetch_all(pool)
+        .await?;
+
+    let mut newly_unlocked = Vec::new();
+    for definition in &config.achievements.definitions {
+        if unlocked_ids.contains(&definition.id) || !rule_is_met(pool, config, &definition.rule, split).await? {
+            continue;
+        }
+
+        // Another split by the same user may have unlocked it meanwhile, and only that one announces it
+        let inserted = sqlx::query("INSERT OR IGNORE INTO user_achievements (user, achievement_id, split_id) VALUES (?1, ?2, ?3)")
+            .bind(&split.user)
+            .bind(&definition.id)
+            .bind(split.id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+        if inserted != 1 {
+            continue;
+        }
+        info!("{} unlocked achievement {}", split.user, definition.id);
+        newly_unlocked.push(definition.clone());
+    }
+
+    Ok(newly_unlocked)
+}
+
+/// Check a single rule against a user's history up to and including `split`
+async fn rule_is_met(pool: &SqlitePool, config: &Config, rule: &Rule, split: &Split) -> Result<bool> {
+    match rule {
+        Rule::RunCount { runs } => {
+            let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM splits WHERE user = ?1 AND id <= ?2")
+                .bind(&split.user)
+                .bind(split.id)
+                .fetch_one(pool)
+                .await?;
+            Ok(count >= *runs)
+        }
+        Rule::WrInEveryCategory => {
+            let records = get_world_records(pool).await?;
+            Ok(records.len() == Category::ALL.len() && records.iter().all(|wr| wr.user == split.user))
+        }
+        Rule::Under { duration_ms, is_down, is_elevator, is_encumbered } => {
+            let category = Category::of(split);
+            Ok(split.duration_ms < *duration_ms
+                && is_down.is_none_or(|d| d == category.is_down)
+                && is_elevator.is_none_or(|e| e == category.is_elevator)
+                && is_encumbered.is_none_or(|e| Some(e) == category.is_encumbered))
+        }
+        Rule::Streak { days } => {
+            let streaks = get_streaks(pool, &split.user, config.streaks.offset()?, OffsetDateTime::now_utc()).await?;
+            Ok(streaks.longest >= *days)
+        }
+        Rule::BeatRival => {
+            // Someone whose best was ahead of the user's previous best, but behind this split
+            let category = Category::of(split);
+            let beaten: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM (
+                    SELECT user, MIN(duration_ms) AS best FROM splits
+                    WHERE is_down = ?1 AND is_elevator = ?2 AND (?3 IS NULL OR is_encumbered = ?3)
+                    AND user != ?4 AND id < ?5
+                    GROUP BY user
+                 ) rivals
+                 WHERE rivals.best > ?6 AND rivals.best < (
+                    SELECT MIN(duration_ms) FROM splits
+                    WHERE is_down = ?1 AND is_elevator = ?2 AND (?3 IS NULL OR is_encumbered = ?3)
+                    AND user = ?4 AND id < ?5
+                 )"
+            )
+            .bind(category.is_down)
+            .bind(category.is_elevator)
+            .bind(category.is_encumbered)
+            .bind(&split.user)
+            .bind(split.id)
+            .bind(split.duration_ms)
+            .fetch_one(pool)
+            .await?;
+            Ok(beaten > 0)
+        }
+    }
+}
+
+/// Get the achievements a user has unlocked, oldest first
+pub async fn get_user_achievements(
+    pool: &SqlitePool,
+    config: &AchievementsConfig,
+    user: &str,
+) -> Result<Vec<UnlockedAchievement>> {
+    let rows = sqlx::query(
+        "SELECT achievement_id, split_id, unlocked_at FROM user_achievements WHERE user = ?1 ORDER BY unlocked_at ASC, rowid ASC"
+    )
+    .bind(user)
+    .fetch_all(pool)
+    .await?;
+
+    // Unlocks of achievements since removed from the configuration are not listed
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let id: String = row.get(0);
+            let definition = config.definitions.iter().find(|d| d.id == id)?;
+            Some(UnlockedAchievement {
+                id,
+                name: definition.name.clone(),
+                description: definition.description.clone(),
+                split_id: row.get(1),
+                unlocked_at: row.get(2),
+            })
+        })
+        .collect())
+}
+
+/// Format newly unlocked achievements to append to a split announcement
+pub fn format_unlocked(unlocked: &[AchievementDefinition]) -> String {
+    unlocked
+        .iter()
+        .map(|a| format!("🏅 Unlocked **{}**: {}", a.name, a.description))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Format a user's badges for display
+pub fn format_badges(user: &str, unlocked: &[UnlockedAchievement], total: usize) -> String {
+    if unlocked.is_empty() {
+        return format!("{} hasn't unlocked any badges yet.", user);
+    }
+
+    let mut formatted = format!("**{}'s Badges ({}/{}):**\n", user, unlocked.len(), total);
+    for achievement in unlocked {
+        formatted.push_str(&format!(
+            "🏅 **{}**: {} ({})\n",
+            achievement.name, achievement.description, achievement.unlocked_at
+        ));
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{get_latest_split_for_user, initialize_database};
+
+    async fn log_split(pool: &SqlitePool, config: &Config, user: &str, duration_ms: i32) -> Vec<String> {
+        sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, 0, 0, 0, ?2)")
+            .bind(user)
+            .bind(duration_ms)
+            .execute(pool)
+            .await
+            .unwrap();
+        let split = get_latest_split_for_user(pool, user).await.unwrap().unwrap();
+        evaluate_achievements(pool, config, &split).await.unwrap().into_iter().map(|a| a.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_achievements() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let config = Config::default();
+
+        assert_eq!(log_split(&pool, &config, "alice", 40_000).await, vec!["first_run"]);
+        assert_eq!(log_split(&pool, &config, "bob", 35_000).await, vec!["first_run"]);
+        // Alice overtakes Bob's best and climbs under 30 seconds
+        assert_eq!(log_split(&pool, &config, "alice", 29_000).await, vec!["sub_30s_climb", "rival_beaten"]);
+        // Achievements unlock only once
+        assert!(log_split(&pool, &config, "alice", 28_000).await.is_empty());
+
+        let unlocked = get_user_achievements(&pool, &config.achievements, "alice").await.unwrap();
+        assert_eq!(unlocked.len(), 3);
+        assert_eq!(unlocked[0].name, "First Steps");
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_splits_unlock_once() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let config = Config::default();
+        for duration_ms in [40_000, 39_000] {
+            sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES ('carol', 0, 0, 0, ?1)")
+                .bind(duration_ms)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let splits = crate::database::get_all_splits(&pool).await.unwrap();
+        let (first, second) = (splits.iter().min_by_key(|s| s.id).unwrap(), splits.iter().max_by_key(|s| s.id).unwrap());
+
+        // Only the history up to each split counts
+        assert!(rule_is_met(&pool, &config, &Rule::RunCount { runs: 1 }, first).await.unwrap());
+        assert!(!rule_is_met(&pool, &config, &Rule::RunCount { runs: 2 }, first).await.unwrap());
+        assert!(rule_is_met(&pool, &config, &Rule::RunCount { runs: 2 }, second).await.unwrap());
+
+        // Both see the achievement as locked, but only one announces it
+        let (a, b) = tokio::join!(evaluate_achievements(&pool, &config, first), evaluate_achievements(&pool, &config, second));
+        let announced = [a.unwrap(), b.unwrap()].concat().iter().filter(|a| a.id == "first_run").count();
+        assert_eq!(announced, 1);
+    }
+}
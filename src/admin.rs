@@ -0,0 +1,256 @@
+use crate::config::{ValidationConfig, WebhooksConfig};
+use crate::error::{AppError, Result};
+use crate::models::Split;
+use crate::stream::{StreamEventKind, record_event};
+use crate::validation::UsernameValidator;
+use crate::webhooks::{WebhookEvent, enqueue_split_changed};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use tracing::info;
+
+/// Queue the webhooks and live feed event for a moderated split in the transaction removing
+/// or restoring it, so they go out exactly when the change is made
+async fn record_split_change(
+    conn: &mut SqliteConnection,
+    webhooks: &WebhooksConfig,
+    restored: bool,
+    split: &Split,
+) -> Result<()> {
+    let (event, kind) = if restored {
+        (WebhookEvent::SplitRestored, StreamEventKind::SplitRestored)
+    } else {
+        (WebhookEvent::SplitDeleted, StreamEventKind::SplitDeleted)
+    };
+    enqueue_split_changed(&mut *conn, webhooks, event, split).await?;
+    record_event(conn, kind, split).await?;
+    Ok(())
+}
+
+fn split_from_row(row: &sqlx::sqlite::SqliteRow) -> Split {
+    Split {
+        id: row.get(0),
+        user: row.get(1),
+        is_down: row.get(2),
+        is_elevator: row.get(3),
+        is_encumbered: row.get(4),
+        duration_ms: row.get(5),
+        created_at: row.get(6),
+    }
+}
+
+/// Hide a split, moving it out of `splits` so no leaderboard, stat or export sees it,
+/// and queue `split.deleted` webhooks. Returns false if there's no visible split with that id.
+pub async fn hide_split(pool: &SqlitePool, webhooks: &WebhooksConfig, id: i32) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO hidden_splits (id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at)
+         SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits WHERE id = ?1"
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    let row = sqlx::query(
+        "DELETE FROM splits WHERE id = ?1 RETURNING id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at"
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(false);
+    };
+    record_split_change(&mut tx, webhooks, false, &split_from_row(&row)).await?;
+    tx.commit().await?;
+    info!("Hid split {}", id);
+    Ok(true)
+}
+
+/// Restore a hidden split with its original id and queue `split.restored` webhooks.
+/// Returns false if no split with that id is hidden.
+pub async fn unhide_split(pool: &SqlitePool, webhooks: &WebhooksConfig, id: i32) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO splits (id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at)
+         SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM hidden_splits WHERE id = ?1"
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    let row = sqlx::query(
+        "DELETE FROM hidden_splits WHERE id = ?1 RETURNING id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at"
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(false);
+    };
+    record_split_change(&mut tx, webhooks, true, &split_from_row(&row)).await?;
+    tx.commit().await?;
+    info!("Unhid split {}", id);
+    Ok(true)
+}
+
+/// Permanently delete a split, hidden or not, and queue `split.deleted` webhooks.
+/// Returns false if there was no such split.
+pub async fn delete_split(pool: &SqlitePool, webhooks: &WebhooksConfig, id: i32) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    let mut deleted = None;
+    for table in ["splits", "hidden_splits"] {
+        let row = sqlx::query(&format!(
+            "DELETE FROM {} WHERE id = ?1 RETURNING id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at",
+            table
+        ))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(row) = row {
+            deleted = Some(split_from_row(&row));
+        }
+    }
+
+    let Some(split) = deleted else {
+        return Ok(false);
+    };
+    record_split_change(&mut tx, webhooks, false, &split).await?;
+    tx.commit().await?;
+    info!("Deleted split {}", id);
+    Ok(true)
+}
+
+/// Get hidden splits, most recently hidden first
+pub async fn get_hidden_splits(pool: &SqlitePool) -> Result<Vec<Split>> {
+    let rows = sqlx::query(
+        "SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM hidden_splits
+         ORDER BY hidden_at DESC, id DESC"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(split_from_row).collect())
+}
+
+/// Tables with a `user` column, all of which follow a rename
+const USER_TABLES: [&str; 9] = [
+    "accounts",
+    "splits",
+    "hidden_splits",
+    "season_standings",
+    "user_achievements",
+    "discord_links",
+    "discord_link_codes",
+    "ratings",
+    "rating_history",
+];
+
+/// Rename a user everywhere. Fails if the new name is already in use, since
+/// merging two users' achievements, ratings and links isn't well defined, if it
+/// isn't a name the API would accept, or if there's no user by the old name.
+/// Returns the number of rows updated.
+pub async fn rename_user(pool: &SqlitePool, config: &ValidationConfig, from: &str, to: &str) -> Result<u64> {
+    if let Err(e) = UsernameValidator::validate(to, config) {
+        return Err(AppError::Invalid(format!("Can't rename to {}: {}", to, e)));
+    }
+
+    let mut tx = pool.begin().await?;
+    for table in USER_TABLES {
+        let taken: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {} WHERE user = ?1", table))
+            .bind(to)
+            .fetch_one(&mut *tx)
+            .await?;
+        if taken > 0 {
+            return Err(AppError::Other(format!("User {} already exists", to)));
+        }
+    }
+
+    let mut updated = 0;
+    for table in USER_TABLES {
+        updated += sqlx::query(&format!("UPDATE {} SET user = ?2 WHERE user = ?1", table))
+            .bind(from)
+            .bind(to)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    }
+    if updated == 0 {
+        return Err(AppError::Invalid(format!("There's no user named {}", from)));
+    }
+    tx.commit().await?;
+
+    info!("Renamed user {} to {} ({} rows)", from, to, updated);
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{get_all_splits, initialize_database};
+    use crate::config::WebhookSubscription;
+    use crate::stream::get_events_after;
+
+    async fn log_split(pool: &SqlitePool, user: &str, duration_ms: i32) {
+        sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, 0, 0, 0, ?2)")
+            .bind(user)
+            .bind(duration_ms)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_operations() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        log_split(&pool, "alice", 30_000).await;
+        log_split(&pool, "bob", 31_000).await;
+
+        let webhooks = WebhooksConfig {
+            subscriptions: vec![WebhookSubscription {
+                url: "http://127.0.0.1:1/hook".to_string(),
+                secret: "s3cret".to_string(),
+                events: WebhookEvent::all(),
+            }],
+            ..Default::default()
+        };
+        assert!(hide_split(&pool, &webhooks, 1).await.unwrap());
+        assert!(!hide_split(&pool, &webhooks, 1).await.unwrap());
+        assert_eq!(get_all_splits(&pool).await.unwrap().len(), 1);
+        assert_eq!(get_hidden_splits(&pool).await.unwrap()[0].user, "alice");
+
+        assert!(unhide_split(&pool, &webhooks, 1).await.unwrap());
+        assert_eq!(get_all_splits(&pool).await.unwrap().iter().filter(|s| s.id == 1).count(), 1);
+
+        let validation = ValidationConfig::default();
+        assert!(rename_user(&pool, &validation, "alice", "bob").await.is_err());
+        assert!(matches!(rename_user(&pool, &validation, "alice", " ").await, Err(AppError::Invalid(_))));
+        assert!(matches!(rename_user(&pool, &validation, "nobody", "dave").await, Err(AppError::Invalid(_))));
+        assert_eq!(rename_user(&pool, &validation, "alice", "carol").await.unwrap(), 1);
+
+        assert!(hide_split(&pool, &webhooks, 2).await.unwrap());
+        assert!(delete_split(&pool, &webhooks, 2).await.unwrap());
+        assert!(!delete_split(&pool, &webhooks, 2).await.unwrap());
+        assert!(get_hidden_splits(&pool).await.unwrap().is_empty());
+        assert_eq!(get_all_splits(&pool).await.unwrap()[0].user, "carol");
+
+        // Webhook subscribers hear about every change, queued along with it
+        let queued: Vec<String> = sqlx::query_scalar("SELECT event FROM webhook_outbox ORDER BY id").fetch_all(&pool).await.unwrap();
+        assert_eq!(queued, vec!["split.deleted", "split.restored", "split.deleted", "split.deleted"]);
+
+        // Live feed clients see each change, including ones made from the CLI
+        let events: Vec<_> = get_events_after(&pool, 0, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| (event.kind, event.data["split"]["id"].as_i64().unwrap()))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (StreamEventKind::SplitDeleted, 1),
+                (StreamEventKind::SplitRestored, 1),
+                (StreamEventKind::SplitDeleted, 2),
+                (StreamEventKind::SplitDeleted, 2),
+            ]
+        );
+    }
+}
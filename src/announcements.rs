@@ -0,0 +1,368 @@
+use crate::config::{Config, LiveConfig};
+use crate::error::{AppError, Result};
+use crate::models::{AppContext, Split};
+use crate::notify::{SplitEvent, build_split_event, find_notifier, notifier_keys};
+use crate::period::TIMESTAMP_FORMAT;
+use crate::webhooks::backoff;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::{error, info, warn};
+
+/// How often the queue is checked for due announcements
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most announcements looked at in one pass over the queue
+const BATCH_SIZE: i64 = 100;
+
+/// Wait for a notifier that isn't available yet, such as the bot before it connects
+const UNAVAILABLE_RETRY: Duration = Duration::from_secs(5);
+
+/// How long a queued job may wait for its announcement before the worker builds one itself
+const PREPARE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Queue a job for every enabled notifier. Called in the transaction inserting the split,
+/// so the jobs exist as soon as the split does. Returns the number of jobs queued.
+pub async fn queue_announcements(conn: &mut SqliteConnection, config: &Config, split_id: i32) -> Result<usize> {
+    let keys = notifier_keys(config);
+    for key in &keys {
+        sqlx::query("INSERT INTO announcement_jobs (notifier, split_id) VALUES (?1, ?2)")
+            .bind(key)
+            .bind(split_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(keys.len())
+}
+
+/// Fill in the announcement for a split's queued jobs, ready to be sent
+pub async fn prepare_announcements(pool: &SqlitePool, event: &SplitEvent) -> Result<()> {
+    let payload = serde_json::to_string(event).map_err(|e| AppError::Other(e.to_string()))?;
+    sqlx::query("UPDATE announcement_jobs SET payload = ?2, status = 'pending' WHERE split_id = ?1 AND status = 'preparing'")
+        .bind(event.split.id)
+        .bind(payload)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Build the announcement for jobs whose split was logged but never announced, such as
+/// after a crash. Achievements and rating changes aren't known by then and are left out.
+async fn prepare_stale_jobs(pool: &SqlitePool, now: OffsetDateTime) -> Result<()> {
+    let cutoff = (now - PREPARE_TIMEOUT).format(TIMESTAMP_FORMAT).map_err(|e| AppError::Other(e.to_string()))?;
+    let split_ids: Vec<i32> = sqlx::query_scalar(
+        "SELECT DISTINCT split_id FROM announcement_jobs WHERE status = 'preparing' AND created_at <= ?1"
+    )
+    .bind(cutoff)
+    .fetch_all(pool)
+    .await?;
+
+    for split_id in split_ids {
+        let row = sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits WHERE id = ?1")
+            .bind(split_id)
+            .fetch_optional(pool)
+            .await?;
+        let Some(row) = row else {
+            sqlx::query("UPDATE announcement_jobs SET status = 'failed', last_error = 'Split no longer exists' WHERE split_id = ?1 AND status = 'preparing'")
+                .bind(split_id)
+                .execute(pool)
+                .await?;
+            continue;
+        };
+        let split = Split {
+            id: row.get(0),
+            user: row.get(1),
+            is_down: row.get(2),
+            is_elevator: row.get(3),
+            is_encumbered: row.get(4),
+            duration_ms: row.get(5),
+            created_at: row.get(6),
+        };
+        prepare_announcements(pool, &build_split_event(pool, split, Vec::new(), None).await?).await?;
+        warn!("Prepared the announcement for split {}, which was left unannounced", split_id);
+    }
+    Ok(())
+}
+
+/// Run announcements due at `now`. Each notifier gets its announcements in the order
+/// they were queued, so one waiting to be retried holds back the later ones but not
+/// other notifiers'. Returns how many were attempted.
+pub async fn run_due_announcements(
+    pool: &SqlitePool,
+    config: &Config,
+    #[cfg(feature = "discord")] discord_http: Option<std::sync::Arc<serenity::http::Http>>,
+    now: OffsetDateTime,
+) -> Result<usize> {
+    let format = |at: OffsetDateTime| at.format(TIMESTAMP_FORMAT).map_err(|e| AppError::Other(e.to_string()));
+    prepare_stale_jobs(pool, now).await?;
+    let keys: Vec<String> = sqlx::query_scalar("SELECT DISTINCT notifier FROM announcement_jobs WHERE status = 'pending'")
+        .fetch_all(pool)
+        .await?;
+
+    let mut attempted = 0;
+    for key in keys {
+        // A job still being prepared keeps its place in line
+        let rows = sqlx::query(
+            "SELECT id, status, payload, attempts, run_at FROM announcement_jobs
+             WHERE notifier = ?1 AND status IN ('preparing', 'pending') ORDER BY id ASC LIMIT ?2"
+        )
+        .bind(&key)
+        .bind(BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+
+        for row in rows {
+            let (id, status, payload, run_at): (i64, String, Option<String>, String) = (row.get(0), row.get(1), row.get(2), row.get(4));
+            if status == "preparing" || run_at > format(now)? {
+                break;
+            }
+
+            let Some(notifier) = find_notifier(
+                config,
+                &key,
+                #[cfg(feature = "discord")]
+                discord_http.clone(),
+            ) else {
+                if notifier_keys(config).contains(&key) {
+                    // Configured but not ready, try again shortly without using up an attempt
+                    sqlx::query("UPDATE announcement_jobs SET run_at = ?2 WHERE id = ?1")
+                        .bind(id)
+                        .bind(format(now + UNAVAILABLE_RETRY)?)
+                        .execute(pool)
+                        .await?;
+                    break;
+                }
+                sqlx::query("UPDATE announcement_jobs SET status = 'failed', last_error = 'Notifier no longer configured' WHERE id = ?1")
+                    .bind(id)
+                    .execute(pool)
+                    .await?;
+                warn!("Dropped announcement {} for {}, which is no longer configured", id, key);
+                continue;
+            };
+
+            attempted += 1;
+            let event: SplitEvent = match serde_json::from_str(payload.as_deref().unwrap_or_default()) {
+                Ok(event) => event,
+                Err(e) => {
+                    error!("Dropped announcement {} with an unreadable payload: {}", id, e);
+                    sqlx::query("UPDATE announcement_jobs SET status = 'failed', last_error = ?2 WHERE id = ?1")
+                        .bind(id)
+                        .bind(e.to_string())
+                        .execute(pool)
+                        .await?;
+                    continue;
+                }
+            };
+
+            let attempts = row.get::<u32, _>(3) + 1;
+            match notifier.notify(&event).await {
+                Ok(()) => {
+                    sqlx::query("UPDATE announcement_jobs SET status = 'done', attempts = ?2, last_error = NULL, finished_at = ?3 WHERE id = ?1")
+                        .bind(id)
+                        .bind(attempts)
+                        .bind(format(now)?)
+                        .execute(pool)
+                        .await?;
+                    info!("Announced split {} via {}", event.split.id, notifier.name());
+                }
+                Err(AppError::RateLimited(retry_after)) => {
+                    // Being told to wait isn't a failure, so it doesn't use up an attempt
+                    sqlx::query("UPDATE announcement_jobs SET run_at = ?2, last_error = 'Rate limited' WHERE id = ?1")
+                        .bind(id)
+                        .bind(format(now + retry_after)?)
+                        .execute(pool)
+                        .await?;
+                    warn!("{} is rate limited, waiting {:?} before announcing split {}", notifier.name(), retry_after, event.split.id);
+                    break;
+                }
+                Err(e) => {
+                    let gave_up = attempts >= config.notifiers.max_attempts;
+                    let retry_at = now + backoff(config.notifiers.backoff_secs, config.notifiers.max_backoff_secs, attempts);
+                    sqlx::query("UPDATE announcement_jobs SET status = ?2, attempts = ?3, last_error = ?4, run_at = ?5 WHERE id = ?1")
+                        .bind(id)
+                        .bind(if gave_up { "failed" } else { "pending" })
+                        .bind(attempts)
+                        .bind(e.to_string())
+                        .bind(format(retry_at)?)
+                        .execute(pool)
+                        .await?;
+                    if gave_up {
+                        error!("Giving up announcing split {} via {} after {} attempts: {}", event.split.id, notifier.name(), attempts, e);
+                    } else {
+                        warn!("Error announcing split {} via {}, retrying at {}: {}", event.split.id, notifier.name(), format(retry_at)?, e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(attempted)
+}
+
+/// Work through queued announcements as they come due
+pub async fn run_announcement_worker(context: AppContext, config: LiveConfig) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let result = run_due_announcements(
+            &context.db_pool,
+            &config.get(),
+            #[cfg(feature = "discord")]
+            context.discord_http(),
+            OffsetDateTime::now_utc(),
+        )
+        .await;
+        if let Err(e) = result {
+            error!("Error running announcements: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NotifierTarget;
+    use crate::database::initialize_database;
+    use crate::handlers::spawn_test_server;
+    use crate::notify::SplitEventKind;
+    use axum::Router;
+    use axum::body::Bytes;
+    use axum::http::{StatusCode, header};
+    use axum::response::IntoResponse;
+    use std::sync::{Arc, Mutex};
+
+    /// Stand-in Slack that records what it's sent, rate limiting the first request if asked to
+    async fn slack(rate_limited: bool) -> (String, Arc<Mutex<Vec<String>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        let limited = Arc::new(Mutex::new(!rate_limited));
+        let app = Router::new().fallback(move |body: Bytes| {
+            let (log, limited) = (log.clone(), limited.clone());
+            async move {
+                if !std::mem::replace(&mut *limited.lock().unwrap(), true) {
+                    return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "2")]).into_response();
+                }
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                log.lock().unwrap().push(body["text"].as_str().unwrap().to_string());
+                StatusCode::OK.into_response()
+            }
+        });
+        (format!("{}/slack", spawn_test_server(app).await), received)
+    }
+
+    fn event(id: i32, user: &str) -> SplitEvent {
+        SplitEvent {
+            kind: SplitEventKind::Split,
+            split: Split {
+                id,
+                user: user.to_string(),
+                is_down: false,
+                is_elevator: true,
+                is_encumbered: None,
+                duration_ms: 40_000,
+                created_at: "2025-09-01 12:00:00".to_string(),
+            },
+            category: "up-elevator".to_string(),
+            is_world_record: false,
+            is_personal_best: false,
+            season_record: None,
+            percentile: None,
+            rating_change: None,
+            achievements: Vec::new(),
+        }
+    }
+
+    async fn announce(pool: &SqlitePool, config: &Config, event: &SplitEvent) -> usize {
+        let queued = queue_announcements(&mut pool.acquire().await.unwrap(), config, event.split.id).await.unwrap();
+        prepare_announcements(pool, event).await.unwrap();
+        queued
+    }
+
+    async fn count(pool: &SqlitePool, status: &str) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM announcement_jobs WHERE status = ?1")
+            .bind(status)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_announcement_queue() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let (url, received) = slack(true).await;
+        let mut config = Config::default();
+        config.discord.enabled = false;
+        config.notifiers.max_attempts = 2;
+        config.notifiers.targets = vec![
+            NotifierTarget::Slack { url },
+            NotifierTarget::DiscordWebhook { url: "http://127.0.0.1:1/down".to_string() },
+        ];
+        let run = |now: OffsetDateTime| {
+            run_due_announcements(
+                &pool,
+                &config,
+                #[cfg(feature = "discord")]
+                None,
+                now,
+            )
+        };
+
+        assert_eq!(announce(&pool, &config, &event(1, "alice")).await, 2);
+        announce(&pool, &config, &event(2, "bob")).await;
+
+        // Slack is rate limited on alice's split, which holds bob's back; the unreachable webhook backs off
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(run(now).await.unwrap(), 2);
+        assert!(received.lock().unwrap().is_empty());
+        assert_eq!(run(now + time::Duration::seconds(1)).await.unwrap(), 0);
+
+        // After Retry-After both go out in order, and the webhook gives up on alice's split
+        assert_eq!(run(now + time::Duration::seconds(6)).await.unwrap(), 4);
+        assert_eq!(received.lock().unwrap().len(), 2);
+        assert!(received.lock().unwrap()[0].starts_with("alice"));
+        assert!(received.lock().unwrap()[1].starts_with("bob"));
+        assert_eq!((count(&pool, "done").await, count(&pool, "failed").await), (2, 1));
+        assert_eq!(run(now + time::Duration::seconds(12)).await.unwrap(), 1);
+        assert_eq!(count(&pool, "failed").await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_notifier_backlog() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let mut config = Config::default();
+        config.discord.enabled = false;
+        config.notifiers.targets = vec![NotifierTarget::DiscordWebhook { url: "http://127.0.0.1:1/down".to_string() }];
+        for id in 0..BATCH_SIZE as i32 + 10 {
+            announce(&pool, &config, &event(id, "alice")).await;
+        }
+
+        // A notifier added later isn't stuck behind the unreachable one's backlog
+        let (url, received) = slack(false).await;
+        config.notifiers.targets.push(NotifierTarget::Slack { url });
+        announce(&pool, &config, &event(1000, "bob")).await;
+        let run = || {
+            run_due_announcements(
+                &pool,
+                &config,
+                #[cfg(feature = "discord")]
+                None,
+                OffsetDateTime::now_utc(),
+            )
+        };
+        assert_eq!(run().await.unwrap(), 2);
+        assert!(received.lock().unwrap()[0].starts_with("bob"));
+
+        // Nor is a split still being prepared, which only holds back its own notifier's later jobs
+        let mut conn = pool.acquire().await.unwrap();
+        queue_announcements(&mut conn, &config, 1001).await.unwrap();
+        announce(&pool, &config, &event(1002, "carol")).await;
+        assert_eq!(run().await.unwrap(), 0);
+        prepare_announcements(&pool, &event(1001, "dave")).await.unwrap();
+        assert_eq!(run().await.unwrap(), 2);
+        let received = received.lock().unwrap();
+        assert!(received[1].starts_with("dave") && received[2].starts_with("carol"));
+    }
+}
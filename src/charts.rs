@@ -0,0 +1,247 @@
+use crate::database::{get_user_category_splits, get_world_records};
+use crate::error::{AppError, Result};
+use crate::models::{Category, Split};
+use crate::validation::DurationValidator;
+use resvg::{tiny_skia, usvg};
+use sqlx::SqlitePool;
+use std::fmt::Write;
+use std::sync::{Arc, OnceLock};
+
+pub(crate) const WIDTH: f64 = 800.0;
+pub(crate) const HEIGHT: f64 = 400.0;
+pub(crate) const MARGIN_LEFT: f64 = 80.0;
+pub(crate) const MARGIN_RIGHT: f64 = 24.0;
+pub(crate) const MARGIN_TOP: f64 = 48.0;
+pub(crate) const MARGIN_BOTTOM: f64 = 48.0;
+pub(crate) const FONT: &str = "font-family=\"DejaVu Sans, Arial, sans-serif\"";
+
+/// Escape text for use inside SVG markup
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Maps durations to vertical pixel positions within the plot area
+pub(crate) struct YScale {
+    min: f64,
+    max: f64,
+}
+
+impl YScale {
+    /// Scale covering every value with some padding, so lines don't hug the edges
+    pub(crate) fn covering(values: impl Iterator<Item = i32>) -> Self {
+        let (min, max) = values.fold((f64::MAX, f64::MIN), |(min, max), v| (min.min(v as f64), max.max(v as f64)));
+        let padding = ((max - min) * 0.1).max(500.0);
+        YScale { min: (min - padding).max(0.0), max: max + padding }
+    }
+
+    pub(crate) fn y(&self, duration_ms: f64) -> f64 {
+        let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+        MARGIN_TOP + plot_height * (1.0 - (duration_ms - self.min) / (self.max - self.min))
+    }
+
+    /// Horizontal grid lines with duration labels
+    pub(crate) fn grid(&self) -> String {
+        let mut grid = String::new();
+        for step in 0..=4 {
+            let value = self.min + (self.max - self.min) * step as f64 / 4.0;
+            let y = self.y(value);
+            let _ = write!(
+                grid,
+                "<line x1=\"{:.1}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"#e5e7eb\"/>\
+                 <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"12\" text-anchor=\"end\" fill=\"#4b5563\" {FONT}>{}</text>",
+                MARGIN_LEFT,
+                WIDTH - MARGIN_RIGHT,
+                MARGIN_LEFT - 8.0,
+                y + 4.0,
+                DurationValidator::format_duration(value.round() as i32)
+            );
+        }
+        grid
+    }
+}
+
+/// Opening tag, background and title shared by every chart
+pub(crate) fn chart_header(title: &str) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" viewBox=\"0 0 {WIDTH} {HEIGHT}\">\
+         <rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\
+         <text x=\"{:.1}\" y=\"28\" font-size=\"18\" font-weight=\"bold\" text-anchor=\"middle\" fill=\"#111827\" {FONT}>{}</text>",
+        WIDTH / 2.0,
+        escape_xml(title)
+    )
+}
+
+/// Chart with only a title and a centered message
+fn empty_chart(title: &str, message: &str) -> String {
+    format!(
+        "{}<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"16\" text-anchor=\"middle\" fill=\"#6b7280\" {FONT}>{}</text></svg>",
+        chart_header(title),
+        WIDTH / 2.0,
+        HEIGHT / 2.0,
+        escape_xml(message)
+    )
+}
+
+/// Render a line chart of a user's times in a category, oldest run first,
+/// with their PB progression and the current world record
+pub fn render_progress_chart(user: &str, category: Category, splits: &[Split], world_record: Option<&Split>) -> String {
+    let title = format!("{} - {}", user, category.name());
+    if splits.is_empty() {
+        return empty_chart(&title, "No runs yet");
+    }
+
+    let scale = YScale::covering(splits.iter().chain(world_record).map(|s| s.duration_ms));
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let x = |index: usize| {
+        if splits.len() == 1 {
+            MARGIN_LEFT + plot_width / 2.0
+        } else {
+            MARGIN_LEFT + plot_width * index as f64 / (splits.len() - 1) as f64
+        }
+    };
+
+    let mut svg = chart_header(&title);
+    svg.push_str(&scale.grid());
+
+    // Every run
+    let points: Vec<String> = splits
+        .iter()
+        .enumerate()
+        .map(|(i, s)| format!("{:.1},{:.1}", x(i), scale.y(s.duration_ms as f64)))
+        .collect();
+    let _ = write!(svg, "<polyline points=\"{}\" fill=\"none\" stroke=\"#9ca3af\" stroke-width=\"1.5\"/>", points.join(" "));
+    for point in &points {
+        let (cx, cy) = point.split_once(',').unwrap_or_default();
+        let _ = write!(svg, "<circle cx=\"{cx}\" cy=\"{cy}\" r=\"3\" fill=\"#6b7280\"/>");
+    }
+
+    // PB progression as a step line that only ever goes down
+    let mut best = i32::MAX;
+    let mut pb_points = Vec::new();
+    for (i, split) in splits.iter().enumerate() {
+        if split.duration_ms < best {
+            if best != i32::MAX {
+                pb_points.push(format!("{:.1},{:.1}", x(i), scale.y(best as f64)));
+            }
+            best = split.duration_ms;
+        }
+        pb_points.push(format!("{:.1},{:.1}", x(i), scale.y(best as f64)));
+    }
+    let _ = write!(svg, "<polyline points=\"{}\" fill=\"none\" stroke=\"#16a34a\" stroke-width=\"2.5\"/>", pb_points.join(" "));
+
+    // World record
+    if let Some(wr) = world_record {
+        let y = scale.y(wr.duration_ms as f64);
+        let _ = write!(
+            svg,
+            "<line x1=\"{MARGIN_LEFT}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"#dc2626\" stroke-width=\"1.5\" stroke-dasharray=\"6 4\"/>\
+             <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"12\" text-anchor=\"end\" fill=\"#dc2626\" {FONT}>WR {} by {}</text>",
+            WIDTH - MARGIN_RIGHT,
+            WIDTH - MARGIN_RIGHT,
+            y - 6.0,
+            DurationValidator::format_duration(wr.duration_ms),
+            escape_xml(&wr.user)
+        );
+    }
+
+    // Date range along the bottom and a legend
+    let date = |split: &Split| split.created_at.split(' ').next().unwrap_or_default().to_string();
+    let _ = write!(
+        svg,
+        "<text x=\"{MARGIN_LEFT}\" y=\"{:.1}\" font-size=\"12\" fill=\"#4b5563\" {FONT}>{}</text>\
+         <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"12\" text-anchor=\"end\" fill=\"#4b5563\" {FONT}>{}</text>\
+         <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"12\" text-anchor=\"middle\" {FONT}>\
+         <tspan fill=\"#6b7280\">● runs</tspan> <tspan fill=\"#16a34a\">━ PB</tspan> <tspan fill=\"#dc2626\">┅ WR</tspan></text>",
+        HEIGHT - MARGIN_BOTTOM + 20.0,
+        escape_xml(&date(&splits[0])),
+        WIDTH - MARGIN_RIGHT,
+        HEIGHT - MARGIN_BOTTOM + 20.0,
+        escape_xml(&date(&splits[splits.len() - 1])),
+        WIDTH / 2.0,
+        HEIGHT - 12.0
+    );
+
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Load a user's history in a category and render their progress chart
+pub async fn build_progress_chart(pool: &SqlitePool, user: &str, category: Category) -> Result<String> {
+    let splits = get_user_category_splits(pool, user, category).await?;
+    let world_records = get_world_records(pool).await?;
+    let world_record = world_records.iter().find(|wr| Category::of(wr) == category);
+    Ok(render_progress_chart(user, category, &splits, world_record))
+}
+
+/// System fonts, loaded once since scanning them is slow
+fn font_database() -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut fonts = usvg::fontdb::Database::new();
+            fonts.load_system_fonts();
+            Arc::new(fonts)
+        })
+        .clone()
+}
+
+/// Rasterize an SVG to PNG so chat clients display it inline
+pub fn render_png(svg: &str) -> Result<Vec<u8>> {
+    let options = usvg::Options {
+        fontdb: font_database(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| AppError::Other(format!("Invalid chart SVG: {}", e)))?;
+
+    let size = tree.size().to_int_size();
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| AppError::Other("Chart has no area to render".to_string()))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+    pixmap.encode_png().map_err(|e| AppError::Other(format!("Failed to encode chart: {}", e)))
+}
+
+/// Rasterize on the blocking thread pool, since it takes long enough to hold up other requests
+pub async fn spawn_render_png(svg: String) -> Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || render_png(&svg))
+        .await
+        .map_err(|e| AppError::Other(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(id: i32, user: &str, duration_ms: i32) -> Split {
+        Split {
+            id,
+            user: user.to_string(),
+            is_down: false,
+            is_elevator: false,
+            is_encumbered: Some(false),
+            duration_ms,
+            created_at: format!("2025-09-0{} 12:00:00", id),
+        }
+    }
+
+    #[test]
+    fn test_render_progress_chart() {
+        let category = Category::ALL[5];
+        let splits = vec![split(1, "a<b", 40_000), split(2, "a<b", 45_000), split(3, "a<b", 35_000)];
+        let wr = split(4, "bob", 30_000);
+
+        let svg = render_progress_chart("a<b", category, &splits, Some(&wr));
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+        assert!(svg.contains("a&lt;b - Up Stairs (No Items)"));
+        assert!(svg.contains("WR 30.000s by bob"));
+        assert_eq!(svg.matches("<circle").count(), 3);
+
+        let png = render_png(&svg).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+
+        assert!(render_progress_chart("a", category, &[], None).contains("No runs yet"));
+    }
+}
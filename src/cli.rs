@@ -0,0 +1,159 @@
+use crate::admin::{delete_split, get_hidden_splits, hide_split, rename_user, unhide_split};
+use crate::csv_io::{SplitFilter, format_import_report, get_filtered_splits, import_csv, write_csv};
+use crate::config::Config;
+use crate::database::format_splits;
+use crate::error::{AppError, Result};
+use crate::models::Category;
+use crate::period::Period;
+use crate::ratings::recompute_ratings;
+use crate::seasons::recompute_season_standings;
+use clap::{Parser, Subcommand};
+use sqlx::SqlitePool;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Track stair and elevator splits, with a Discord bot and web API
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Read the configuration from this file instead of ./config.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Check that the configuration loads and is valid, then exit
+    #[arg(long)]
+    pub check_config: bool,
+    /// Print the default configuration as TOML, then exit
+    #[arg(long)]
+    pub print_default_config: bool,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+/// Maintenance commands, run instead of starting the server
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// List splits, oldest first
+    List {
+        /// Only list this user's splits
+        #[arg(long)]
+        user: Option<String>,
+        /// Only list splits by users whose name contains this text
+        #[arg(long)]
+        search: Option<String>,
+        /// Only list this category, such as up-stairs-empty
+        #[arg(long, value_parser = parse_category)]
+        category: Option<Category>,
+        /// Only list the most recent splits
+        #[arg(long)]
+        limit: Option<usize>,
+        /// List hidden splits instead
+        #[arg(long)]
+        hidden: bool,
+    },
+    /// Hide a split from leaderboards, stats and exports without deleting it
+    Hide { id: i32 },
+    /// Restore a hidden split
+    Unhide { id: i32 },
+    /// Permanently delete a split
+    Delete { id: i32 },
+    /// Rename a user across all of their splits, badges, ratings and links
+    RenameUser { from: String, to: String },
+    /// Rebuild ratings and archived season standings from the current splits
+    RecomputeRecords,
+    /// Create or upgrade the database tables, then exit
+    Migrate,
+    /// Write a sample configuration file with every option at its default
+    GenerateConfig {
+        #[arg(long, short, default_value = "config.toml.example")]
+        output: PathBuf,
+    },
+    /// Export splits as CSV
+    Export {
+        /// Only export this user's splits
+        #[arg(long)]
+        user: Option<String>,
+        /// Only export this category, such as up-stairs-empty
+        #[arg(long, value_parser = parse_category)]
+        category: Option<Category>,
+        /// Only export this period: all, today, week or month
+        #[arg(long)]
+        period: Option<String>,
+        /// Start of a custom period (YYYY-MM-DD)
+        #[arg(long)]
+        from: Option<String>,
+        /// End of a custom period, inclusive (YYYY-MM-DD)
+        #[arg(long)]
+        to: Option<String>,
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Import splits from CSV in the export's format
+    Import {
+        file: PathBuf,
+        /// Report what would be imported without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+fn parse_category(slug: &str) -> std::result::Result<Category, String> {
+    Category::from_slug(slug).ok_or_else(|| {
+        let slugs: Vec<String> = Category::ALL.iter().map(|c| c.slug()).collect();
+        format!("unknown category, expected one of {}", slugs.join(", "))
+    })
+}
+
+/// Run a maintenance command against the database
+pub async fn run_command(command: CliCommand, pool: &SqlitePool, config: &Config) -> Result<()> {
+    match command {
+        CliCommand::List { user, search, category, limit, hidden } => {
+            let mut splits = if hidden {
+                get_hidden_splits(pool).await?
+            } else {
+                get_filtered_splits(pool, &SplitFilter { user, category, search, ..Default::default() }).await?
+            };
+            if let Some(limit) = limit {
+                splits.drain(..splits.len().saturating_sub(limit));
+            }
+            println!("{}", format_splits(&splits));
+        }
+        CliCommand::Hide { id } => report_split_change(hide_split(pool, &config.webhooks, id).await?, id, "Hid")?,
+        CliCommand::Unhide { id } => report_split_change(unhide_split(pool, &config.webhooks, id).await?, id, "Unhid")?,
+        CliCommand::Delete { id } => report_split_change(delete_split(pool, &config.webhooks, id).await?, id, "Deleted")?,
+        CliCommand::RenameUser { from, to } => {
+            let updated = rename_user(pool, &config.validation, &from, &to).await?;
+            println!("Renamed {} to {} ({} rows updated)", from, to, updated);
+        }
+        CliCommand::RecomputeRecords => {
+            let splits = recompute_ratings(pool, &config.ratings).await?;
+            let seasons = recompute_season_standings(pool).await?;
+            println!("Replayed {} splits into ratings and rebuilt {} season standings", splits, seasons);
+        }
+        CliCommand::Migrate => println!("Database is up to date"),
+        CliCommand::GenerateConfig { output } => Config::generate_sample_config(&output)?,
+        CliCommand::Export { user, category, period, from, to, output } => {
+            let period = Period::parse(period.as_deref(), from.as_deref(), to.as_deref())
+                .map_err(|e| AppError::Other(format!("Invalid period: {}", e)))?;
+            let splits = get_filtered_splits(pool, &SplitFilter { user, category, period, ..Default::default() }).await?;
+            match output {
+                Some(path) => write_csv(&splits, File::create(path)?)?,
+                None => write_csv(&splits, io::stdout().lock())?,
+            }
+        }
+        CliCommand::Import { file, dry_run } => {
+            let report = import_csv(pool, &config.validation, File::open(file)?, dry_run).await?;
+            io::stdout().write_all(format_import_report(&report).as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn report_split_change(changed: bool, id: i32, verb: &str) -> Result<()> {
+    if !changed {
+        return Err(AppError::Other(format!("No split with id {}", id)));
+    }
+    println!("{} split {}. Run recompute-records to update ratings and archived seasons.", verb, id);
+    Ok(())
+}
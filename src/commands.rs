@@ -1,5 +1,19 @@
-use crate::database::{get_world_records, format_world_records};
+use crate::charts::{build_progress_chart, spawn_render_png};
+use crate::achievements::{format_badges, get_user_achievements};
+use crate::config::LiveConfig;
+use crate::error::AppError;
+use crate::histograms::{build_histogram, render_histogram};
+use crate::database::{format_board, get_slowest_records_for_period, get_world_records_for_period};
+use crate::models::Category;
+use crate::period::{Period, parse_date};
+use crate::ratings::{format_ratings, get_ratings};
+use crate::stats::{format_user_stats, get_user_stats};
+use crate::streaks::{link_discord_user, redeem_link_code, set_streak_reminders, unlink_discord_user};
+use crate::seasons::{create_season, get_active_season, get_season_by_name, get_season_standings};
+use crate::validation::DurationValidator;
+use crate::versus::{compare_users, format_versus};
 use sqlx::SqlitePool;
+use time::OffsetDateTime;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Context<'a> = poise::Context<'a, Data, Error>;
@@ -7,22 +21,99 @@ pub type Context<'a> = poise::Context<'a, Data, Error>;
 // User data passed to all command functions
 pub struct Data {
     pub db_pool: SqlitePool,
+    pub config: LiveConfig,
+}
+
+/// Time window choices offered by the board commands
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum PeriodChoice {
+    #[name = "This season"]
+    Season,
+    #[name = "All time"]
+    AllTime,
+    #[name = "Today"]
+    Today,
+    #[name = "This week"]
+    Week,
+    #[name = "This month"]
+    Month,
+}
+
+/// Category choices offered by commands
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum CategoryChoice {
+    #[name = "Down Elevator"]
+    DownElevator,
+    #[name = "Up Elevator"]
+    UpElevator,
+    #[name = "Down Stairs (Encumbered)"]
+    DownStairsEncumbered,
+    #[name = "Down Stairs (No Items)"]
+    DownStairsEmpty,
+    #[name = "Up Stairs (Encumbered)"]
+    UpStairsEncumbered,
+    #[name = "Up Stairs (No Items)"]
+    UpStairsEmpty,
+}
+
+impl From<CategoryChoice> for Category {
+    fn from(choice: CategoryChoice) -> Self {
+        // Same order as Category::ALL
+        Category::ALL[choice as usize]
+    }
+}
+
+/// Resolve the board command options into a period and a title for it.
+/// Without options this is the active season, falling back to all time.
+async fn resolve_period(
+    pool: &SqlitePool,
+    period: Option<PeriodChoice>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<(Period, String), Error> {
+    if from.is_some() || to.is_some() {
+        if period.is_some() {
+            return Err("Use either period or from/to, not both".into());
+        }
+        let period = Period::parse(Some("custom"), from.as_deref(), to.as_deref())?;
+        return Ok((period, period.to_string()));
+    }
+
+    let period = match period {
+        Some(PeriodChoice::Season) | None => {
+            match get_active_season(pool, OffsetDateTime::now_utc().date()).await? {
+                Some(season) => return Ok((season.period()?, format!("Season {}", season.name))),
+                None if period.is_some() => return Err("There is no active season".into()),
+                None => Period::AllTime,
+            }
+        }
+        Some(PeriodChoice::AllTime) => Period::AllTime,
+        Some(PeriodChoice::Today) => Period::Today,
+        Some(PeriodChoice::Week) => Period::Week,
+        Some(PeriodChoice::Month) => Period::Month,
+    };
+    Ok((period, period.to_string()))
 }
 
 /// Display the world records board showing the best time in each category
 #[poise::command(slash_command, rename = "wrboard")]
 pub async fn world_records_board(
     ctx: Context<'_>,
+    #[description = "Time window to rank (defaults to the active season)"] period: Option<PeriodChoice>,
+    #[description = "Start of a custom range (YYYY-MM-DD)"] from: Option<String>,
+    #[description = "End of a custom range, inclusive (YYYY-MM-DD)"] to: Option<String>,
 ) -> Result<(), Error> {
     // Defer the response since database queries might take a moment
     ctx.defer().await?;
 
+    let (period, title) = resolve_period(&ctx.data().db_pool, period, from, to).await?;
+
     // Get world records from the database
-    let world_records = get_world_records(&ctx.data().db_pool).await
+    let world_records = get_world_records_for_period(&ctx.data().db_pool, &period).await
         .map_err(|e| format!("Database error: {}", e))?;
 
     // Format the world records for display
-    let response = format_world_records(&world_records);
+    let response = format_board(&format!("World Records Board ({})", title), &world_records);
 
     // Send the response
     ctx.send(poise::CreateReply::default().content(response)).await?;
@@ -34,19 +125,249 @@ pub async fn world_records_board(
 #[poise::command(slash_command, rename = "slowboard")]
 pub async fn slowest_board(
     ctx: Context<'_>,
+    #[description = "Time window to rank (defaults to the active season)"] period: Option<PeriodChoice>,
+    #[description = "Start of a custom range (YYYY-MM-DD)"] from: Option<String>,
+    #[description = "End of a custom range, inclusive (YYYY-MM-DD)"] to: Option<String>,
 ) -> Result<(), Error> {
     ctx.defer().await?;
-    let slowest_records = crate::database::get_slowest_records(&ctx.data().db_pool).await
+    let (period, title) = resolve_period(&ctx.data().db_pool, period, from, to).await?;
+    let slowest_records = get_slowest_records_for_period(&ctx.data().db_pool, &period).await
         .map_err(|e| format!("Database error: {}", e))?;
-    let response = crate::database::format_world_records(&slowest_records);
+    let response = format_board(&format!("Slowest Board ({})", title), &slowest_records);
     ctx.send(poise::CreateReply::default().content(response)).await?;
     Ok(())
 }
 
+/// Manage and view competitive seasons
+#[poise::command(slash_command, subcommands("season_create", "season_current", "season_standings"))]
+pub async fn season(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Create a new season (admin only)
+#[poise::command(
+    slash_command,
+    rename = "create",
+    default_member_permissions = "MANAGE_GUILD",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn season_create(
+    ctx: Context<'_>,
+    #[description = "Season name, e.g. 2025 Q4"] name: String,
+    #[description = "First day of the season (YYYY-MM-DD)"] starts_on: String,
+    #[description = "Last day of the season (YYYY-MM-DD)"] ends_on: String,
+) -> Result<(), Error> {
+    let starts_on = parse_date(&starts_on)?;
+    let ends_on = parse_date(&ends_on)?;
+    let season = create_season(&ctx.data().db_pool, &name, starts_on, ends_on).await?;
+    ctx.say(format!("Created season **{}** ({} to {})", season.name, season.starts_on, season.ends_on)).await?;
+    Ok(())
+}
+
+/// Show the season currently running
+#[poise::command(slash_command, rename = "current")]
+pub async fn season_current(ctx: Context<'_>) -> Result<(), Error> {
+    let response = match get_active_season(&ctx.data().db_pool, OffsetDateTime::now_utc().date()).await? {
+        Some(season) => format!("Season **{}** runs from {} to {}", season.name, season.starts_on, season.ends_on),
+        None => "There is no active season.".to_string(),
+    };
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// Show the archived final standings of a season
+#[poise::command(slash_command, rename = "standings")]
+pub async fn season_standings(
+    ctx: Context<'_>,
+    #[description = "Season name"] name: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let pool = &ctx.data().db_pool;
+    let season = get_season_by_name(pool, &name).await?
+        .ok_or_else(|| format!("No season named {}", name))?;
+    if season.archived_at.is_none() {
+        ctx.say(format!("Season **{}** hasn't ended yet, try /wrboard.", season.name)).await?;
+        return Ok(());
+    }
+
+    let standings = get_season_standings(pool, season.id).await?;
+    let mut response = format!("**Season {} Final Standings:**\n", season.name);
+    for category in Category::ALL {
+        let podium: Vec<String> = standings
+            .iter()
+            .filter(|s| s.category() == category)
+            .take(3)
+            .map(|s| format!("{}. {} ({})", s.rank, s.user, DurationValidator::format_duration(s.duration_ms)))
+            .collect();
+        if !podium.is_empty() {
+            response.push_str(&format!("**{}**: {}\n", category.name(), podium.join(", ")));
+        }
+    }
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// Show the badges a user has unlocked
+#[poise::command(slash_command)]
+pub async fn badges(
+    ctx: Context<'_>,
+    #[description = "Username to show badges for"] user: String,
+) -> Result<(), Error> {
+    let config = ctx.data().config.get();
+    let achievements = &config.achievements;
+    let unlocked = get_user_achievements(&ctx.data().db_pool, achievements, &user).await?;
+    ctx.say(format_badges(&user, &unlocked, achievements.definitions.len())).await?;
+    Ok(())
+}
+
+/// Show a user's run count, streaks and personal bests
+#[poise::command(slash_command)]
+pub async fn stats(
+    ctx: Context<'_>,
+    #[description = "Username to show stats for"] user: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let stats = get_user_stats(&ctx.data().db_pool, &ctx.data().config.get(), &user).await?;
+    ctx.say(format_user_stats(&stats)).await?;
+    Ok(())
+}
+
+/// Link your Discord account to your runner name, using a code from the website
+#[poise::command(slash_command, ephemeral)]
+pub async fn link(
+    ctx: Context<'_>,
+    #[description = "Code from logging in on the website and asking for a Discord link code"] code: String,
+) -> Result<(), Error> {
+    let pool = &ctx.data().db_pool;
+    let response = match redeem_link_code(pool, code.trim()).await? {
+        Some(user) => match link_discord_user(pool, &user, ctx.author().id.get()).await {
+            Ok(()) => format!("Linked your account to {}. Use /reminders to get streak reminders.", user),
+            Err(AppError::AlreadyTaken(_)) => format!("{} is linked to another Discord account, which has to /unlink first.", user),
+            Err(e) => return Err(e.into()),
+        },
+        None => "That code is invalid or has expired. Get a new one from the website.".to_string(),
+    };
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// Unlink your Discord account from your runner name
+#[poise::command(slash_command, ephemeral)]
+pub async fn unlink(ctx: Context<'_>) -> Result<(), Error> {
+    let response = match unlink_discord_user(&ctx.data().db_pool, ctx.author().id.get()).await? {
+        Some(user) => format!("Unlinked your account from {}.", user),
+        None => "Your account isn't linked.".to_string(),
+    };
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// Turn evening reminders about an expiring streak on or off
+#[poise::command(slash_command, ephemeral)]
+pub async fn reminders(
+    ctx: Context<'_>,
+    #[description = "Whether to remind you before your streak breaks"] enabled: bool,
+) -> Result<(), Error> {
+    let response = match set_streak_reminders(&ctx.data().db_pool, ctx.author().id.get(), enabled).await? {
+        Some(user) if enabled => format!("Streak reminders enabled for {}.", user),
+        Some(user) => format!("Streak reminders disabled for {}.", user),
+        None => "Link your account with /link first.".to_string(),
+    };
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// Compare two users head to head across every category
+#[poise::command(slash_command)]
+pub async fn versus(
+    ctx: Context<'_>,
+    #[description = "First username"] user1: String,
+    #[description = "Second username"] user2: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let versus = match compare_users(&ctx.data().db_pool, &user1, &user2).await {
+        Ok(versus) => versus,
+        Err(AppError::Invalid(e)) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    ctx.say(format_versus(&versus)).await?;
+    Ok(())
+}
+
+/// Show the global skill rating ranking
+#[poise::command(slash_command)]
+pub async fn ratings(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer().await?;
+    let ratings = get_ratings(&ctx.data().db_pool).await?;
+    ctx.say(format_ratings(&ratings)).await?;
+    Ok(())
+}
+
+/// Chart a user's times in a category with their PB progression and the WR
+#[poise::command(slash_command)]
+pub async fn chart(
+    ctx: Context<'_>,
+    #[description = "Username to chart"] user: String,
+    #[description = "Category to chart"] category: CategoryChoice,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let svg = build_progress_chart(&ctx.data().db_pool, &user, category.into()).await?;
+    let png = spawn_render_png(svg).await?;
+    let attachment = serenity::all::CreateAttachment::bytes(png, "chart.png");
+    ctx.send(poise::CreateReply::default().attachment(attachment)).await?;
+    Ok(())
+}
+
+/// Show how everyone's times in a category are distributed
+#[poise::command(slash_command)]
+pub async fn histogram(
+    ctx: Context<'_>,
+    #[description = "Category to show"] category: CategoryChoice,
+    #[description = "Highlight this user's runs"] user: Option<String>,
+    #[description = "Bucket width in milliseconds"]
+    #[min = 1]
+    bucket_ms: Option<i32>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let histogram = match build_histogram(
+        &ctx.data().db_pool,
+        &ctx.data().config.get().histograms,
+        category.into(),
+        bucket_ms,
+        user.as_deref(),
+    )
+    .await
+    {
+        Ok(histogram) => histogram,
+        Err(AppError::Invalid(e)) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let png = spawn_render_png(render_histogram(&histogram, None)).await?;
+    let attachment = serenity::all::CreateAttachment::bytes(png, "histogram.png");
+    ctx.send(poise::CreateReply::default().attachment(attachment)).await?;
+    Ok(())
+}
+
 /// Register all slash commands
 pub fn commands() -> Vec<poise::Command<Data, Error>> {
     vec![
         world_records_board(),
         slowest_board(),
+        season(),
+        badges(),
+        stats(),
+        link(),
+        unlink(),
+        reminders(),
+        versus(),
+        ratings(),
+        chart(),
+        histogram(),
     ]
 }
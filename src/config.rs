@@ -1,24 +1,56 @@
+use crate::achievements::{AchievementDefinition, default_achievements};
+use crate::webhooks::WebhookEvent;
 use crate::error::{AppError, Result};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
 use std::path::Path;
+use std::sync::{Arc, RwLock};
 use tracing::{debug, error, info, warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
     pub discord: DiscordConfig,
     pub database: DatabaseConfig,
     pub server: ServerConfig,
     pub validation: ValidationConfig,
+    #[serde(default)]
+    pub seasons: SeasonsConfig,
+    #[serde(default)]
+    pub digest: DigestConfig,
+    #[serde(default)]
+    pub achievements: AchievementsConfig,
+    #[serde(default)]
+    pub streaks: StreaksConfig,
+    #[serde(default)]
+    pub ratings: RatingsConfig,
+    #[serde(default)]
+    pub histograms: HistogramsConfig,
+    #[serde(default)]
+    pub notifiers: NotifiersConfig,
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub dashboard: DashboardConfig,
+    #[serde(default)]
+    pub accounts: AccountsConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscordConfig {
+    /// Run the Discord bot. When false the token and channel are ignored.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
     pub token: String,
     pub channel_id: u64,
 }
 
+fn default_true() -> bool {
+    true
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
     pub url: String,
@@ -45,13 +77,324 @@ pub struct ValidationConfig {
     pub min_duration_ms: i32,
 }
 
-impl Default for Config {
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SeasonsConfig {
+    /// Automatically start a season for each calendar quarter
+    pub quarterly: bool,
+    /// Seasons to create on startup if they don't exist yet
+    pub definitions: Vec<SeasonDefinition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeasonDefinition {
+    pub name: String,
+    /// First day of the season (YYYY-MM-DD)
+    pub starts_on: String,
+    /// Last day of the season, inclusive (YYYY-MM-DD)
+    pub ends_on: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DigestConfig {
+    /// Digests posted to the Discord channel, each covering the time since it last ran
+    pub schedules: Vec<DigestSchedule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestSchedule {
+    /// Title of the digest, e.g. "Daily Digest"
+    pub name: String,
+    /// Five field cron expression evaluated in UTC, e.g. "0 18 * * 5"
+    pub cron: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AchievementsConfig {
+    /// Achievements evaluated after every split
+    pub definitions: Vec<AchievementDefinition>,
+}
+
+impl Default for AchievementsConfig {
+    fn default() -> Self {
+        Self {
+            definitions: default_achievements(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreaksConfig {
+    /// Offset from UTC in minutes of the timezone that decides which day a split counts for.
+    /// This is a fixed offset rather than a time zone, so it doesn't follow daylight saving time:
+    /// change it when the clocks change or reminders arrive an hour early or late.
+    pub utc_offset_minutes: i32,
+    /// Local hour (0-23) to remind users whose streak is about to break
+    pub reminder_hour: u8,
+    /// Whether to send streak reminders to users who opted in
+    pub reminders_enabled: bool,
+}
+
+impl Default for StreaksConfig {
+    fn default() -> Self {
+        Self {
+            utc_offset_minutes: 0,
+            reminder_hour: 20,
+            reminders_enabled: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatingsConfig {
+    /// Rating given to users before their first rated split
+    pub initial_rating: f64,
+    /// Maximum rating change from a single split
+    pub k_factor: f64,
+    /// Number of recent runs by others in the category each split is rated against
+    pub opponents: i64,
+}
+
+impl Default for RatingsConfig {
+    fn default() -> Self {
+        Self {
+            initial_rating: 1500.0,
+            k_factor: 32.0,
+            opponents: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistogramsConfig {
+    /// Default width of each histogram bucket in milliseconds
+    pub bucket_ms: i32,
+    /// Largest number of buckets a histogram may have, limiting tiny bucket widths
+    pub max_buckets: usize,
+}
+
+impl Default for HistogramsConfig {
+    fn default() -> Self {
+        Self {
+            bucket_ms: 1000,
+            max_buckets: 200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotifiersConfig {
+    /// Where new splits are announced, besides the Discord bot
+    pub targets: Vec<NotifierTarget>,
+    /// Attempts before an announcement is given up on
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubling after each failure
+    pub backoff_secs: u64,
+    /// Longest wait between retries
+    pub max_backoff_secs: u64,
+}
+
+impl Default for NotifiersConfig {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            max_attempts: 10,
+            backoff_secs: 5,
+            max_backoff_secs: 600,
+        }
+    }
+}
+
+/// A place to announce new splits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotifierTarget {
+    /// Discord incoming webhook
+    DiscordWebhook { url: String },
+    /// Slack incoming webhook, or any service accepting Slack's payload
+    Slack { url: String },
+    /// Matrix room, posted to as the user owning the access token
+    Matrix { homeserver: String, room_id: String, access_token: String },
+    /// JSON event posted to any URL, signed with HMAC-SHA256 when a secret is set
+    Webhook { url: String, secret: Option<String> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhooksConfig {
+    /// Endpoints that receive split events, delivered from a persistent outbox
+    pub subscriptions: Vec<WebhookSubscription>,
+    /// Attempts before a delivery is marked failed
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubling after each failure
+    pub backoff_secs: u64,
+    /// Longest wait between retries
+    pub max_backoff_secs: u64,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            subscriptions: Vec::new(),
+            max_attempts: 8,
+            backoff_secs: 30,
+            max_backoff_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    pub url: String,
+    /// Key for the HMAC-SHA256 signature sent with every delivery
+    pub secret: String,
+    /// Events to send, all of them when left out
+    #[serde(default = "WebhookEvent::all")]
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminConfig {
+    /// Bearer token for the admin API, which is disabled while empty
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DashboardConfig {
+    /// Heading shown across the top of the screen
+    pub title: String,
+    /// Boards to cycle through, in order
+    pub boards: Vec<DashboardBoard>,
+    /// Seconds each board stays on screen
+    pub rotate_secs: u32,
+    /// Runs listed per category on the today board
+    pub top_runs: usize,
+    pub theme: DashboardTheme,
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        Self {
+            title: "Splits".to_string(),
+            boards: vec![DashboardBoard::Records, DashboardBoard::Today, DashboardBoard::Verdict],
+            rotate_secs: 15,
+            top_runs: 3,
+            theme: DashboardTheme::Dark,
+        }
+    }
+}
+
+/// A screen the dashboard can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DashboardBoard {
+    /// World record of each category
+    Records,
+    /// Fastest runs logged today in each category
+    Today,
+    /// Whether the stairs or the elevator is faster, going up and down
+    Verdict,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DashboardTheme {
+    Dark,
+    Light,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountsConfig {
+    /// Accept splits from visitors who aren't logged in, under any name no account has claimed
+    pub allow_anonymous: bool,
+    /// Days a login lasts
+    pub session_days: u32,
+    /// Minutes a magic login link stays valid
+    pub magic_link_minutes: u32,
+    /// Address the server is reached at, which magic links point to
+    pub public_url: String,
+    /// Only send the session cookie over HTTPS
+    pub secure_cookies: bool,
+    pub password: PasswordConfig,
+    pub smtp: SmtpConfig,
+}
+
+impl Default for AccountsConfig {
+    fn default() -> Self {
+        Self {
+            allow_anonymous: true,
+            session_days: 30,
+            magic_link_minutes: 15,
+            public_url: "http://localhost:7758".to_string(),
+            secure_cookies: false,
+            password: PasswordConfig::default(),
+            smtp: SmtpConfig::default(),
+        }
+    }
+}
+
+/// Argon2id cost of new password hashes. Existing hashes keep the cost they were made with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        // OWASP's recommended minimum for Argon2id
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl PasswordConfig {
+    /// The Argon2 parameters new hashes are made with. Fails if they're out of range.
+    pub fn params(&self) -> Result<argon2::Params> {
+        argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| AppError::Config(format!("Invalid Argon2 parameters: {}", e)))
+    }
+}
+
+/// Mail server magic links are sent through
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    /// Magic links are disabled while empty
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    /// Sender address of login emails
+    pub from: String,
+    /// Login for the mail server, skipped while empty. Requires `tls`.
+    pub username: String,
+    pub password: String,
+}
+
+/// How the connection to the mail server is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    /// Plain SMTP, only for a trusted local relay
+    None,
+    /// Upgrade to TLS with STARTTLS, usually on port 587
+    #[default]
+    Starttls,
+    /// TLS from the start, usually on port 465
+    Tls,
+}
+
+impl Default for SmtpConfig {
     fn default() -> Self {
         Self {
-            discord: DiscordConfig::default(),
-            database: DatabaseConfig::default(),
-            server: ServerConfig::default(),
-            validation: ValidationConfig::default(),
+            host: String::new(),
+            port: 587,
+            tls: SmtpTls::Starttls,
+            from: "splits@localhost".to_string(),
+            username: String::new(),
+            password: String::new(),
         }
     }
 }
@@ -59,6 +402,7 @@ impl Default for Config {
 impl Default for DiscordConfig {
     fn default() -> Self {
         Self {
+            enabled: true,
             token: "YOUR_TOKEN_HERE".to_string(),
             channel_id: 1234567890123456789,
         }
@@ -95,40 +439,223 @@ impl Default for ValidationConfig {
     }
 }
 
+/// The running configuration, shared by everything that reads it and swapped in one step on reload
+#[derive(Debug, Clone)]
+pub struct LiveConfig(Arc<RwLock<Arc<Config>>>);
+
+impl LiveConfig {
+    pub fn new(config: Config) -> Self {
+        LiveConfig(Arc::new(RwLock::new(Arc::new(config))))
+    }
+
+    /// Snapshot of the current configuration, unaffected by later reloads
+    pub fn get(&self) -> Arc<Config> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the configuration, returning the previous one
+    pub fn swap(&self, config: Config) -> Arc<Config> {
+        let mut current = self.0.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, Arc::new(config))
+    }
+}
+
+/// Configuration file read when no path is given
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Prefix of environment variables overriding configuration fields
+pub const ENV_PREFIX: &str = "SPLITS_";
+
 impl Config {
-    pub fn load() -> Result<Self> {
-        // Start with default configuration
-        let mut config = Config::default();
+    /// Load and validate the configuration, leaving out the Discord checks, which only the
+    /// server needs. See [`Config::load_for_server`].
+    ///
+    /// Later sources take precedence over earlier ones:
+    /// 1. The defaults
+    /// 2. The config file. A missing file at the default path falls back to the defaults,
+    ///    but a path given explicitly must exist, and a file that fails to parse is always an error.
+    /// 3. `SPLITS_` environment variables, see [`Config::apply_env_overrides`]
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let config = Self::load_file(path)?.apply_env_overrides(env::vars())?;
 
-        // Try to load TOML configuration file
-        if let Ok(config_str) = fs::read_to_string("config.toml") {
-            match toml::from_str::<Config>(&config_str) {
-                Ok(toml_config) => {
-                    config = toml_config;
-                    debug!("Loaded configuration from config.toml");
-                }
-                Err(e) => {
-                    warn!("Failed to parse config.toml: {}", e);
+        // Validate the configuration
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Load the configuration for running the server, which also needs working Discord
+    /// settings if the bot is enabled
+    pub fn load_for_server(path: Option<&Path>) -> Result<Self> {
+        let config = Self::load(path)?;
+        config.validate_for_server()?;
+        Ok(config)
+    }
+
+    /// Read the config file, or the defaults if there is none at the default path
+    fn load_file(path: Option<&Path>) -> Result<Self> {
+        Ok(match path {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => Self::from_file(Path::new(DEFAULT_CONFIG_PATH))?,
+            None => {
+                warn!("{} not found, using the default configuration", DEFAULT_CONFIG_PATH);
+                Config::default()
+            }
+        })
+    }
+
+    /// Override fields from `SPLITS_` environment variables. Nested fields are separated by a
+    /// double underscore, so `SPLITS_DISCORD__TOKEN` sets `discord.token`. Values are parsed
+    /// as the type of the field they replace, and arrays or tables as inline TOML.
+    ///
+    /// Each variable also has a `_FILE` variant, such as `SPLITS_DISCORD__TOKEN_FILE`, which
+    /// reads the value from a file (without its trailing newline) for mounted secrets.
+    /// Setting both variants of one field, or naming a field that doesn't exist, is an error.
+    pub fn apply_env_overrides(self, vars: impl IntoIterator<Item = (String, String)>) -> Result<Self> {
+        let mut overrides: Vec<(String, String, String)> = Vec::new();
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let (key, value) = match key.strip_suffix("_FILE") {
+                Some(key) => {
+                    let content = fs::read_to_string(&value)
+                        .map_err(|e| AppError::Config(format!("Failed to read {} from {}: {}", name, value, e)))?;
+                    (key.to_string(), content.trim_end_matches(['\r', '\n']).to_string())
                 }
+                None => (key.to_string(), value),
+            };
+            if let Some((_, other, _)) = overrides.iter().find(|(k, _, _)| *k == key) {
+                return Err(AppError::Config(format!("Both {} and {} are set", other, name)));
             }
+            overrides.push((key, name, value));
+        }
+        if overrides.is_empty() {
+            return Ok(self);
         }
 
-        // Validate the configuration
-        config.validate()?;
+        let mut root = toml::Value::try_from(&self).map_err(|e| AppError::Config(e.to_string()))?;
+        for (key, name, value) in overrides {
+            let path: Vec<String> = key.split("__").map(str::to_lowercase).collect();
+            let field = path
+                .iter()
+                .try_fold(&mut root, |table, segment| table.get_mut(segment.as_str()))
+                .ok_or_else(|| AppError::Config(format!("{} doesn't match any configuration field", name)))?;
+            *field = Self::parse_env_value(field, &value)
+                .map_err(|e| AppError::Config(format!("Invalid value for {}: {}", name, e)))?;
+            debug!("Overrode {} from {}", path.join("."), name);
+        }
+
+        root.try_into().map_err(|e: toml::de::Error| AppError::Config(e.message().to_string()))
+    }
 
+    /// Parse an environment variable as the type of the value it replaces
+    fn parse_env_value(current: &toml::Value, value: &str) -> std::result::Result<toml::Value, String> {
+        use toml::Value;
+        match current {
+            Value::String(_) => Ok(Value::String(value.to_string())),
+            Value::Integer(_) => value.parse().map(Value::Integer).map_err(|e| e.to_string()),
+            Value::Float(_) => value.parse().map(Value::Float).map_err(|e| e.to_string()),
+            Value::Boolean(_) => value.parse().map(Value::Boolean).map_err(|e| e.to_string()),
+            _ => {
+                let table: toml::Table = toml::from_str(&format!("value = {}", value)).map_err(|e| e.message().to_string())?;
+                table.get("value").cloned().ok_or_else(|| "missing value".to_string())
+            }
+        }
+    }
+
+    /// Read and parse a configuration file without validating it
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| AppError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+        let config = Self::parse(&content).map_err(|e| AppError::Config(format!("{}: {}", path.display(), e)))?;
+        debug!("Loaded configuration from {}", path.display());
         Ok(config)
     }
 
-    /// Validate the configuration
+    /// Parse configuration TOML, reporting the line and column of any error
+    pub fn parse(content: &str) -> std::result::Result<Self, String> {
+        toml::from_str(content).map_err(|e| match e.span() {
+            Some(span) => {
+                let before = &content[..span.start];
+                let line = before.matches('\n').count() + 1;
+                let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+                format!("line {}, column {}: {}", line, column, e.message().trim_end())
+            }
+            None => e.message().trim_end().to_string(),
+        })
+    }
+
+    /// Validate the Discord settings, on top of [`Config::validate`]
+    fn validate_for_server(&self) -> Result<()> {
+        if self.discord.enabled && cfg!(feature = "discord") {
+            if self.discord.token == "YOUR_TOKEN_HERE" {
+                error!("Discord Token not changed. Please update it in the config file");
+                return Err(AppError::EnvVar(env::VarError::NotPresent));
+            }
+
+            if self.discord.channel_id == 1234567890123456789 {
+                error!("Discord channel id not changed. Please update it in the config file");
+                return Err(AppError::EnvVar(env::VarError::NotPresent));
+            }
+        } else if self.discord.enabled {
+            warn!("Discord is enabled but this build has no Discord support, running without the bot");
+        }
+        Ok(())
+    }
+
+    /// Validate everything but the Discord settings
     fn validate(&self) -> Result<()> {
-        if self.discord.token == "YOUR_TOKEN_HERE" {
-            error!("Discord Token not changed. Please update in config.toml");
-            return Err(AppError::EnvVar(env::VarError::NotPresent));
+        if self.streaks.offset().is_err() || self.streaks.reminder_hour > 23 {
+            error!("Invalid streaks configuration. utc_offset_minutes must be within +/-18 hours and reminder_hour 0-23");
+            return Err(AppError::Other("Invalid streaks configuration".to_string()));
+        }
+
+        if self.histograms.bucket_ms <= 0 || self.histograms.max_buckets == 0 {
+            error!("Invalid histograms configuration. bucket_ms and max_buckets must be positive");
+            return Err(AppError::Other("Invalid histograms configuration".to_string()));
+        }
+
+        if self.notifiers.max_attempts == 0 || self.notifiers.backoff_secs == 0 {
+            error!("Invalid notifiers configuration. max_attempts and backoff_secs must be positive");
+            return Err(AppError::Other("Invalid notifiers configuration".to_string()));
+        }
+
+        if self.webhooks.max_attempts == 0 || self.webhooks.backoff_secs == 0 {
+            error!("Invalid webhooks configuration. max_attempts and backoff_secs must be positive");
+            return Err(AppError::Other("Invalid webhooks configuration".to_string()));
+        }
+
+        if self.dashboard.boards.is_empty() || self.dashboard.rotate_secs == 0 || self.dashboard.top_runs == 0 {
+            error!("Invalid dashboard configuration. boards must not be empty and rotate_secs and top_runs must be positive");
+            return Err(AppError::Other("Invalid dashboard configuration".to_string()));
         }
 
-        if self.discord.channel_id == 1234567890123456789 {
-            error!("Discord channel id not changed. Please update in config.toml");
-            return Err(AppError::EnvVar(env::VarError::NotPresent));
+        if let Err(e) = self.accounts.password.params() {
+            error!("Invalid accounts configuration. {}", e);
+            return Err(AppError::Other("Invalid accounts configuration".to_string()));
+        }
+
+        if !self.accounts.smtp.username.is_empty() && self.accounts.smtp.tls == SmtpTls::None {
+            error!("Invalid accounts configuration. smtp.tls must be starttls or tls when smtp.username is set");
+            return Err(AppError::Other("Invalid accounts configuration".to_string()));
+        }
+
+        if self.accounts.session_days == 0 || self.accounts.magic_link_minutes == 0 {
+            error!("Invalid accounts configuration. session_days and magic_link_minutes must be positive");
+            return Err(AppError::Other("Invalid accounts configuration".to_string()));
+        }
+
+        for (index, subscription) in self.webhooks.subscriptions.iter().enumerate() {
+            if reqwest::Url::parse(&subscription.url).is_err() || subscription.secret.is_empty() {
+                error!("Invalid webhook subscription {}. It needs a valid URL and a secret", subscription.url);
+                return Err(AppError::Other("Invalid webhooks configuration".to_string()));
+            }
+            // Queued deliveries find their subscription, and so its secret, by URL
+            if self.webhooks.subscriptions[..index].iter().any(|other| other.url == subscription.url) {
+                error!("Invalid webhook subscription {}. Each URL can only be subscribed once, list all of its events there", subscription.url);
+                return Err(AppError::Other("Invalid webhooks configuration".to_string()));
+            }
         }
 
         if !Path::new(&self.server.static_dir).exists() {
@@ -141,14 +668,15 @@ impl Config {
         Ok(())
     }
 
-    /// Generate a sample configuration file
-    pub fn generate_sample_config() -> Result<()> {
-        let config = Config::default();
-        let toml_string = toml::to_string_pretty(&config)
-            .map_err(|e| AppError::Network(std::io::Error::new(std::io::ErrorKind::Other, e)))?;
+    /// The default configuration as TOML
+    pub fn default_toml() -> Result<String> {
+        toml::to_string_pretty(&Config::default()).map_err(|e| AppError::Config(e.to_string()))
+    }
 
-        fs::write("config.toml.example", toml_string)?;
-        info!("Generated config.toml.example");
+    /// Generate a sample configuration file
+    pub fn generate_sample_config(path: &Path) -> Result<()> {
+        fs::write(path, Self::default_toml()?)?;
+        info!("Generated {}", path.display());
         Ok(())
     }
 
@@ -162,13 +690,108 @@ impl Config {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_env_overrides() {
+        let secret = env::temp_dir().join(format!("splits-token-{}", std::process::id()));
+        fs::write(&secret, "from-file\n").unwrap();
+        let var = |name: &str, value: &str| (name.to_string(), value.to_string());
+
+        let config = Config::default()
+            .apply_env_overrides([
+                var("SPLITS_DISCORD__TOKEN_FILE", secret.to_str().unwrap()),
+                var("SPLITS_DISCORD__CHANNEL_ID", "42"),
+                var("SPLITS_STREAKS__REMINDERS_ENABLED", "false"),
+                var("SPLITS_RATINGS__K_FACTOR", "16.5"),
+                var("SPLITS_VALIDATION__USERNAME_BLACKLIST", "[\"admin\", \"root\"]"),
+                var("PATH", "/usr/bin"),
+            ])
+            .unwrap();
+        assert_eq!(config.discord.token, "from-file");
+        assert_eq!(config.discord.channel_id, 42);
+        assert!(!config.streaks.reminders_enabled);
+        assert_eq!(config.ratings.k_factor, 16.5);
+        assert_eq!(config.validation.username_blacklist, vec!["admin", "root"]);
+        // Untouched fields keep their values
+        assert_eq!(config.server.port, Config::default().server.port);
+
+        let both = Config::default().apply_env_overrides([
+            var("SPLITS_DISCORD__TOKEN", "a"),
+            var("SPLITS_DISCORD__TOKEN_FILE", secret.to_str().unwrap()),
+        ]);
+        assert!(both.is_err());
+        assert!(Config::default().apply_env_overrides([var("SPLITS_DISCORD__TOKNE", "a")]).is_err());
+        assert!(Config::default().apply_env_overrides([var("SPLITS_SERVER__PORT", "http")]).is_err());
+
+        fs::remove_file(secret).unwrap();
+    }
+
+    #[test]
+    fn test_discord_disabled() {
+        let mut config = Config::default();
+        assert_eq!(config.validate_for_server().is_err(), cfg!(feature = "discord"));
+        // Admin commands never connect, so they don't need the credentials
+        assert!(config.validate().is_ok());
+
+        // Placeholder credentials are fine when the bot doesn't run
+        config.discord.enabled = false;
+        assert!(config.validate_for_server().is_ok());
+
+        // Older config files without the field keep the bot enabled
+        let sample = Config::default_toml().unwrap().replacen("enabled = true\n", "", 1);
+        assert!(Config::parse(&sample).unwrap().discord.enabled);
+    }
+
+    #[test]
+    fn test_accounts_validation() {
+        let mut config = Config::default();
+        config.discord.enabled = false;
+        config.accounts.smtp.username = "splits".to_string();
+        assert!(config.validate().is_ok());
+
+        // A mail server login is only sent over TLS
+        config.accounts.smtp.tls = SmtpTls::None;
+        assert!(config.validate().is_err());
+        config.accounts.smtp.username.clear();
+        assert!(config.validate().is_ok());
+
+        config.accounts.password.parallelism = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_webhooks_validation() {
+        let mut config = Config::default();
+        let subscription = |secret: &str, events: Vec<WebhookEvent>| WebhookSubscription {
+            url: "https://example.com/hooks".to_string(),
+            secret: secret.to_string(),
+            events,
+        };
+        config.webhooks.subscriptions = vec![subscription("first", WebhookEvent::all())];
+        assert!(config.validate().is_ok());
+
+        // A second subscription to the same URL would be signed with the first one's secret
+        config.webhooks.subscriptions.push(subscription("second", vec![WebhookEvent::RecordBroken]));
+        assert!(config.validate().is_err());
+        config.webhooks.subscriptions[1].url = "https://example.com/other".to_string();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_errors_have_a_location() {
+        let error = Config::parse("[discord]\ntoken = \"x\"\nchannel_id = \"not a number\"\n").unwrap_err();
+        assert!(error.starts_with("line 3, column 14:"), "{}", error);
+
+        let error = Config::parse("[server\n").unwrap_err();
+        assert!(error.starts_with("line 1, column"), "{}", error);
+    }
+
     #[test]
     fn test_generate_sample_config() {
         // Clean up any existing example file
         let _ = fs::remove_file("config.toml.example");
 
         // Generate the sample config
-        Config::generate_sample_config().expect("Failed to generate sample config");
+        Config::generate_sample_config(Path::new("config.toml.example")).expect("Failed to generate sample config");
 
         // Verify the file was created
         assert!(Path::new("config.toml.example").exists());
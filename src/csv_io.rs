@@ -0,0 +1,353 @@
+use crate::config::ValidationConfig;
+use crate::error::{AppError, Result};
+use crate::models::{Category, Split, SplitData};
+use crate::period::{Period, TIMESTAMP_FORMAT};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tracing::info;
+
+/// Which splits to export or list
+#[derive(Debug, Clone)]
+pub struct SplitFilter {
+    pub user: Option<String>,
+    pub category: Option<Category>,
+    pub period: Period,
+    /// Only splits by users whose name contains this text
+    pub search: Option<String>,
+}
+
+impl Default for SplitFilter {
+    fn default() -> Self {
+        Self {
+            user: None,
+            category: None,
+            period: Period::AllTime,
+            search: None,
+        }
+    }
+}
+
+/// A row of an imported CSV file, in the same layout as the export.
+/// Any `id` column is ignored, imported splits get new ids.
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    user: String,
+    is_down: bool,
+    is_elevator: bool,
+    is_encumbered: Option<bool>,
+    duration_ms: i32,
+    /// When the split was logged (YYYY-MM-DD HH:MM:SS, UTC), defaults to now
+    #[serde(default)]
+    created_at: Option<String>,
+}
+
+/// A row that was (or in a dry run, would be) imported
+#[derive(Debug, Clone, Serialize)]
+pub struct AcceptedRow {
+    pub line: u64,
+    pub user: String,
+    pub duration_ms: i32,
+    pub created_at: String,
+}
+
+/// A row that was skipped, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedRow {
+    pub line: u64,
+    pub reason: String,
+}
+
+/// Outcome of a CSV import
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    /// Nothing was written to the database
+    pub dry_run: bool,
+    pub accepted: Vec<AcceptedRow>,
+    pub rejected: Vec<RejectedRow>,
+}
+
+/// Get the splits matching a filter, oldest first
+pub async fn get_filtered_splits(pool: &SqlitePool, filter: &SplitFilter) -> Result<Vec<Split>> {
+    let (start, end) = filter.period.sql_bounds(OffsetDateTime::now_utc());
+    let rows = sqlx::query(
+        "SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits
+         WHERE (?1 IS NULL OR user = ?1)
+         AND (?2 IS NULL OR (is_down = ?2 AND is_elevator = ?3 AND (?4 IS NULL OR is_encumbered = ?4)))
+         AND (?5 IS NULL OR created_at >= ?5) AND (?6 IS NULL OR created_at < ?6)
+         AND (?7 IS NULL OR instr(user, ?7) > 0)
+         ORDER BY id ASC"
+    )
+    .bind(&filter.user)
+    .bind(filter.category.map(|c| c.is_down))
+    .bind(filter.category.map(|c| c.is_elevator))
+    .bind(filter.category.and_then(|c| c.is_encumbered))
+    .bind(&start)
+    .bind(&end)
+    .bind(&filter.search)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Split {
+            id: row.get(0),
+            user: row.get(1),
+            is_down: row.get(2),
+            is_elevator: row.get(3),
+            is_encumbered: row.get(4),
+            duration_ms: row.get(5),
+            created_at: row.get(6),
+        })
+        .collect())
+}
+
+/// First characters that make a spreadsheet treat a cell as a formula
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Quote text so spreadsheets show it rather than evaluate it. Text already starting
+/// with a quote gets another, so `unescape_cell` gives back exactly the original.
+fn escape_cell(value: &str) -> Cow<'_, str> {
+    if value.starts_with(FORMULA_PREFIXES) || value.starts_with('\'') {
+        Cow::Owned(format!("'{}", value))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Undo `escape_cell`
+fn unescape_cell(value: String) -> String {
+    match value.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(FORMULA_PREFIXES) || rest.starts_with('\'') => rest.to_string(),
+        _ => value,
+    }
+}
+
+/// A split as written to CSV
+#[derive(Serialize)]
+struct CsvSplit<'a> {
+    id: i32,
+    user: Cow<'a, str>,
+    is_down: bool,
+    is_elevator: bool,
+    is_encumbered: Option<bool>,
+    duration_ms: i32,
+    created_at: &'a str,
+}
+
+/// Write splits as CSV with a header row. Names that a spreadsheet would run as a formula
+/// are quoted, and importing the file removes the quote again.
+pub fn write_csv<W: Write>(splits: &[Split], writer: W) -> Result<()> {
+    let mut csv = csv::Writer::from_writer(writer);
+    for split in splits {
+        let row = CsvSplit {
+            id: split.id,
+            user: escape_cell(&split.user),
+            is_down: split.is_down,
+            is_elevator: split.is_elevator,
+            is_encumbered: split.is_encumbered,
+            duration_ms: split.duration_ms,
+            created_at: &split.created_at,
+        };
+        csv.serialize(row).map_err(|e| AppError::Other(format!("Failed to write CSV: {}", e)))?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// Import splits from CSV, validating every row and skipping duplicates of existing splits
+/// (or of earlier rows in the file). A dry run reports what would happen without writing.
+pub async fn import_csv<R: Read>(
+    pool: &SqlitePool,
+    config: &ValidationConfig,
+    reader: R,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let mut report = ImportReport { dry_run, ..Default::default() };
+    let now = OffsetDateTime::now_utc();
+    let now = PrimitiveDateTime::new(now.date(), now.time())
+        .format(TIMESTAMP_FORMAT)
+        .map_err(|e| AppError::Other(e.to_string()))?;
+
+    // Everything goes through one transaction, which a dry run rolls back
+    let mut tx = pool.begin().await?;
+    let mut csv = csv::Reader::from_reader(reader);
+    let headers = csv.headers().map_err(|e| AppError::Other(format!("Invalid CSV header: {}", e)))?.clone();
+    for record in csv.records() {
+        let (line, row) = match record {
+            Ok(record) => (
+                record.position().map(|p| p.line()).unwrap_or_default(),
+                record.deserialize::<CsvRow>(Some(&headers)),
+            ),
+            Err(e) => (e.position().map(|p| p.line()).unwrap_or_default(), Err(e)),
+        };
+        let mut reject = |reason: String| report.rejected.push(RejectedRow { line, reason });
+
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                reject(format!("Invalid row: {}", e));
+                continue;
+            }
+        };
+
+        let data = SplitData {
+            user: unescape_cell(row.user),
+            is_down: row.is_down,
+            is_elevator: row.is_elevator,
+            duration_ms: row.duration_ms,
+            is_encumbered: row.is_encumbered,
+        };
+        if let Err(e) = data.validate(config) {
+            reject(e.to_string());
+            continue;
+        }
+
+        let created_at = row.created_at.unwrap_or_else(|| now.clone());
+        if PrimitiveDateTime::parse(&created_at, TIMESTAMP_FORMAT).is_err() {
+            reject(format!("Invalid created_at '{}', expected YYYY-MM-DD HH:MM:SS", created_at));
+            continue;
+        }
+
+        let duplicates: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM splits WHERE user = ?1 AND is_down = ?2 AND is_elevator = ?3
+             AND is_encumbered IS ?4 AND duration_ms = ?5 AND created_at = ?6"
+        )
+        .bind(&data.user)
+        .bind(data.is_down)
+        .bind(data.is_elevator)
+        .bind(data.is_encumbered)
+        .bind(data.duration_ms)
+        .bind(&created_at)
+        .fetch_one(&mut *tx)
+        .await?;
+        if duplicates > 0 {
+            reject(format!("Duplicate of an existing split by {} at {}", data.user, created_at));
+            continue;
+        }
+
+        sqlx::query(
+            "INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+        )
+        .bind(&data.user)
+        .bind(data.is_down)
+        .bind(data.is_elevator)
+        .bind(data.is_encumbered)
+        .bind(data.duration_ms)
+        .bind(&created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        report.accepted.push(AcceptedRow {
+            line,
+            user: data.user,
+            duration_ms: data.duration_ms,
+            created_at,
+        });
+    }
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+
+    info!(
+        "{} CSV import: {} accepted, {} rejected",
+        if dry_run { "Dry run of" } else { "Finished" },
+        report.accepted.len(),
+        report.rejected.len()
+    );
+    Ok(report)
+}
+
+/// Format an import report for the command line
+pub fn format_import_report(report: &ImportReport) -> String {
+    let verb = if report.dry_run { "Would import" } else { "Imported" };
+    let mut formatted = format!("{} {} rows, rejected {}\n", verb, report.accepted.len(), report.rejected.len());
+    for row in &report.accepted {
+        formatted.push_str(&format!("  line {}: accepted {} in {}ms at {}\n", row.line, row.user, row.duration_ms, row.created_at));
+    }
+    for row in &report.rejected {
+        formatted.push_str(&format!("  line {}: rejected, {}\n", row.line, row.reason));
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{get_all_splits, initialize_database};
+
+    #[tokio::test]
+    async fn test_csv_round_trip() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let config = ValidationConfig::default();
+
+        let input = "\
+user,is_down,is_elevator,is_encumbered,duration_ms,created_at
+alice,false,false,true,30000,2025-09-01 12:00:00
+bob,true,true,,12000,2025-09-02 08:30:00
+alice,false,false,true,30000,2025-09-01 12:00:00
+carol,false,true,true,12000,2025-09-03 09:00:00
+dave,false,false,false,-5,2025-09-03 09:00:00
+erin,false,false,false,31000,yesterday
+frank,maybe,false,false,31000,2025-09-03 09:00:00
+";
+
+        let report = import_csv(&pool, &config, input.as_bytes(), true).await.unwrap();
+        assert_eq!(report.accepted.iter().map(|r| r.line).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(report.rejected.iter().map(|r| r.line).collect::<Vec<_>>(), vec![4, 5, 6, 7, 8]);
+        assert!(report.rejected[0].reason.starts_with("Duplicate"));
+        assert!(get_all_splits(&pool).await.unwrap().is_empty());
+
+        let report = import_csv(&pool, &config, input.as_bytes(), false).await.unwrap();
+        assert_eq!(report.accepted.len(), 2);
+        // Importing again only finds duplicates
+        let report = import_csv(&pool, &config, input.as_bytes(), false).await.unwrap();
+        assert!(report.accepted.is_empty());
+
+        let filter = SplitFilter { user: Some("alice".to_string()), ..Default::default() };
+        let splits = get_filtered_splits(&pool, &filter).await.unwrap();
+        let mut output = Vec::new();
+        write_csv(&splits, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "id,user,is_down,is_elevator,is_encumbered,duration_ms,created_at\n1,alice,false,false,true,30000,2025-09-01 12:00:00\n"
+        );
+
+        let filter = SplitFilter { category: Some(Category::ALL[0]), ..Default::default() };
+        assert_eq!(get_filtered_splits(&pool, &filter).await.unwrap()[0].user, "bob");
+    }
+
+    #[tokio::test]
+    async fn test_csv_formula_names() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let names = ["=HYPERLINK(\"http://evil\")", "@cmd", "+1", "-2", "'quoted", "'=both", "plain"];
+        for name in names {
+            sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, 0, 0, 0, 30000)")
+                .bind(name)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let mut output = Vec::new();
+        write_csv(&get_all_splits(&pool).await.unwrap(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\"'=HYPERLINK(\"\"http://evil\"\")\""));
+        assert!(output.contains(",'@cmd,") && output.contains(",''quoted,") && output.contains(",plain,"));
+        assert!(output.lines().skip(1).all(|line| !line.split(',').nth(1).unwrap().starts_with(FORMULA_PREFIXES)));
+
+        // Importing the export gives back the original names
+        let other = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&other).await.unwrap();
+        import_csv(&other, &ValidationConfig::default(), output.as_bytes(), false).await.unwrap();
+        let imported: Vec<String> = get_all_splits(&other).await.unwrap().into_iter().map(|split| split.user).collect();
+        assert_eq!(imported, names);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::charts::escape_xml;
+use crate::config::{DashboardBoard, DashboardConfig, DashboardTheme};
+use crate::database::{get_category_leaderboard, get_world_records};
+use crate::error::Result;
+use crate::models::{Category, Split};
+use crate::period::Period;
+use crate::validation::DurationValidator;
+use sqlx::{Row, SqlitePool};
+use std::fmt::Write;
+
+/// Run count, best and average time of one way between floors
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSummary {
+    pub runs: i64,
+    pub best_ms: i32,
+    pub average_ms: f64,
+}
+
+/// Stairs against the elevator in one direction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub is_down: bool,
+    pub stairs: Option<MethodSummary>,
+    pub elevator: Option<MethodSummary>,
+}
+
+impl Verdict {
+    /// One line settling the argument, or saying there isn't enough data yet
+    pub fn summary(&self) -> String {
+        let direction = if self.is_down { "down" } else { "up" };
+        match (&self.stairs, &self.elevator) {
+            (Some(stairs), Some(elevator)) => {
+                let difference = (stairs.average_ms - elevator.average_ms).abs().round() as i32;
+                let winner = if stairs.average_ms <= elevator.average_ms { "Stairs" } else { "Elevator" };
+                format!(
+                    "{} wins going {} by {} on average",
+                    winner,
+                    direction,
+                    DurationValidator::format_duration(difference)
+                )
+            }
+            _ => format!("Not enough runs going {} to decide", direction),
+        }
+    }
+}
+
+/// Compare the stairs and the elevator going up, then going down
+pub async fn get_verdicts(pool: &SqlitePool) -> Result<Vec<Verdict>> {
+    let rows = sqlx::query(
+        "SELECT is_down, is_elevator, COUNT(*), MIN(duration_ms), AVG(duration_ms) FROM splits GROUP BY is_down, is_elevator"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut verdicts: Vec<Verdict> = [false, true]
+        .into_iter()
+        .map(|is_down| Verdict { is_down, stairs: None, elevator: None })
+        .collect();
+    for row in rows {
+        let (is_down, is_elevator): (bool, bool) = (row.get(0), row.get(1));
+        let summary = MethodSummary { runs: row.get(2), best_ms: row.get(3), average_ms: row.get(4) };
+        let verdict = &mut verdicts[is_down as usize];
+        if is_elevator {
+            verdict.elevator = Some(summary);
+        } else {
+            verdict.stairs = Some(summary);
+        }
+    }
+    Ok(verdicts)
+}
+
+fn board_name(board: DashboardBoard) -> &'static str {
+    match board {
+        DashboardBoard::Records => "records",
+        DashboardBoard::Today => "today",
+        DashboardBoard::Verdict => "verdict",
+    }
+}
+
+fn runs_rows(splits: &[Split]) -> String {
+    let mut rows = String::new();
+    for split in splits {
+        let _ = write!(
+            rows,
+            "<tr><td>{}</td><td>{}</td><td class=\"time\">{}</td></tr>",
+            escape_xml(&Category::of(split).name()),
+            escape_xml(&split.user),
+            DurationValidator::format_duration(split.duration_ms)
+        );
+    }
+    rows
+}
+
+fn method_cell(summary: Option<&MethodSummary>) -> String {
+    match summary {
+        Some(summary) => format!(
+            "<td class=\"time\">{}</td><td class=\"time\">{}</td><td>{}</td>",
+            DurationValidator::format_duration(summary.average_ms.round() as i32),
+            DurationValidator::format_duration(summary.best_ms),
+            summary.runs
+        ),
+        None => "<td colspan=\"3\" class=\"muted\">No runs yet</td>".to_string(),
+    }
+}
+
+/// Render the configured boards, which the page rotates through and refreshes as splits come in
+pub async fn render_boards(pool: &SqlitePool, config: &DashboardConfig) -> Result<String> {
+    let mut html = String::new();
+    for (index, board) in config.boards.iter().enumerate() {
+        let active = if index == 0 { " active" } else { "" };
+        let _ = write!(html, "<section class=\"board{}\" data-board=\"{}\">", active, board_name(*board));
+        match board {
+            DashboardBoard::Records => {
+                let records = get_world_records(pool).await?;
+                html.push_str("<h2>World Records</h2>");
+                if records.is_empty() {
+                    html.push_str("<p class=\"muted\">No records yet</p>");
+                } else {
+                    let _ = write!(html, "<table>{}</table>", runs_rows(&records));
+                }
+            }
+            DashboardBoard::Today => {
+                let mut top = Vec::new();
+                for category in Category::ALL {
+                    let leaderboard = get_category_leaderboard(pool, category, &Period::Today).await?;
+                    top.extend(leaderboard.into_iter().take(config.top_runs));
+                }
+                html.push_str("<h2>Today's Top Runs</h2>");
+                if top.is_empty() {
+                    html.push_str("<p class=\"muted\">No runs yet today</p>");
+                } else {
+                    let _ = write!(html, "<table>{}</table>", runs_rows(&top));
+                }
+            }
+            DashboardBoard::Verdict => {
+                html.push_str("<h2>Stairs vs Elevator</h2>");
+                for verdict in get_verdicts(pool).await? {
+                    let _ = write!(
+                        html,
+                        "<h3>{}</h3><table><tr><th></th><th>Average</th><th>Best</th><th>Runs</th></tr>\
+                         <tr><td>Stairs</td>{}</tr><tr><td>Elevator</td>{}</tr></table>",
+                        escape_xml(&verdict.summary()),
+                        method_cell(verdict.stairs.as_ref()),
+                        method_cell(verdict.elevator.as_ref())
+                    );
+                }
+            }
+        }
+        html.push_str("</section>");
+    }
+    Ok(html)
+}
+
+const STYLE: &str = "\
+body.dark{--background:#0b1020;--text:#f9fafb;--muted:#9ca3af;--line:#1f2937;--accent:#facc15}\
+body.light{--background:#f9fafb;--text:#111827;--muted:#6b7280;--line:#e5e7eb;--accent:#1d4ed8}\
+body{margin:0;height:100vh;overflow:hidden;font-family:Arial,sans-serif;background:var(--background);color:var(--text)}\
+h1{margin:0;padding:2vh 4vw;font-size:5vh;color:var(--accent)}\
+h2{font-size:4.5vh;margin:0 0 2vh}\
+h3{font-size:3vh;margin:3vh 0 1vh}\
+main{padding:0 4vw}\
+.board{display:none}\
+.board.active{display:block}\
+table{width:100%;border-collapse:collapse;font-size:3.2vh}\
+th,td{text-align:left;padding:1vh 1vw;border-bottom:1px solid var(--line)}\
+td.time{font-variant-numeric:tabular-nums}\
+.muted{color:var(--muted);font-size:3vh}\
+#banner{display:none;position:fixed;bottom:0;left:0;right:0;padding:3vh 4vw;font-size:4vh;font-weight:bold;background:var(--accent);color:var(--background)}";
+
+const SCRIPT: &str = r#"
+const rotateMs = Number(document.body.dataset.rotateSecs) * 1000;
+let current = 0;
+const boards = () => document.querySelectorAll(".board");
+function show() {
+  boards().forEach((board, index) => board.classList.toggle("active", index === current));
+}
+setInterval(() => {
+  current = (current + 1) % Math.max(boards().length, 1);
+  show();
+}, rotateMs);
+
+async function refresh() {
+  const response = await fetch("/dashboard/boards", { cache: "no-store" });
+  if (!response.ok) return;
+  document.getElementById("boards").innerHTML = await response.text();
+  current = Math.min(current, boards().length - 1);
+  show();
+}
+
+function celebrate(event) {
+  const data = JSON.parse(event.data).data;
+  const records = [...boards()].findIndex((board) => board.dataset.board === "records");
+  if (records >= 0) current = records;
+  const banner = document.getElementById("banner");
+  // Events recorded before the display name was added only have the slug
+  banner.textContent = `New world record! ${data.split.user} in ${data.category_name ?? data.category}`;
+  banner.style.display = "block";
+  setTimeout(() => (banner.style.display = "none"), rotateMs);
+}
+
+const feed = new EventSource("/api/v1/stream");
+for (const kind of ["new_split", "split_deleted", "split_restored"]) {
+  feed.addEventListener(kind, refresh);
+}
+feed.addEventListener("world_record", (event) => {
+  celebrate(event);
+  refresh();
+});
+"#;
+
+/// The full-screen dashboard page around already rendered boards
+pub fn render_dashboard(config: &DashboardConfig, boards: &str) -> String {
+    let theme = match config.theme {
+        DashboardTheme::Dark => "dark",
+        DashboardTheme::Light => "light",
+    };
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\"><head><meta charset=\"UTF-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\
+         <title>{title}</title><style>{STYLE}</style></head>\
+         <body class=\"{theme}\" data-rotate-secs=\"{rotate_secs}\"><h1>{title}</h1>\
+         <main id=\"boards\">{boards}</main><div id=\"banner\"></div><script>{SCRIPT}</script></body></html>\n",
+        title = escape_xml(&config.title),
+        rotate_secs = config.rotate_secs,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::initialize_database;
+
+    #[tokio::test]
+    async fn test_dashboard_boards() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let mut config = DashboardConfig::default();
+
+        let boards = render_boards(&pool, &config).await.unwrap();
+        assert!(boards.contains("<section class=\"board active\" data-board=\"records\">"));
+        assert!(boards.contains("No runs yet today"));
+        assert!(boards.contains("Not enough runs going up to decide"));
+
+        let runs = [("alice", 0, 1, 40_000), ("bob", 0, 0, 55_000), ("carol", 0, 0, 47_000), ("dave", 0, 1, 42_000), ("<eve>", 1, 0, 20_000)];
+        for (user, is_down, is_elevator, duration_ms) in runs {
+            sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, ?2, ?3, 0, ?4)")
+                .bind(user)
+                .bind(is_down)
+                .bind(is_elevator)
+                .bind(duration_ms)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let verdicts = get_verdicts(&pool).await.unwrap();
+        assert_eq!(verdicts[0].summary(), "Elevator wins going up by 10.000s on average");
+        assert_eq!(verdicts[0].stairs, Some(MethodSummary { runs: 2, best_ms: 47_000, average_ms: 51_000.0 }));
+        assert_eq!(verdicts[1].summary(), "Not enough runs going down to decide");
+
+        // Only the configured boards, in order, with the top runs per category limited
+        config.boards = vec![DashboardBoard::Today, DashboardBoard::Verdict];
+        config.top_runs = 1;
+        let boards = render_boards(&pool, &config).await.unwrap();
+        assert!(!boards.contains("data-board=\"records\""));
+        assert!(boards.find("data-board=\"today\"").unwrap() < boards.find("data-board=\"verdict\"").unwrap());
+        assert!(boards.contains("alice") && !boards.contains("dave"));
+        assert!(boards.contains("&lt;eve&gt;"));
+
+        config.title = "Lobby <TV>".to_string();
+        config.theme = DashboardTheme::Light;
+        let page = render_dashboard(&config, &boards);
+        assert!(page.contains("<body class=\"light\" data-rotate-secs=\"15\"><h1>Lobby &lt;TV&gt;</h1>"));
+        assert!(page.contains(&boards));
+    }
+}
@@ -1,13 +1,16 @@
 use crate::error::Result;
-use crate::models::{Split, SplitData};
+use crate::histograms::format_percentile;
+use crate::models::{Category, CategoryStats, Split, SplitData};
+use crate::period::Period;
 use crate::validation::DurationValidator;
-use sqlx::{SqlitePool, Row};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use time::OffsetDateTime;
 use tracing::{debug, warn};
 
 /// Create a sqlite database if the given file name doesn't exist
-pub fn create_sqlite_database_if_does_not_exist(url: &String) -> Result<()> {
+pub fn create_sqlite_database_if_does_not_exist(url: &str) -> Result<()> {
     // Create database parent directory if it doesn't exist
-    let db_path = url.strip_prefix("sqlite:").unwrap_or(&url);
+    let db_path = url.strip_prefix("sqlite:").unwrap_or(url);
     if let Some(parent) = std::path::Path::new(db_path).parent() {
         std::fs::create_dir_all(parent)?;
     }
@@ -39,7 +42,212 @@ pub async fn initialize_database(pool: &SqlitePool) -> Result<()> {
     )
     .execute(pool)
     .await?;
-    
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS seasons (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            starts_on DATE NOT NULL,
+            ends_on DATE NOT NULL,
+            archived_at DATETIME
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS season_standings (
+            season_id INTEGER NOT NULL REFERENCES seasons(id),
+            is_down BOOLEAN NOT NULL,
+            is_elevator BOOLEAN NOT NULL,
+            is_encumbered BOOLEAN,
+            rank INTEGER NOT NULL,
+            user TEXT NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            split_id INTEGER NOT NULL
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS scheduled_runs (
+            name TEXT PRIMARY KEY,
+            last_run_at DATETIME NOT NULL
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS user_achievements (
+            user TEXT NOT NULL,
+            achievement_id TEXT NOT NULL,
+            split_id INTEGER NOT NULL,
+            unlocked_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (user, achievement_id)
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS discord_links (
+            user TEXT PRIMARY KEY,
+            discord_user_id INTEGER NOT NULL UNIQUE,
+            streak_reminders BOOLEAN NOT NULL DEFAULT 0
+        );
+        -- Single-use codes from the website proving a Discord user owns the name they link
+        CREATE TABLE IF NOT EXISTS discord_link_codes (
+            code_hash TEXT PRIMARY KEY,
+            user TEXT NOT NULL,
+            expires_at TEXT NOT NULL
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS ratings (
+            user TEXT PRIMARY KEY,
+            rating REAL NOT NULL,
+            matches INTEGER NOT NULL DEFAULT 0
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS rating_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            split_id INTEGER NOT NULL,
+            user TEXT NOT NULL,
+            rating_before REAL NOT NULL,
+            rating_after REAL NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Splits hidden by an admin, kept out of every query on splits until unhidden
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS hidden_splits (
+            id INTEGER PRIMARY KEY,
+            user TEXT NOT NULL,
+            is_down BOOLEAN NOT NULL,
+            is_elevator BOOLEAN NOT NULL,
+            is_encumbered BOOLEAN,
+            duration_ms INTEGER NOT NULL,
+            created_at DATETIME,
+            hidden_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Webhook deliveries, kept until delivered or out of attempts
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS webhook_outbox (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            url TEXT NOT NULL,
+            event TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            last_status INTEGER,
+            last_error TEXT,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            delivered_at DATETIME
+        );
+        CREATE INDEX IF NOT EXISTS idx_webhook_outbox_due ON webhook_outbox (status, next_attempt_at);
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Announcements waiting to be sent, one per notifier, in the order they were queued.
+    // Jobs are queued with their split and stay 'preparing' until the payload is filled in.
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS announcement_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            notifier TEXT NOT NULL,
+            split_id INTEGER NOT NULL,
+            payload TEXT,
+            status TEXT NOT NULL DEFAULT 'preparing',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            run_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            last_error TEXT,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            finished_at DATETIME
+        );
+        CREATE INDEX IF NOT EXISTS idx_announcement_jobs_pending ON announcement_jobs (notifier, status, id);
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Accounts, their login sessions and single-use email login links.
+    // Only hashes of session and link tokens are stored.
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS accounts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user TEXT NOT NULL UNIQUE,
+            email TEXT UNIQUE,
+            password_hash TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS sessions (
+            token_hash TEXT PRIMARY KEY,
+            account_id INTEGER NOT NULL REFERENCES accounts (id) ON DELETE CASCADE,
+            expires_at DATETIME NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS login_links (
+            token_hash TEXT PRIMARY KEY,
+            account_id INTEGER NOT NULL REFERENCES accounts (id) ON DELETE CASCADE,
+            expires_at DATETIME NOT NULL,
+            used_at DATETIME
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Live feed events, kept so clients can resume where they left off
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS stream_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
     Ok(())
 }
 
@@ -65,14 +273,71 @@ pub async fn get_all_splits(pool: &SqlitePool) -> Result<Vec<Split>> {
     Ok(splits)
 }
 
-/// Get the most recent split from the database
-pub async fn get_most_recent_split(pool: &SqlitePool) -> Result<Option<Split>> {
-    let row = sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits ORDER BY created_at DESC LIMIT 1")
+/// Get the most recent split logged by a user, for tests that insert splits directly
+#[cfg(test)]
+pub(crate) async fn get_latest_split_for_user(pool: &SqlitePool, user: &str) -> Result<Option<Split>> {
+    let row = sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits WHERE user = ?1 ORDER BY id DESC LIMIT 1")
+        .bind(user)
         .fetch_optional(pool)
         .await?;
 
-    match row {
-        Some(row) => Ok(Some(Split {
+    Ok(row.map(|row| Split {
+        id: row.get(0),
+        user: row.get(1),
+        is_down: row.get(2),
+        is_elevator: row.get(3),
+        is_encumbered: row.get(4),
+        duration_ms: row.get(5),
+        created_at: row.get(6),
+    }))
+}
+
+/// Get a user's best split in each category they have run
+pub async fn get_personal_bests(pool: &SqlitePool, user: &str) -> Result<Vec<Split>> {
+    let mut personal_bests = Vec::new();
+
+    for category in Category::ALL {
+        let row = sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits
+                    WHERE user = ?1 AND is_down = ?2 AND is_elevator = ?3 AND (?4 IS NULL OR is_encumbered = ?4)
+                    ORDER BY duration_ms ASC, id ASC LIMIT 1")
+            .bind(user)
+            .bind(category.is_down)
+            .bind(category.is_elevator)
+            .bind(category.is_encumbered)
+            .fetch_optional(pool)
+            .await?;
+
+        if let Some(row) = row {
+            personal_bests.push(Split {
+                id: row.get(0),
+                user: row.get(1),
+                is_down: row.get(2),
+                is_elevator: row.get(3),
+                is_encumbered: row.get(4),
+                duration_ms: row.get(5),
+                created_at: row.get(6),
+            });
+        }
+    }
+
+    Ok(personal_bests)
+}
+
+/// Get all of a user's splits in a category, oldest first
+pub async fn get_user_category_splits(pool: &SqlitePool, user: &str, category: Category) -> Result<Vec<Split>> {
+    let rows = sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits
+                WHERE user = ?1 AND is_down = ?2 AND is_elevator = ?3 AND (?4 IS NULL OR is_encumbered = ?4)
+                ORDER BY id ASC")
+        .bind(user)
+        .bind(category.is_down)
+        .bind(category.is_elevator)
+        .bind(category.is_encumbered)
+        .fetch_all(pool)
+        .await?;
+
+    let splits = rows
+        .iter()
+        .map(|row| Split {
             id: row.get(0),
             user: row.get(1),
             is_down: row.get(2),
@@ -80,36 +345,39 @@ pub async fn get_most_recent_split(pool: &SqlitePool) -> Result<Option<Split>> {
             is_encumbered: row.get(4),
             duration_ms: row.get(5),
             created_at: row.get(6),
-        })),
-        None => Ok(None),
-    }
+        })
+        .collect();
+
+    Ok(splits)
 }
 
 /// Check if a split is a world record (WR) for its category
-/// A WR is when no other entry exists with the same is_down, is_elevator, and is_encumbered status
-/// with a better (lower) duration
+/// A WR is when no earlier entry exists with the same is_down, is_elevator, and is_encumbered status
+/// with a better (lower) duration. Earlier means a lower id, so runs logged in the same second are still ordered.
 pub async fn is_world_record(pool: &SqlitePool, split: &Split) -> Result<bool> {
     let count: i64 = if split.is_elevator {
         // For elevator splits, ignore is_encumbered (it's always None)
         sqlx::query_scalar(
             "SELECT COUNT(*) FROM splits 
-             WHERE is_down = ?1 AND is_elevator = ?2 AND duration_ms < ?3"
+             WHERE is_down = ?1 AND is_elevator = ?2 AND duration_ms < ?3 AND id < ?4"
         )
         .bind(split.is_down)
         .bind(split.is_elevator)
         .bind(split.duration_ms)
+        .bind(split.id)
         .fetch_one(pool)
         .await?
     } else {
         // For stairs splits, include is_encumbered in comparison
         sqlx::query_scalar(
             "SELECT COUNT(*) FROM splits 
-             WHERE is_down = ?1 AND is_elevator = ?2 AND is_encumbered = ?3 AND duration_ms < ?4"
+             WHERE is_down = ?1 AND is_elevator = ?2 AND is_encumbered = ?3 AND duration_ms < ?4 AND id < ?5"
         )
         .bind(split.is_down)
         .bind(split.is_elevator)
         .bind(split.is_encumbered)
         .bind(split.duration_ms)
+        .bind(split.id)
         .fetch_one(pool)
         .await?
     };
@@ -117,38 +385,68 @@ pub async fn is_world_record(pool: &SqlitePool, split: &Split) -> Result<bool> {
     Ok(count == 0)
 }
 
+/// Check if a split beat all of its runner's earlier times in its category.
+/// A first run in a category has nothing to beat, so it isn't a PB.
+pub async fn is_personal_best(pool: &SqlitePool, split: &Split) -> Result<bool> {
+    let category = Category::of(split);
+    let row = sqlx::query(
+        "SELECT COUNT(*), COALESCE(SUM(duration_ms <= ?5), 0) FROM splits
+         WHERE user = ?1 AND is_down = ?2 AND is_elevator = ?3 AND (?4 IS NULL OR is_encumbered = ?4) AND id < ?6"
+    )
+    .bind(&split.user)
+    .bind(category.is_down)
+    .bind(category.is_elevator)
+    .bind(category.is_encumbered)
+    .bind(split.duration_ms)
+    .bind(split.id)
+    .fetch_one(pool)
+    .await?;
+
+    let (earlier, as_fast): (i64, i64) = (row.get(0), row.get(1));
+    Ok(earlier > 0 && as_fast == 0)
+}
+
 /// Check if the split data matches the user's most recent entry duration
-async fn is_duplicate_entry(pool: &SqlitePool, data: &SplitData) -> Result<bool> {
+async fn is_duplicate_entry(conn: &mut SqliteConnection, data: &SplitData) -> Result<bool> {
     let last_duration: Option<i32> = sqlx::query_scalar(
-        "SELECT duration_ms FROM splits WHERE user = ?1 ORDER BY created_at DESC LIMIT 1"
+        "SELECT duration_ms FROM splits WHERE user = ?1 ORDER BY id DESC LIMIT 1"
     )
     .bind(&data.user)
-    .fetch_optional(pool)
+    .fetch_optional(&mut *conn)
     .await?;
 
     Ok(last_duration == Some(data.duration_ms))
 }
 
-/// Insert a new split into the database
-pub async fn insert_split(pool: &SqlitePool, data: &SplitData) -> Result<()> {
+/// Insert a new split into the database, returning it as stored
+pub async fn insert_split(conn: &mut SqliteConnection, data: &SplitData) -> Result<Split> {
     // Check if this is a duplicate of the user's last entry
-    if is_duplicate_entry(pool, data).await? {
+    if is_duplicate_entry(&mut *conn, data).await? {
         warn!("Ignoring duplicate entry for user {} with duration {}ms", data.user, data.duration_ms);
         return Err(crate::AppError::DuplicateEntry);
     }
     
-    sqlx::query(
-        "INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, ?2, ?3, ?4, ?5)"
+    let row = sqlx::query(
+        "INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, ?2, ?3, ?4, ?5)
+         RETURNING id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at"
     )
     .bind(&data.user)
     .bind(data.is_down)
     .bind(data.is_elevator)
     .bind(data.is_encumbered)
     .bind(data.duration_ms)
-    .execute(pool)
+    .fetch_one(conn)
     .await?;
     
-    Ok(())
+    Ok(Split {
+        id: row.get(0),
+        user: row.get(1),
+        is_down: row.get(2),
+        is_elevator: row.get(3),
+        is_encumbered: row.get(4),
+        duration_ms: row.get(5),
+        created_at: row.get(6),
+    })
 }
 
 /// Format splits for display
@@ -180,7 +478,7 @@ pub fn format_splits(splits: &[Split]) -> String {
 }
 
 /// Format a single split for display, with optional WR decoration
-pub fn format_single_split(split: &Split, is_wr: bool) -> String {
+pub fn format_single_split(split: &Split, is_wr: bool, percentile: Option<f64>) -> String {
     let direction = if split.is_down { "down" } else { "up" };
     let method = if split.is_elevator { "elevator" } else { "stairs" };
     let formatted_duration = DurationValidator::format_duration(split.duration_ms);
@@ -195,10 +493,13 @@ pub fn format_single_split(split: &Split, is_wr: bool) -> String {
         ""
     };
     
-    let content = format!(
+    let mut content = format!(
         "{} went {} the {}{} in {}",
         split.user, direction, method, encumbered_text, formatted_duration
     );
+    if let Some(percentile) = percentile {
+        content = format!("{} ({})", content, format_percentile(percentile));
+    }
     
     if is_wr {
         format!("@here NEW WR! {} 🎉", content)
@@ -209,91 +510,50 @@ pub fn format_single_split(split: &Split, is_wr: bool) -> String {
 
 /// Get the world record (best time) for each category
 pub async fn get_world_records(pool: &SqlitePool) -> Result<Vec<Split>> {
-    let mut world_records = Vec::new();
-
-    // Define all possible categories
-    let categories = [
-        // Elevator categories (is_encumbered is ignored for elevators)
-        (true, true, None),   // down elevator
-        (false, true, None),  // up elevator
-        
-        // Stairs categories
-        (true, false, Some(true)),   // down stairs encumbered
-        (true, false, Some(false)),  // down stairs not encumbered
-        (false, false, Some(true)),  // up stairs encumbered
-        (false, false, Some(false)), // up stairs not encumbered
-    ];
-
-    for (is_down, is_elevator, is_encumbered) in categories {
-        let row = if is_elevator {
-            // For elevator splits, ignore is_encumbered
-            sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits 
-                        WHERE is_down = ?1 AND is_elevator = ?2 
-                        ORDER BY duration_ms ASC LIMIT 1")
-                .bind(is_down)
-                .bind(is_elevator)
-                .fetch_optional(pool)
-                .await?
-        } else {
-            // For stairs splits, include is_encumbered
-            sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits 
-                        WHERE is_down = ?1 AND is_elevator = ?2 AND is_encumbered = ?3 
-                        ORDER BY duration_ms ASC LIMIT 1")
-                .bind(is_down)
-                .bind(is_elevator)
-                .bind(is_encumbered)
-                .fetch_optional(pool)
-                .await?
-        };
-
-        if let Some(row) = row {
-            world_records.push(Split {
-                id: row.get(0),
-                user: row.get(1),
-                is_down: row.get(2),
-                is_elevator: row.get(3),
-                is_encumbered: row.get(4),
-                duration_ms: row.get(5),
-                created_at: row.get(6),
-            });
-        }
-    }
+    get_world_records_for_period(pool, &Period::AllTime).await
+}
 
-    Ok(world_records)
+/// Get the world record (best time) for each category within a time window
+pub async fn get_world_records_for_period(pool: &SqlitePool, period: &Period) -> Result<Vec<Split>> {
+    get_category_records(pool, period, "ASC").await
 }
 
 /// Get the slowest record (worst time) for each category
 pub async fn get_slowest_records(pool: &SqlitePool) -> Result<Vec<Split>> {
-    let mut slowest_records = Vec::new();
-    let categories = [
-        (true, true, None),
-        (false, true, None),
-        (true, false, Some(true)),
-        (true, false, Some(false)),
-        (false, false, Some(true)),
-        (false, false, Some(false)),
-    ];
-    for (is_down, is_elevator, is_encumbered) in categories {
-        let row = if is_elevator {
-            sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits \
-                        WHERE is_down = ?1 AND is_elevator = ?2 \
-                        ORDER BY duration_ms DESC LIMIT 1")
-                .bind(is_down)
-                .bind(is_elevator)
-                .fetch_optional(pool)
-                .await?
-        } else {
-            sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits \
-                        WHERE is_down = ?1 AND is_elevator = ?2 AND is_encumbered = ?3 \
-                        ORDER BY duration_ms DESC LIMIT 1")
-                .bind(is_down)
-                .bind(is_elevator)
-                .bind(is_encumbered)
-                .fetch_optional(pool)
-                .await?
-        };
+    get_slowest_records_for_period(pool, &Period::AllTime).await
+}
+
+/// Get the slowest record (worst time) for each category within a time window
+pub async fn get_slowest_records_for_period(pool: &SqlitePool, period: &Period) -> Result<Vec<Split>> {
+    get_category_records(pool, period, "DESC").await
+}
+
+/// Get the first split of each category ordered by duration in the given direction
+async fn get_category_records(pool: &SqlitePool, period: &Period, order: &str) -> Result<Vec<Split>> {
+    let mut records = Vec::new();
+    let (start, end) = period.sql_bounds(OffsetDateTime::now_utc());
+
+    // A NULL is_encumbered (elevator categories) matches any value
+    let query = format!(
+        "SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits
+         WHERE is_down = ?1 AND is_elevator = ?2 AND (?3 IS NULL OR is_encumbered = ?3)
+         AND (?4 IS NULL OR created_at >= ?4) AND (?5 IS NULL OR created_at < ?5)
+         ORDER BY duration_ms {}, created_at ASC LIMIT 1",
+        order
+    );
+
+    for category in Category::ALL {
+        let row = sqlx::query(&query)
+            .bind(category.is_down)
+            .bind(category.is_elevator)
+            .bind(category.is_encumbered)
+            .bind(&start)
+            .bind(&end)
+            .fetch_optional(pool)
+            .await?;
+
         if let Some(row) = row {
-            slowest_records.push(Split {
+            records.push(Split {
                 id: row.get(0),
                 user: row.get(1),
                 is_down: row.get(2),
@@ -304,33 +564,140 @@ pub async fn get_slowest_records(pool: &SqlitePool) -> Result<Vec<Split>> {
             });
         }
     }
-    Ok(slowest_records)
+
+    Ok(records)
+}
+
+/// Get each user's best split in a category within a period, fastest first
+pub async fn get_category_leaderboard(pool: &SqlitePool, category: Category, period: &Period) -> Result<Vec<Split>> {
+    let (start, end) = period.sql_bounds(OffsetDateTime::now_utc());
+    let rows = sqlx::query(
+        "SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM (
+            SELECT *, ROW_NUMBER() OVER (PARTITION BY user ORDER BY duration_ms ASC, id ASC) AS rank FROM splits
+            WHERE is_down = ?1 AND is_elevator = ?2 AND (?3 IS NULL OR is_encumbered = ?3)
+            AND (?4 IS NULL OR created_at >= ?4) AND (?5 IS NULL OR created_at < ?5)
+         )
+         WHERE rank = 1
+         ORDER BY duration_ms ASC, id ASC"
+    )
+    .bind(category.is_down)
+    .bind(category.is_elevator)
+    .bind(category.is_encumbered)
+    .bind(&start)
+    .bind(&end)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Split {
+            id: row.get(0),
+            user: row.get(1),
+            is_down: row.get(2),
+            is_elevator: row.get(3),
+            is_encumbered: row.get(4),
+            duration_ms: row.get(5),
+            created_at: row.get(6),
+        })
+        .collect())
+}
+
+/// Get the most recently logged splits, newest first
+pub async fn get_recent_splits(pool: &SqlitePool, limit: i64) -> Result<Vec<Split>> {
+    let rows = sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits ORDER BY id DESC LIMIT ?1")
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Split {
+            id: row.get(0),
+            user: row.get(1),
+            is_down: row.get(2),
+            is_elevator: row.get(3),
+            is_encumbered: row.get(4),
+            duration_ms: row.get(5),
+            created_at: row.get(6),
+        })
+        .collect())
+}
+
+/// Get a user's run count, best and average time in each category they have run
+pub async fn get_category_stats(pool: &SqlitePool, user: &str) -> Result<Vec<CategoryStats>> {
+    let rows = sqlx::query(
+        "SELECT is_down, is_elevator, CASE WHEN is_elevator THEN NULL ELSE is_encumbered END AS encumbered,
+                COUNT(*), MIN(duration_ms), AVG(duration_ms)
+         FROM splits WHERE user = ?1
+         GROUP BY is_down, is_elevator, encumbered"
+    )
+    .bind(user)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| CategoryStats {
+            category: Category {
+                is_down: row.get(0),
+                is_elevator: row.get(1),
+                is_encumbered: row.get(2),
+            },
+            runs: row.get(3),
+            best_ms: row.get(4),
+            average_ms: row.get(5),
+        })
+        .collect())
+}
+
+/// Get every split that was a world record when it was set, oldest first
+pub async fn get_world_record_history(pool: &SqlitePool) -> Result<Vec<Split>> {
+    let rows = sqlx::query(
+        "SELECT s.id, s.user, s.is_down, s.is_elevator, s.is_encumbered, s.duration_ms, s.created_at FROM splits s
+         WHERE NOT EXISTS (
+            SELECT 1 FROM splits t
+            WHERE t.is_down = s.is_down AND t.is_elevator = s.is_elevator
+            AND (s.is_elevator OR t.is_encumbered IS s.is_encumbered)
+            AND t.id < s.id AND t.duration_ms <= s.duration_ms
+         )
+         ORDER BY s.id ASC"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let history = rows
+        .iter()
+        .map(|row| Split {
+            id: row.get(0),
+            user: row.get(1),
+            is_down: row.get(2),
+            is_elevator: row.get(3),
+            is_encumbered: row.get(4),
+            duration_ms: row.get(5),
+            created_at: row.get(6),
+        })
+        .collect();
+
+    Ok(history)
 }
 
 /// Format world records for display
 pub fn format_world_records(world_records: &[Split]) -> String {
-    if world_records.is_empty() {
-        return "No world records found.".to_string();
+    format_board("World Records Board", world_records)
+}
+
+/// Format a per-category board under the given title
+pub fn format_board(title: &str, records: &[Split]) -> String {
+    let mut formatted = format!("**{}:**\n", title);
+    if records.is_empty() {
+        formatted.push_str("No runs found.");
+        return formatted;
     }
 
-    let mut formatted = String::from("**World Records Board:**\n");
-    
-    for split in world_records {
-        let direction = if split.is_down { "Down" } else { "Up" };
-        let method = if split.is_elevator { "Elevator" } else { "Stairs" };
+    for split in records {
         let formatted_duration = DurationValidator::format_duration(split.duration_ms);
-        
-        let category = if split.is_elevator {
-            format!("{} {}", direction, method)
-        } else {
-            let encumbered_text = match split.is_encumbered {
-                Some(true) => " (Encumbered)",
-                Some(false) => " (No Items)",
-                None => "",
-            };
-            format!("{} {}{}", direction, method, encumbered_text)
-        };
-        
+        let category = Category::of(split).name();
+
         formatted.push_str(&format!(
             "**{}**: {} - {} ({})\n",
             category, split.user, formatted_duration, split.created_at
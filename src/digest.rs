@@ -0,0 +1,148 @@
+use crate::config::Config;
+use crate::error::Result;
+use crate::models::{Category, Split};
+use crate::scheduler::{Clock, Scheduler};
+use crate::validation::DurationValidator;
+use sqlx::{Row, SqlitePool};
+
+/// Summary of the activity between two timestamps
+#[derive(Debug, Default)]
+pub struct Digest {
+    pub runs: i64,
+    /// Splits that were the fastest in their category when they were set
+    pub new_wrs: Vec<Split>,
+    /// Number of splits that improved on the user's previous best in the category
+    pub new_pbs: i64,
+    /// User with the most runs, with their run count
+    pub most_active: Option<(String, i64)>,
+    pub fastest: Option<Split>,
+}
+
+/// Splits belonging to the same category as `s`, matching `is_world_record`'s rules
+const SAME_CATEGORY: &str =
+    "t.is_down = s.is_down AND t.is_elevator = s.is_elevator AND (s.is_elevator OR t.is_encumbered IS s.is_encumbered)";
+
+fn split_from_row(row: &sqlx::sqlite::SqliteRow) -> Split {
+    Split {
+        id: row.get(0),
+        user: row.get(1),
+        is_down: row.get(2),
+        is_elevator: row.get(3),
+        is_encumbered: row.get(4),
+        duration_ms: row.get(5),
+        created_at: row.get(6),
+    }
+}
+
+/// Build the digest of splits created in `[start, end)` (timestamps in `created_at` format)
+pub async fn build_digest(pool: &SqlitePool, start: &str, end: &str) -> Result<Digest> {
+    let runs: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM splits WHERE created_at >= ?1 AND created_at < ?2")
+        .bind(start)
+        .bind(end)
+        .fetch_one(pool)
+        .await?;
+
+    let new_wrs = sqlx::query(&format!(
+        "SELECT s.id, s.user, s.is_down, s.is_elevator, s.is_encumbered, s.duration_ms, s.created_at FROM splits s
+         WHERE s.created_at >= ?1 AND s.created_at < ?2
+         AND NOT EXISTS (SELECT 1 FROM splits t WHERE {} AND t.id < s.id AND t.duration_ms <= s.duration_ms)
+         ORDER BY s.id ASC",
+        SAME_CATEGORY
+    ))
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?
+    .iter()
+    .map(split_from_row)
+    .collect();
+
+    let new_pbs: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM splits s
+         WHERE s.created_at >= ?1 AND s.created_at < ?2
+         AND EXISTS (SELECT 1 FROM splits t WHERE {0} AND t.user = s.user AND t.id < s.id)
+         AND NOT EXISTS (SELECT 1 FROM splits t WHERE {0} AND t.user = s.user AND t.id < s.id AND t.duration_ms <= s.duration_ms)",
+        SAME_CATEGORY
+    ))
+    .bind(start)
+    .bind(end)
+    .fetch_one(pool)
+    .await?;
+
+    let most_active = sqlx::query(
+        "SELECT user, COUNT(*) AS runs FROM splits WHERE created_at >= ?1 AND created_at < ?2
+         GROUP BY user ORDER BY runs DESC, MIN(id) ASC LIMIT 1"
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_optional(pool)
+    .await?
+    .map(|row| (row.get(0), row.get(1)));
+
+    let fastest = sqlx::query(
+        "SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits
+         WHERE created_at >= ?1 AND created_at < ?2 ORDER BY duration_ms ASC, id ASC LIMIT 1"
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_optional(pool)
+    .await?
+    .as_ref()
+    .map(split_from_row);
+
+    Ok(Digest { runs, new_wrs, new_pbs, most_active, fastest })
+}
+
+/// Format a digest for Discord under the given title
+pub fn format_digest(title: &str, digest: &Digest) -> String {
+    let mut formatted = format!("**{}**\n", title);
+
+    if digest.runs == 0 {
+        formatted.push_str("No runs were logged. The stairs miss you.");
+        return formatted;
+    }
+
+    formatted.push_str(&format!("Runs logged: {}\n", digest.runs));
+    formatted.push_str(&format!("New PBs: {}\n", digest.new_pbs));
+
+    if let Some((user, runs)) = &digest.most_active {
+        formatted.push_str(&format!("Most active: {} ({} runs)\n", user, runs));
+    }
+
+    if let Some(split) = &digest.fastest {
+        formatted.push_str(&format!(
+            "Fastest run: {} - {} ({})\n",
+            split.user,
+            Category::of(split).name(),
+            DurationValidator::format_duration(split.duration_ms)
+        ));
+    }
+
+    if digest.new_wrs.is_empty() {
+        formatted.push_str("New WRs: none");
+    } else {
+        formatted.push_str(&format!("New WRs: {}\n", digest.new_wrs.len()));
+        for split in &digest.new_wrs {
+            formatted.push_str(&format!(
+                "- **{}**: {} - {}\n",
+                Category::of(split).name(),
+                split.user,
+                DurationValidator::format_duration(split.duration_ms)
+            ));
+        }
+    }
+
+    formatted
+}
+
+/// Prefix of the scheduler jobs backing digests
+pub const JOB_PREFIX: &str = "digest:";
+
+/// Register every configured digest with the scheduler
+pub fn schedule_digests<C: Clock>(scheduler: &mut Scheduler<C>, config: &Config) -> Result<()> {
+    for schedule in &config.digest.schedules {
+        scheduler.add_job(&format!("{}{}", JOB_PREFIX, schedule.name), &schedule.cron)?;
+    }
+    Ok(())
+}
+
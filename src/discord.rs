@@ -1,64 +1,90 @@
-use crate::config::Config;
-use crate::database::{get_most_recent_split, format_single_split, is_world_record};
-use crate::models::SharedAppContext;
+use crate::config::{Config, LiveConfig};
+use crate::digest::{build_digest, format_digest};
+use crate::error::AppError;
+use crate::models::AppContext;
+use crate::notify::{Notifier, SplitEvent};
+use crate::period::TIMESTAMP_FORMAT;
+use crate::seasons::{archive_finished_seasons, format_season_summary, unarchive_season};
+use crate::streaks::get_streaks;
 use crate::commands::{Data, Error, commands};
 use poise::serenity_prelude as serenity;
 use serenity::async_trait;
+use serenity::http::Http;
 use serenity::builder::CreateMessage;
 use serenity::model::gateway::Ready;
-use serenity::model::id::ChannelId;
+use serenity::model::id::{ChannelId, UserId};
 use serenity::prelude::*;
-use sqlx::SqlitePool;
-use tracing::{error, info};
+use sqlx::{Row, SqlitePool};
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tracing::{error, info, warn};
 
 pub struct Handler {
-    pub context: SharedAppContext,
+    pub context: AppContext,
 }
 
 #[async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, _ctx: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
         info!("{} bot is connected to Discord!", ready.user.name);
-        let mut context = self.context.lock().await;
-        context.discord_ctx = Some(_ctx.clone());
+        // Reconnects fire ready again with the same client, which is already set
+        let _ = self.context.discord_http.set(ctx.http.clone());
     }
 }
 
-/// Send splits information to Discord
-pub async fn send_split_to_discord(ctx: &Context, pool: &SqlitePool, config: &Config) {
-    match get_most_recent_split(pool).await {
-        Ok(Some(split)) => {
-            // Check if this split is a world record
-            match is_world_record(pool, &split).await {
-                Ok(is_wr) => {
-                    let content = format_single_split(&split, is_wr);
-                    let builder = CreateMessage::new().content(content);
-                    let message = ChannelId::new(config.discord.channel_id)
-                        .send_message(ctx, builder)
-                        .await;
-                    if let Err(why) = message {
-                        error!("Error sending message: {why:?}");
-                    }
-                }
-                Err(e) => {
-                    error!("Error checking if split is world record: {}", e);
-                }
-            }
-        }
-        Ok(None) => {
-            error!("No splits found in database");
-        }
+/// Announces new splits in the configured channel through the bot
+pub struct DiscordBotNotifier {
+    pub http: Arc<Http>,
+    pub channel_id: u64,
+}
+
+#[async_trait]
+impl Notifier for DiscordBotNotifier {
+    fn name(&self) -> &'static str {
+        "Discord"
+    }
+
+    async fn notify(&self, event: &SplitEvent) -> crate::error::Result<()> {
+        let builder = CreateMessage::new().content(event.message());
+        ChannelId::new(self.channel_id).send_message(&*self.http, builder).await?;
+        Ok(())
+    }
+}
+
+/// Archive seasons that have ended and post their final standings to Discord.
+/// A season whose summary can't be posted is left unarchived to try again next time.
+pub async fn announce_finished_seasons(http: &Http, pool: &SqlitePool, config: &Config) {
+    let archived = match archive_finished_seasons(pool, OffsetDateTime::now_utc().date()).await {
+        Ok(archived) => archived,
         Err(e) => {
-            error!("Error getting most recent split for Discord: {}", e);
+            error!("Error archiving finished seasons: {}", e);
+            return;
+        }
+    };
+
+    for (season, standings) in archived {
+        if let Err(e) = send_to_channel(http, config, format_season_summary(&season, &standings)).await {
+            warn!("Could not post the summary of season {}, trying again later: {}", season.name, e);
+            if let Err(e) = unarchive_season(pool, season.id).await {
+                error!("Error unarchiving season {}: {}", season.name, e);
+            }
         }
     }
 }
 
+/// Post a message to the configured channel
+pub async fn send_to_channel(http: &Http, config: &Config, content: String) -> crate::error::Result<()> {
+    let builder = CreateMessage::new().content(content);
+    ChannelId::new(config.discord.channel_id).send_message(http, builder).await?;
+    Ok(())
+}
+
 /// Create and configure Discord client with poise framework
-pub async fn create_discord_client(config: &Config, handler: Handler) -> Result<serenity::Client, Box<dyn std::error::Error + Send + Sync>> {
+pub async fn create_discord_client(config: &LiveConfig, handler: Handler) -> Result<serenity::Client, Box<dyn std::error::Error + Send + Sync>> {
     let intents = GatewayIntents::GUILDS | GatewayIntents::GUILD_MESSAGES;
     
     let context_clone = handler.context.clone();
+    let framework_config = config.clone();
     
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
@@ -73,13 +99,14 @@ pub async fn create_discord_client(config: &Config, handler: Handler) -> Result<
                 info!("Bot is ready! Registering slash commands...");
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
                 Ok(Data {
-                    db_pool: context_clone.lock().await.db_pool.clone(),
+                    db_pool: context_clone.db_pool.clone(),
+                    config: framework_config,
                 })
             })
         })
         .build();
 
-    let client = serenity::ClientBuilder::new(&config.discord.token, intents)
+    let client = serenity::ClientBuilder::new(&config.get().discord.token, intents)
         .event_handler(handler)
         .framework(framework)
         .await?;
@@ -93,11 +120,58 @@ async fn event_handler(
     _framework: poise::FrameworkContext<'_, Data, Error>,
     _data: &Data,
 ) -> Result<(), Error> {
-    match event {
-        serenity::FullEvent::Ready { data_about_bot } => {
-            info!("{} bot is connected to Discord!", data_about_bot.user.name);
+    if let serenity::FullEvent::Ready { data_about_bot } = event {
+        info!("{} bot is connected to Discord!", data_about_bot.user.name);
+    }
+    Ok(())
+}
+
+/// Build and post the digest for splits created between `start` and `end`
+pub async fn post_digest(
+    http: &Http,
+    pool: &SqlitePool,
+    config: &Config,
+    name: &str,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> crate::error::Result<()> {
+    let format = |at: OffsetDateTime| at.format(TIMESTAMP_FORMAT).map_err(|e| AppError::Other(e.to_string()));
+    let digest = build_digest(pool, &format(start)?, &format(end)?).await?;
+    send_to_channel(http, config, format_digest(name, &digest)).await
+}
+
+/// Remind opted-in users whose streak breaks if they don't log a split today
+pub async fn send_streak_reminders(http: &Http, pool: &SqlitePool, config: &Config, now: OffsetDateTime) -> crate::error::Result<()> {
+    let offset = config.streaks.offset()?;
+    let today = now.to_offset(offset).date().to_string();
+    let rows = sqlx::query("SELECT user, discord_user_id FROM discord_links WHERE streak_reminders")
+        .fetch_all(pool)
+        .await?;
+
+    for row in rows {
+        let user: String = row.get(0);
+        let discord_user_id: i64 = row.get(1);
+        let streaks = get_streaks(pool, &user, offset, now).await?;
+        if streaks.current == 0 || streaks.last_active.as_deref() == Some(today.as_str()) {
+            continue;
+        }
+
+        let content = format!(
+            "Your {} day streak ends tonight, {}! Take the stairs to keep it alive.",
+            streaks.current, user
+        );
+        let user_id = UserId::new(discord_user_id as u64);
+        match user_id.direct_message(http, CreateMessage::new().content(content.clone())).await {
+            Ok(_) => info!("Sent streak reminder to {}", user),
+            Err(e) => {
+                // DMs may be closed, fall back to a ping in the channel
+                warn!("Could not DM streak reminder to {}: {}", user, e);
+                if let Err(e) = send_to_channel(http, config, format!("<@{}> {}", discord_user_id, content)).await {
+                    error!("Error sending streak reminder to {}: {}", user, e);
+                }
+            }
         }
-        _ => {}
     }
+
     Ok(())
 }
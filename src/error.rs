@@ -4,16 +4,33 @@ use thiserror::Error;
 pub enum AppError {
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
+    #[cfg(feature = "discord")]
     #[error("Discord error: {0}")]
-    Discord(#[from] serenity::Error),
+    Discord(#[from] Box<serenity::Error>),
     #[error("Environment variable error: {0}")]
     EnvVar(#[from] std::env::VarError),
     #[error("Network error: {0}")]
     Network(#[from] std::io::Error),
+    #[error("Configuration error: {0}")]
+    Config(String),
+    #[error("Rate limited, retry after {0:?}")]
+    RateLimited(std::time::Duration),
     #[error("Duplicate entry error")]
     DuplicateEntry,
+    #[error("{0} is already taken")]
+    AlreadyTaken(String),
+    /// Input the caller should fix, such as a password that's too short
+    #[error("{0}")]
+    Invalid(String),
     #[error("Other error: {0}")]
     Other(String),
 }
 
+#[cfg(feature = "discord")]
+impl From<serenity::Error> for AppError {
+    fn from(err: serenity::Error) -> Self {
+        AppError::Discord(Box::new(err))
+    }
+}
+
 pub type Result<T> = std::result::Result<T, AppError>;
@@ -1,15 +1,67 @@
-use crate::database::{format_splits, get_all_splits, insert_split};
-use crate::discord::send_split_to_discord;
-use crate::models::{AppState, SplitData};
-use axum::Json;
-use axum::extract::State;
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Response};
+use crate::accounts::{Account, authenticate, clear_session_cookie, create_account, create_session, delete_session, get_session_account, is_registered, redeem_login_link, send_login_link, session_cookie, session_token};
+use crate::achievements::{AchievementDefinition, evaluate_achievements, get_user_achievements};
+use crate::charts::{build_progress_chart, spawn_render_png};
+use crate::config::Config;
+use crate::dashboard::{render_boards, render_dashboard};
+use crate::csv_io::{SplitFilter, get_filtered_splits, write_csv};
+use crate::database::{format_splits, get_all_splits, get_category_leaderboard, get_recent_splits, get_world_records, get_user_category_splits, get_slowest_records_for_period, get_world_records_for_period, insert_split};
+use crate::error::AppError;
+use crate::histograms::{build_histogram, render_histogram};
+use crate::models::{AppState, Category, Split, SplitData};
+use crate::announcements::{prepare_announcements, queue_announcements};
+use crate::notify::build_split_event;
+use crate::pages::{HISTORY_LIMIT, cached_html, render_error, render_leaderboard, render_leaderboard_index, render_login_confirm, render_profile, render_recent};
+use crate::period::Period;
+use crate::ratings::{RatingChange, get_ratings, rate_split};
+use crate::stats::get_user_stats;
+use crate::streaks::{LINK_CODE_MINUTES, create_link_code};
+use crate::stream::{StreamEventKind, resume_from, websocket_response};
+use crate::timers::{to_livesplit, to_splits_io};
+use crate::validation::UsernameValidator;
+use crate::versus::compare_users;
+use crate::webhooks::{enqueue_split_created, get_delivery_status};
+use crate::seasons::{get_active_season, get_season_by_name, get_season_standings, get_seasons};
+use axum::{Json, Router};
+use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::sse::{KeepAlive, Sse};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use std::net::SocketAddr;
+use std::time::Instant;
+use subtle::ConstantTimeEq;
+use time::OffsetDateTime;
+use tower_http::services::ServeDir;
 use tracing::{debug, error, info, warn};
 
+/// Query parameters selecting a leaderboard time window
+#[derive(Debug, Default, Deserialize)]
+pub struct PeriodQuery {
+    pub period: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl PeriodQuery {
+    /// Resolve the query into a period, where `season` means the active season
+    async fn period(&self, pool: &SqlitePool) -> Result<Period, String> {
+        if self.period.as_deref() == Some("season") {
+            return match get_active_season(pool, OffsetDateTime::now_utc().date()).await {
+                Ok(Some(season)) => season.period().map_err(|e| e.to_string()),
+                Ok(None) => Err("there is no active season".to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+        }
+        Period::parse(self.period.as_deref(), self.from.as_deref(), self.to.as_deref())
+    }
+}
+
 /// HTTP handler to get all splits
 pub async fn all_splits(State(app_state): State<AppState>) -> String {
-    let ctx = app_state.context.lock().await;
+    let ctx = &app_state.context;
     match get_all_splits(&ctx.db_pool).await {
         Ok(splits) => {
             debug!("Sending {} splits to client", splits.len());
@@ -22,10 +74,109 @@ pub async fn all_splits(State(app_state): State<AppState>) -> String {
     }
 }
 
+/// Query parameters selecting a chart's image format
+#[derive(Debug, Default, Deserialize)]
+pub struct ChartQuery {
+    /// `svg` (default) or `png`
+    pub format: Option<String>,
+}
+
+/// Query parameters for a category histogram
+#[derive(Debug, Default, Deserialize)]
+pub struct HistogramQuery {
+    /// Bucket width, defaults to the configured width
+    pub bucket_ms: Option<i32>,
+    /// Count this user's runs separately
+    pub user: Option<String>,
+    /// Duration to mark on rendered charts, such as a run that was just logged
+    pub mark_ms: Option<i32>,
+    /// `json` (default), `svg` or `png`
+    pub format: Option<String>,
+}
+
+/// Query parameters filtering a CSV export
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportQuery {
+    pub user: Option<String>,
+    /// Category slug, such as `up-stairs-empty`
+    pub category: Option<String>,
+    #[serde(flatten)]
+    pub period: PeriodQuery,
+}
+
+/// Query parameters listing webhook deliveries
+#[derive(Debug, Default, Deserialize)]
+pub struct DeliveriesQuery {
+    /// Only list `pending`, `delivered` or `failed` deliveries
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Check the request carries the admin token. Returns the response to send instead if not.
+fn require_admin(headers: &HeaderMap, config: &Config) -> Option<Response> {
+    if config.admin.token.is_empty() {
+        return Some((StatusCode::NOT_FOUND, "Admin API is disabled").into_response());
+    }
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    // Compared in constant time so response timing doesn't give the token away
+    let valid = token.is_some_and(|token| bool::from(token.as_bytes().ct_eq(config.admin.token.as_bytes())));
+    if !valid {
+        return Some((StatusCode::UNAUTHORIZED, "Invalid admin token").into_response());
+    }
+    None
+}
+
+/// Evaluate achievements and update the rating for a newly inserted split.
+/// Both are best effort, a failure here shouldn't fail the insert.
+async fn process_new_split(
+    pool: &SqlitePool,
+    config: &Config,
+    split: &Split,
+) -> (Vec<AchievementDefinition>, Option<RatingChange>) {
+    let unlocked = evaluate_achievements(pool, config, split).await.unwrap_or_else(|e| {
+        error!("Error evaluating achievements: {}", e);
+        Vec::new()
+    });
+
+    let rating_change = rate_split(pool, &config.ratings, split).await.unwrap_or_else(|e| {
+        error!("Error rating split {}: {}", split.id, e);
+        None
+    });
+
+    (unlocked, rating_change)
+}
+
 /// HTTP handler to create a new split with validation
-pub async fn new_split(State(app_state): State<AppState>, Json(data): Json<SplitData>) -> Response {
+/// Splits from a logged in runner are always theirs, whatever name the body gives
+pub async fn new_split(State(app_state): State<AppState>, headers: HeaderMap, Json(mut data): Json<SplitData>) -> Response {
+    let config = app_state.config.get();
+    match current_account(&app_state, &headers).await {
+        Ok(Some(account)) => data.user = account.user,
+        Ok(None) if !config.accounts.allow_anonymous => {
+            return (StatusCode::UNAUTHORIZED, "Log in to submit splits").into_response();
+        }
+        // Anyone may submit under an unclaimed name, but only its owner under a registered one
+        Ok(None) => match is_registered(&app_state.context.db_pool, &data.user).await {
+            Ok(false) => {}
+            Ok(true) => {
+                return (StatusCode::UNAUTHORIZED, format!("{} has an account, log in to submit splits as them", data.user)).into_response();
+            }
+            Err(e) => {
+                error!("Error checking account for {}: {}", data.user, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error checking account").into_response();
+            }
+        },
+        Err(e) => {
+            error!("Error checking session: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error checking session").into_response();
+        }
+    }
+
     // Validate the input data using configuration
-    if let Err(validation_error) = data.validate(&app_state.config.validation) {
+    if let Err(validation_error) = data.validate(&config.validation) {
         warn!("Validation error: {}", validation_error);
         return (
             StatusCode::BAD_REQUEST,
@@ -34,14 +185,26 @@ pub async fn new_split(State(app_state): State<AppState>, Json(data): Json<Split
             .into_response();
     }
 
-    let ctx = app_state.context.lock().await;
+    let ctx = &app_state.context;
+
+    match store_split(&ctx.db_pool, &config, &data).await {
+        Ok(split) => {
+            info!("New split {}: {:?}", split.id, data);
 
-    match insert_split(&ctx.db_pool, &data).await {
-        Ok(_) => {
-            info!("New split: {:?}", data);
+            let (unlocked, rating_change) = process_new_split(&ctx.db_pool, &config, &split).await;
 
-            if let Some(discord_ctx) = &ctx.discord_ctx {
-                send_split_to_discord(discord_ctx, &ctx.db_pool, &app_state.config).await;
+            // Announcements and webhooks are sent in the background
+            match build_split_event(&ctx.db_pool, split, unlocked, rating_change).await {
+                Ok(event) => {
+                    let kind = if event.is_world_record { StreamEventKind::WorldRecord } else { StreamEventKind::NewSplit };
+                    if let Err(e) = ctx.events.publish(&ctx.db_pool, kind, &event.split).await {
+                        error!("Error publishing split {} to the live feed: {}", event.split.id, e);
+                    }
+                    if let Err(e) = prepare_announcements(&ctx.db_pool, &event).await {
+                        error!("Error preparing announcements for split {}: {}", event.split.id, e);
+                    }
+                }
+                Err(e) => error!("Error building split announcement: {}", e),
             }
 
             (StatusCode::CREATED, "Data inserted successfully!").into_response()
@@ -52,3 +215,1010 @@ pub async fn new_split(State(app_state): State<AppState>, Json(data): Json<Split
         }
     }
 }
+
+/// Insert a split along with its announcement jobs and webhooks, so none are lost if
+/// the server stops before they're sent. The transaction takes the write lock up front,
+/// since concurrent submissions couldn't upgrade a read lock taken by the duplicate check.
+async fn store_split(pool: &SqlitePool, config: &Config, data: &SplitData) -> Result<Split, AppError> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    let split = insert_split(&mut tx, data).await?;
+    queue_announcements(&mut tx, config, split.id).await?;
+    enqueue_split_created(&mut tx, &config.webhooks, &split).await?;
+    tx.commit().await?;
+    Ok(split)
+}
+
+/// The account logged in by the request's session cookie, if any
+async fn current_account(app_state: &AppState, headers: &HeaderMap) -> Result<Option<Account>, AppError> {
+    match session_token(headers) {
+        Some(token) => get_session_account(&app_state.context.db_pool, &token).await,
+        None => Ok(None),
+    }
+}
+
+/// Respond with the account as JSON, starting a session for it
+async fn logged_in(app_state: &AppState, status: StatusCode, account: Account) -> Response {
+    let config = app_state.config.get();
+    match create_session(&app_state.context.db_pool, &config.accounts, account.id).await {
+        Ok(token) => (status, [(header::SET_COOKIE, session_cookie(&config.accounts, &token))], Json(account)).into_response(),
+        Err(e) => {
+            error!("Error starting session for {}: {}", account.user, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error starting session").into_response()
+        }
+    }
+}
+
+/// 429 if the address has made too many password checks lately
+fn throttled(app_state: &AppState, addr: SocketAddr) -> Option<Response> {
+    let wait = app_state.context.login_throttle.check(addr.ip(), Instant::now())?;
+    warn!("Throttling password checks from {}", addr.ip());
+    let retry_after = wait.as_secs().max(1).to_string();
+    Some((StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry_after)], "Too many attempts, try again later").into_response())
+}
+
+#[derive(Deserialize)]
+pub struct RegisterRequest {
+    user: String,
+    password: String,
+    email: Option<String>,
+}
+
+/// HTTP handler to create an account and log straight into it
+pub async fn register(
+    State(app_state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(request): Json<RegisterRequest>,
+) -> Response {
+    let config = app_state.config.get();
+    if let Err(validation_error) = UsernameValidator::validate(&request.user, &config.validation) {
+        return (StatusCode::BAD_REQUEST, format!("Validation failed: {}", validation_error)).into_response();
+    }
+    if let Some(response) = throttled(&app_state, addr) {
+        return response;
+    }
+    let email = request.email.as_deref().filter(|email| !email.is_empty());
+    match create_account(&app_state.context.db_pool, &config.accounts, &request.user, &request.password, email).await {
+        Ok(account) => logged_in(&app_state, StatusCode::CREATED, account).await,
+        Err(e @ AppError::AlreadyTaken(_)) => (StatusCode::CONFLICT, e.to_string()).into_response(),
+        Err(AppError::Invalid(message)) => (StatusCode::BAD_REQUEST, message).into_response(),
+        Err(e) => {
+            error!("Error creating account for {}: {}", request.user, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error creating account").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    user: String,
+    password: String,
+}
+
+/// HTTP handler to log in with a name and password
+pub async fn login(
+    State(app_state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(request): Json<LoginRequest>,
+) -> Response {
+    if let Some(response) = throttled(&app_state, addr) {
+        return response;
+    }
+    match authenticate(&app_state.context.db_pool, &request.user, &request.password).await {
+        Ok(Some(account)) => logged_in(&app_state, StatusCode::OK, account).await,
+        Ok(None) => (StatusCode::UNAUTHORIZED, "Wrong name or password").into_response(),
+        Err(e) => {
+            error!("Error logging in {}: {}", request.user, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error logging in").into_response()
+        }
+    }
+}
+
+/// HTTP handler to end the current session
+pub async fn logout(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Some(token) = session_token(&headers)
+        && let Err(e) = delete_session(&app_state.context.db_pool, &token).await
+    {
+        error!("Error ending session: {}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error logging out").into_response();
+    }
+    (StatusCode::NO_CONTENT, [(header::SET_COOKIE, clear_session_cookie())]).into_response()
+}
+
+/// HTTP handler for the logged in account as JSON
+pub async fn me(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    match current_account(&app_state, &headers).await {
+        Ok(Some(account)) => Json(account).into_response(),
+        Ok(None) => (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
+        Err(e) => {
+            error!("Error checking session: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error checking session").into_response()
+        }
+    }
+}
+
+/// HTTP handler giving the logged in runner a code for linking their Discord account with `/link`
+pub async fn discord_link_code(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    let account = match current_account(&app_state, &headers).await {
+        Ok(Some(account)) => account,
+        Ok(None) => return (StatusCode::UNAUTHORIZED, "Not logged in").into_response(),
+        Err(e) => {
+            error!("Error checking session: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error checking session").into_response();
+        }
+    };
+    match create_link_code(&app_state.context.db_pool, &account.user).await {
+        Ok(code) => {
+            let body = serde_json::json!({ "code": code, "expires_in_minutes": LINK_CODE_MINUTES });
+            (StatusCode::CREATED, Json(body)).into_response()
+        }
+        Err(e) => {
+            error!("Error creating Discord link code for {}: {}", account.user, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error creating link code").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginLinkRequest {
+    email: String,
+}
+
+/// HTTP handler to email a login link. Answers the same, as quickly, whether or not the
+/// address has an account, so it can't be used to find out who's registered.
+pub async fn request_login_link(State(app_state): State<AppState>, Json(request): Json<LoginLinkRequest>) -> Response {
+    let config = app_state.config.get();
+    if config.accounts.smtp.host.is_empty() {
+        return (StatusCode::NOT_FOUND, "Email login is disabled").into_response();
+    }
+    if let Err(e) = send_login_link(&app_state.context.db_pool, &config.accounts, &request.email).await {
+        error!("Error sending login link: {}", e);
+    }
+    (StatusCode::ACCEPTED, "If that address has an account, a login link is on its way").into_response()
+}
+
+/// HTTP handler for the link in a login email, a page asking to confirm the login. Opening the
+/// link doesn't use it up, since mail scanners follow links before the runner does.
+pub async fn confirm_login(Path(token): Path<String>) -> Response {
+    ([(header::CACHE_CONTROL, "no-store")], Html(render_login_confirm(&token))).into_response()
+}
+
+/// HTTP handler for the confirm page's form, which logs in and returns to the timer
+pub async fn redeem_login(State(app_state): State<AppState>, Path(token): Path<String>) -> Response {
+    let config = app_state.config.get();
+    let ctx = &app_state.context;
+    let account = match redeem_login_link(&ctx.db_pool, &token).await {
+        Ok(Some(account)) => account,
+        Ok(None) => {
+            let page = render_error("Login link expired", "This login link has already been used or has expired. Ask for a new one.");
+            return (StatusCode::GONE, Html(page)).into_response();
+        }
+        Err(e) => {
+            error!("Error redeeming login link: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error logging in").into_response();
+        }
+    };
+    match create_session(&ctx.db_pool, &config.accounts, account.id).await {
+        Ok(session) => (
+            StatusCode::SEE_OTHER,
+            [(header::SET_COOKIE, session_cookie(&config.accounts, &session)), (header::LOCATION, "/".to_string())],
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Error starting session for {}: {}", account.user, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error starting session").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    last_event_id: Option<i64>,
+}
+
+/// HTTP handler for the live feed as Server-Sent Events.
+/// Resumes after `Last-Event-ID` or `?last_event_id=` when given.
+pub async fn live_events(State(app_state): State<AppState>, headers: HeaderMap, Query(query): Query<StreamQuery>) -> Response {
+    let ctx = &app_state.context;
+    let subscription = match ctx.events.subscribe(&ctx.db_pool, resume_from(&headers, query.last_event_id)).await {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            error!("Error subscribing to the live feed: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error starting live feed").into_response();
+        }
+    };
+
+    let events = futures_util::stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.next().await?;
+        Some((event.to_sse(), subscription))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// HTTP handler for the live feed over a WebSocket, one JSON text message per event
+pub async fn live_events_websocket(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<StreamQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let ctx = &app_state.context;
+    match ctx.events.subscribe(&ctx.db_pool, resume_from(&headers, query.last_event_id)).await {
+        Ok(subscription) => websocket_response(upgrade, subscription),
+        Err(e) => {
+            error!("Error subscribing to the live feed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error starting live feed").into_response()
+        }
+    }
+}
+
+/// HTML page listing every category with its record
+pub async fn leaderboard_index_page(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    let ctx = &app_state.context;
+    match get_world_records(&ctx.db_pool).await {
+        Ok(records) => cached_html(&headers, render_leaderboard_index(&records)),
+        Err(e) => {
+            error!("Error getting world records: {}", e);
+            page_error(StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving records")
+        }
+    }
+}
+
+/// HTML leaderboard for one category, optionally scoped with `?period=`
+pub async fn leaderboard_page(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(category): Path<String>,
+    Query(query): Query<PeriodQuery>,
+) -> Response {
+    let ctx = &app_state.context;
+    let Some(category) = Category::from_slug(&category) else {
+        return page_error(StatusCode::NOT_FOUND, "Unknown category");
+    };
+    let period = match query.period(&ctx.db_pool).await {
+        Ok(period) => period,
+        Err(e) => return page_error(StatusCode::BAD_REQUEST, &format!("Invalid period: {}", e)),
+    };
+
+    match get_category_leaderboard(&ctx.db_pool, category, &period).await {
+        Ok(entries) => cached_html(&headers, render_leaderboard(category, &period.to_string(), query.period.as_deref(), &entries)),
+        Err(e) => {
+            error!("Error getting leaderboard for {}: {}", category.slug(), e);
+            page_error(StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving leaderboard")
+        }
+    }
+}
+
+/// HTML profile with a user's personal bests, streaks and history
+pub async fn profile_page(State(app_state): State<AppState>, headers: HeaderMap, Path(user): Path<String>) -> Response {
+    let ctx = &app_state.context;
+    let config = app_state.config.get();
+    let stats = match get_user_stats(&ctx.db_pool, &config, &user).await {
+        Ok(stats) if stats.runs == 0 => return page_error(StatusCode::NOT_FOUND, &format!("{} hasn't logged any runs yet.", user)),
+        Ok(stats) => stats,
+        Err(e) => {
+            error!("Error getting stats for {}: {}", user, e);
+            return page_error(StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving profile");
+        }
+    };
+
+    let filter = SplitFilter { user: Some(user.clone()), ..Default::default() };
+    match get_filtered_splits(&ctx.db_pool, &filter).await {
+        Ok(splits) => {
+            let history: Vec<_> = splits.into_iter().rev().take(HISTORY_LIMIT).collect();
+            cached_html(&headers, render_profile(&stats, &history))
+        }
+        Err(e) => {
+            error!("Error getting history for {}: {}", user, e);
+            page_error(StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving profile")
+        }
+    }
+}
+
+/// HTML feed of the most recent runs
+pub async fn recent_page(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    let ctx = &app_state.context;
+    match get_recent_splits(&ctx.db_pool, HISTORY_LIMIT as i64).await {
+        Ok(splits) => cached_html(&headers, render_recent(&splits)),
+        Err(e) => {
+            error!("Error getting recent splits: {}", e);
+            page_error(StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving recent runs")
+        }
+    }
+}
+
+/// Full-screen dashboard for a wall display, rotating through the configured boards
+pub async fn dashboard_page(State(app_state): State<AppState>) -> Response {
+    let config = app_state.config.get();
+    match render_boards(&app_state.context.db_pool, &config.dashboard).await {
+        Ok(boards) => dashboard_html(render_dashboard(&config.dashboard, &boards)),
+        Err(e) => {
+            error!("Error rendering dashboard: {}", e);
+            page_error(StatusCode::INTERNAL_SERVER_ERROR, "Error rendering dashboard")
+        }
+    }
+}
+
+/// Just the dashboard's boards, fetched by the page when the live feed reports a change
+pub async fn dashboard_boards(State(app_state): State<AppState>) -> Response {
+    let config = app_state.config.get();
+    match render_boards(&app_state.context.db_pool, &config.dashboard).await {
+        Ok(boards) => dashboard_html(boards),
+        Err(e) => {
+            error!("Error rendering dashboard: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error rendering dashboard").into_response()
+        }
+    }
+}
+
+/// The dashboard changes with every split, so it's never cached
+fn dashboard_html(html: String) -> Response {
+    ([(header::CACHE_CONTROL, "no-store")], Html(html)).into_response()
+}
+
+fn page_error(status: StatusCode, message: &str) -> Response {
+    (status, Html(render_error(status.canonical_reason().unwrap_or("Error"), message))).into_response()
+}
+
+/// HTTP handler to get the world record of each category as JSON
+pub async fn world_records(State(app_state): State<AppState>, Query(query): Query<PeriodQuery>) -> Response {
+    let ctx = &app_state.context;
+    let period = match query.period(&ctx.db_pool).await {
+        Ok(period) => period,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid period: {}", e)).into_response(),
+    };
+
+    match get_world_records_for_period(&ctx.db_pool, &period).await {
+        Ok(records) => Json(records).into_response(),
+        Err(e) => {
+            error!("Error getting world records: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving records").into_response()
+        }
+    }
+}
+
+/// HTTP handler to get the slowest run of each category as JSON
+pub async fn slowest_records(State(app_state): State<AppState>, Query(query): Query<PeriodQuery>) -> Response {
+    let ctx = &app_state.context;
+    let period = match query.period(&ctx.db_pool).await {
+        Ok(period) => period,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid period: {}", e)).into_response(),
+    };
+
+    match get_slowest_records_for_period(&ctx.db_pool, &period).await {
+        Ok(records) => Json(records).into_response(),
+        Err(e) => {
+            error!("Error getting slowest records: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving records").into_response()
+        }
+    }
+}
+
+/// HTTP handler to list all seasons as JSON
+pub async fn seasons(State(app_state): State<AppState>) -> Response {
+    let ctx = &app_state.context;
+    match get_seasons(&ctx.db_pool).await {
+        Ok(seasons) => Json(seasons).into_response(),
+        Err(e) => {
+            error!("Error getting seasons: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving seasons").into_response()
+        }
+    }
+}
+
+/// HTTP handler to get the archived standings of a season as JSON
+pub async fn season_standings(State(app_state): State<AppState>, Path(name): Path<String>) -> Response {
+    let ctx = &app_state.context;
+    let season = match get_season_by_name(&ctx.db_pool, &name).await {
+        Ok(Some(season)) => season,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Season not found").into_response(),
+        Err(e) => {
+            error!("Error getting season {}: {}", name, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving season").into_response();
+        }
+    };
+
+    match get_season_standings(&ctx.db_pool, season.id).await {
+        Ok(standings) => Json(standings).into_response(),
+        Err(e) => {
+            error!("Error getting standings for season {}: {}", name, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving standings").into_response()
+        }
+    }
+}
+
+/// HTTP handler to get the badges a user has unlocked as JSON
+pub async fn user_badges(State(app_state): State<AppState>, Path(user): Path<String>) -> Response {
+    let ctx = &app_state.context;
+    match get_user_achievements(&ctx.db_pool, &app_state.config.get().achievements, &user).await {
+        Ok(badges) => Json(badges).into_response(),
+        Err(e) => {
+            error!("Error getting badges for {}: {}", user, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving badges").into_response()
+        }
+    }
+}
+
+/// HTTP handler to get a user's run count, streaks and personal bests as JSON
+pub async fn user_stats(State(app_state): State<AppState>, Path(user): Path<String>) -> Response {
+    let ctx = &app_state.context;
+    match get_user_stats(&ctx.db_pool, &app_state.config.get(), &user).await {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => {
+            error!("Error getting stats for {}: {}", user, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving stats").into_response()
+        }
+    }
+}
+
+/// HTTP handler to compare two users across every category as JSON
+pub async fn versus(State(app_state): State<AppState>, Path((first, second)): Path<(String, String)>) -> Response {
+    let ctx = &app_state.context;
+    match compare_users(&ctx.db_pool, &first, &second).await {
+        Ok(versus) => Json(versus).into_response(),
+        Err(AppError::Invalid(e)) => (StatusCode::BAD_REQUEST, e).into_response(),
+        Err(e) => {
+            error!("Error comparing {} and {}: {}", first, second, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error comparing users").into_response()
+        }
+    }
+}
+
+/// HTTP handler to get every user's skill rating, best first, as JSON
+pub async fn ratings(State(app_state): State<AppState>) -> Response {
+    let ctx = &app_state.context;
+    match get_ratings(&ctx.db_pool).await {
+        Ok(ratings) => Json(ratings).into_response(),
+        Err(e) => {
+            error!("Error getting ratings: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving ratings").into_response()
+        }
+    }
+}
+
+/// HTTP handler to render a user's progress in a category as an SVG (or PNG) chart
+pub async fn progress_chart(
+    State(app_state): State<AppState>,
+    Path((user, category)): Path<(String, String)>,
+    Query(query): Query<ChartQuery>,
+) -> Response {
+    let Some(category) = Category::from_slug(&category) else {
+        return (StatusCode::NOT_FOUND, "Unknown category").into_response();
+    };
+
+    let ctx = &app_state.context;
+    let svg = match build_progress_chart(&ctx.db_pool, &user, category).await {
+        Ok(svg) => svg,
+        Err(e) => {
+            error!("Error building chart for {}: {}", user, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error rendering chart").into_response();
+        }
+    };
+
+    match query.format.as_deref() {
+        None | Some("svg") => ([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response(),
+        Some("png") => match spawn_render_png(svg).await {
+            Ok(png) => ([(header::CONTENT_TYPE, "image/png")], png).into_response(),
+            Err(e) => {
+                error!("Error rasterizing chart for {}: {}", user, e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Error rendering chart").into_response()
+            }
+        },
+        Some(_) => (StatusCode::BAD_REQUEST, "Unsupported format, use svg or png").into_response(),
+    }
+}
+
+/// HTTP handler to get the distribution of a category's durations as JSON, SVG or PNG
+pub async fn category_histogram(
+    State(app_state): State<AppState>,
+    Path(category): Path<String>,
+    Query(query): Query<HistogramQuery>,
+) -> Response {
+    let Some(category) = Category::from_slug(&category) else {
+        return (StatusCode::NOT_FOUND, "Unknown category").into_response();
+    };
+
+    let ctx = &app_state.context;
+    let histogram = match build_histogram(
+        &ctx.db_pool,
+        &app_state.config.get().histograms,
+        category,
+        query.bucket_ms,
+        query.user.as_deref(),
+    )
+    .await
+    {
+        Ok(histogram) => histogram,
+        Err(AppError::Invalid(e)) => return (StatusCode::BAD_REQUEST, e).into_response(),
+        Err(e) => {
+            error!("Error building histogram for {}: {}", category.name(), e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving histogram").into_response();
+        }
+    };
+
+    match query.format.as_deref() {
+        None | Some("json") => Json(histogram).into_response(),
+        Some("svg") => ([(header::CONTENT_TYPE, "image/svg+xml")], render_histogram(&histogram, query.mark_ms)).into_response(),
+        Some("png") => match spawn_render_png(render_histogram(&histogram, query.mark_ms)).await {
+            Ok(png) => ([(header::CONTENT_TYPE, "image/png")], png).into_response(),
+            Err(e) => {
+                error!("Error rasterizing histogram for {}: {}", category.name(), e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Error rendering histogram").into_response()
+            }
+        },
+        Some(_) => (StatusCode::BAD_REQUEST, "Unsupported format, use json, svg or png").into_response(),
+    }
+}
+
+/// HTTP handler to download a user's splits as CSV, optionally filtered by category and period.
+/// Exporting every user's splits needs the admin token.
+pub async fn export_splits(State(app_state): State<AppState>, headers: HeaderMap, Query(query): Query<ExportQuery>) -> Response {
+    // One runner's splits are public on their profile anyway, everyone's at once is for admins
+    if query.user.is_none()
+        && let Some(response) = require_admin(&headers, &app_state.config.get())
+    {
+        return response;
+    }
+
+    let category = match query.category.as_deref().map(Category::from_slug) {
+        Some(None) => return (StatusCode::BAD_REQUEST, "Unknown category").into_response(),
+        Some(category) => category,
+        None => None,
+    };
+
+    let ctx = &app_state.context;
+    let period = match query.period.period(&ctx.db_pool).await {
+        Ok(period) => period,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid period: {}", e)).into_response(),
+    };
+
+    let filter = SplitFilter { user: query.user, category, period, ..Default::default() };
+    let mut csv = Vec::new();
+    let result = match get_filtered_splits(&ctx.db_pool, &filter).await {
+        Ok(splits) => write_csv(&splits, &mut csv),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(()) => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (header::CONTENT_DISPOSITION, "attachment; filename=\"splits.csv\""),
+            ],
+            csv,
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Error exporting splits: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error exporting splits").into_response()
+        }
+    }
+}
+
+/// HTTP handler to download a user's runs in a category as a LiveSplit `.lss` file
+pub async fn livesplit_export(
+    State(app_state): State<AppState>,
+    Path((user, category)): Path<(String, String)>,
+) -> Response {
+    let Some(category) = Category::from_slug(&category) else {
+        return (StatusCode::NOT_FOUND, "Unknown category").into_response();
+    };
+
+    let ctx = &app_state.context;
+    let lss = match get_user_category_splits(&ctx.db_pool, &user, category).await {
+        Ok(splits) => to_livesplit(&user, category, &splits),
+        Err(e) => Err(e),
+    };
+
+    match lss {
+        Ok(lss) => (
+            [
+                (header::CONTENT_TYPE, "application/xml; charset=utf-8".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.lss\"", category.slug())),
+            ],
+            lss,
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Error exporting LiveSplit runs for {}: {}", user, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error exporting runs").into_response()
+        }
+    }
+}
+
+/// HTTP handler to get a user's runs in a category in the splits.io exchange format
+pub async fn splits_io_export(
+    State(app_state): State<AppState>,
+    Path((user, category)): Path<(String, String)>,
+) -> Response {
+    let Some(category) = Category::from_slug(&category) else {
+        return (StatusCode::NOT_FOUND, "Unknown category").into_response();
+    };
+
+    let ctx = &app_state.context;
+    let run = match get_user_category_splits(&ctx.db_pool, &user, category).await {
+        Ok(splits) => to_splits_io(&user, category, &splits),
+        Err(e) => Err(e),
+    };
+
+    match run {
+        Ok(run) => Json(run).into_response(),
+        Err(e) => {
+            error!("Error exporting splits.io run for {}: {}", user, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error exporting runs").into_response()
+        }
+    }
+}
+
+/// HTTP handler to get webhook delivery status as JSON, for admins
+pub async fn webhook_deliveries(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<DeliveriesQuery>,
+) -> Response {
+    if let Some(response) = require_admin(&headers, &app_state.config.get()) {
+        return response;
+    }
+    if let Some(status) = query.status.as_deref().filter(|s| !["pending", "delivered", "failed"].contains(s)) {
+        return (StatusCode::BAD_REQUEST, format!("Unknown delivery status: {}", status)).into_response();
+    }
+
+    let ctx = &app_state.context;
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    match get_delivery_status(&ctx.db_pool, query.status.as_deref(), limit).await {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => {
+            error!("Error getting webhook deliveries: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving deliveries").into_response()
+        }
+    }
+}
+
+/// Every HTTP route, with static files from `static_dir` for anything else
+pub fn router(app_state: AppState, static_dir: &str) -> Router {
+    Router::new()
+        .route("/leaderboard", get(leaderboard_index_page))
+        .route("/leaderboard/{category}", get(leaderboard_page))
+        .route("/users/{user}", get(profile_page))
+        .route("/recent", get(recent_page))
+        .route("/dashboard", get(dashboard_page))
+        .route("/dashboard/boards", get(dashboard_boards))
+        .route("/api/v0/split/all", get(all_splits))
+        .route("/api/v0/split/new", post(new_split))
+        .route("/api/v1/accounts", post(register))
+        .route("/api/v1/login", post(login))
+        .route("/api/v1/login/email", post(request_login_link))
+        .route("/api/v1/login/email/{token}", get(confirm_login).post(redeem_login))
+        .route("/api/v1/logout", post(logout))
+        .route("/api/v1/me", get(me))
+        .route("/api/v1/me/discord-link-code", post(discord_link_code))
+        .route("/api/v1/splits/export", get(export_splits))
+        .route("/api/v1/records", get(world_records))
+        .route("/api/v1/records/slowest", get(slowest_records))
+        .route("/api/v1/seasons", get(seasons))
+        .route("/api/v1/seasons/{name}/standings", get(season_standings))
+        .route("/api/v1/users/{user}/badges", get(user_badges))
+        .route("/api/v1/users/{user}/stats", get(user_stats))
+        .route("/api/v1/users/{user}/livesplit/{category}", get(livesplit_export))
+        .route("/api/v1/users/{user}/splitsio/{category}", get(splits_io_export))
+        .route("/api/v1/versus/{first}/{second}", get(versus))
+        .route("/api/v1/ratings", get(ratings))
+        .route("/api/v1/charts/{user}/{category}", get(progress_chart))
+        .route("/api/v1/histograms/{category}", get(category_histogram))
+        .route("/api/v1/stream", get(live_events))
+        .route("/api/v1/stream/ws", get(live_events_websocket))
+        .route("/api/v1/admin/webhooks", get(webhook_deliveries))
+        .with_state(app_state)
+        .fallback_service(ServeDir::new(static_dir))
+}
+
+/// Serve an app on a free local port in the background, returning its base URL such as `http://127.0.0.1:1234`
+#[cfg(test)]
+pub(crate) async fn spawn_test_server(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap() });
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LiveConfig;
+    use crate::database::initialize_database;
+    use crate::models::AppContext;
+    use futures_util::StreamExt;
+    use std::time::Duration;
+
+    /// Many clients reading and writing at once. Requests share the pool without a global lock,
+    /// so they all complete, and a slow request doesn't hold up the others.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_requests() {
+        // A file, since every connection to an in-memory database gets its own empty one
+        let path = std::env::temp_dir().join(format!("splits-load-{}.db", std::process::id()));
+        let pool = SqlitePool::connect(&format!("sqlite:{}?mode=rwc", path.display())).await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        for i in 0..500 {
+            sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, 0, 0, 0, ?2)")
+                .bind(format!("runner{}", i % 20))
+                .bind(20_000 + i)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let mut config = Config::default();
+        config.discord.enabled = false;
+        let app_state = AppState { context: AppContext::new(pool.clone()), config: LiveConfig::new(config) };
+        let address = spawn_test_server(router(app_state, "static")).await;
+
+        const CLIENTS: usize = 16;
+        const REQUESTS_PER_CLIENT: usize = 25;
+        let clients: Vec<_> = (0..CLIENTS)
+            .map(|client| {
+                let address = address.clone();
+                tokio::spawn(async move {
+                    let http = reqwest::Client::new();
+                    for request in 0..REQUESTS_PER_CLIENT {
+                        let response = match request % 5 {
+                            0 => http
+                                .post(format!("{}/api/v0/split/new", address))
+                                .json(&serde_json::json!({
+                                    "user": format!("client{}", client),
+                                    "is_down": true,
+                                    "is_elevator": true,
+                                    "duration_ms": 30_000 + request as i32,
+                                }))
+                                .send()
+                                .await,
+                            1 => http.get(format!("{}/api/v1/records", address)).send().await,
+                            2 => http.get(format!("{}/api/v1/users/runner{}/stats", address, client)).send().await,
+                            _ => http.get(format!("{}/api/v0/split/all", address)).send().await,
+                        };
+                        let status = response.unwrap().status();
+                        assert!(status.is_success(), "request {} of client {} failed with {}", request, client, status);
+                    }
+                })
+            })
+            .collect();
+        for client in clients {
+            client.await.unwrap();
+        }
+        let written: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM splits WHERE user LIKE 'client%'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(written as usize, CLIENTS * REQUESTS_PER_CLIENT / 5);
+
+        // A write stuck waiting for the database doesn't hold up other requests while it waits
+        let blocker = pool.begin_with("BEGIN IMMEDIATE").await.unwrap();
+        let write = tokio::spawn(
+            reqwest::Client::new()
+                .post(format!("{}/api/v0/split/new", address))
+                .json(&serde_json::json!({"user": "slow", "is_down": true, "is_elevator": true, "duration_ms": 30_000}))
+                .send(),
+        );
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let read = tokio::time::timeout(Duration::from_secs(1), reqwest::get(format!("{}/api/v0/split/all", address)))
+            .await
+            .expect("a read was held up by a write in flight")
+            .unwrap();
+        assert!(read.status().is_success());
+        assert!(!write.is_finished());
+        blocker.rollback().await.unwrap();
+        assert_eq!(write.await.unwrap().unwrap().status().as_u16(), 201);
+
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_live_feed() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let mut config = Config::default();
+        config.discord.enabled = false;
+        let context = AppContext::new(pool.clone());
+        tokio::spawn(crate::stream::run_event_relay(pool.clone(), context.events.clone()));
+        let app_state = AppState { context, config: LiveConfig::new(config) };
+        let address = spawn_test_server(router(app_state, "static")).await;
+
+        let http = reqwest::Client::new();
+        let plain = http.get(format!("{}/api/v1/stream/ws", address)).send().await.unwrap();
+        assert_eq!(plain.status().as_u16(), 400);
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("{}/api/v1/stream/ws", address.replacen("http", "ws", 1))).await.unwrap();
+        for duration_ms in [30_000, 35_000] {
+            let response = http
+                .post(format!("{}/api/v0/split/new", address))
+                .json(&serde_json::json!({ "user": "alice", "is_down": false, "is_elevator": true, "duration_ms": duration_ms }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), 201);
+        }
+
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            let message = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next()).await.unwrap().unwrap().unwrap();
+            if let tokio_tungstenite::tungstenite::Message::Text(text) = message {
+                received.push(serde_json::from_str::<serde_json::Value>(&text).unwrap());
+            }
+        }
+        assert_eq!(received[0]["kind"], "world_record");
+        assert_eq!(received[0]["data"]["split"]["duration_ms"], 30_000);
+        assert_eq!(received[1]["kind"], "new_split");
+        assert_eq!(received[1]["data"]["category"], "up-elevator");
+
+        // An SSE client that saw the first event picks up from the second
+        let mut response = http
+            .get(format!("{}/api/v1/stream", address))
+            .header("Last-Event-ID", received[0]["id"].to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let mut body = String::new();
+        while !body.contains("\n\n") {
+            let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), response.chunk()).await.unwrap().unwrap().unwrap();
+            body.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+        assert!(body.contains("event: new_split\n"), "{}", body);
+        assert!(body.contains(&format!("id: {}\n", received[1]["id"])), "{}", body);
+        assert!(!body.contains("world_record"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_pages() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        for (user, duration_ms) in [("alice", 40_000), ("bob", 38_000), ("alice", 37_000), ("bob", 39_000)] {
+            sqlx::query("INSERT INTO splits (user, is_down, is_elevator, duration_ms) VALUES (?1, 0, 1, ?2)")
+                .bind(user)
+                .bind(duration_ms)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let mut config = Config::default();
+        config.discord.enabled = false;
+        config.admin.token = "secret".to_string();
+        let app_state = AppState { context: AppContext::new(pool), config: LiveConfig::new(config) };
+        let address = spawn_test_server(router(app_state, "static")).await;
+        let http = reqwest::Client::new();
+
+        // Everyone's splits at once need the admin token, one runner's don't
+        let export = format!("{}/api/v1/splits/export", address);
+        assert_eq!(http.get(&export).send().await.unwrap().status().as_u16(), 401);
+        assert_eq!(http.get(&export).bearer_auth("secret").send().await.unwrap().status().as_u16(), 200);
+        let response = http.get(format!("{}?user=alice", export)).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap().lines().count(), 3);
+
+        let response = http.get(format!("{}/leaderboard/up-elevator", address)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["content-type"], "text/html; charset=utf-8");
+        assert_eq!(response.headers()["cache-control"], "public, max-age=30");
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+        let board = response.text().await.unwrap();
+        // One row per runner, each with their best time
+        let (alice, bob) = (board.find("37.000s").unwrap(), board.find("38.000s").unwrap());
+        assert!(alice < bob);
+        assert!(!board.contains("40.000s") && !board.contains("39.000s"));
+
+        let response = http.get(format!("{}/leaderboard/up-elevator", address)).header("If-None-Match", &etag).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 304);
+
+        let profile = http.get(format!("{}/users/bob", address)).send().await.unwrap().text().await.unwrap();
+        assert!(profile.contains("2 run(s)"));
+        assert!(profile.find("39.000s").unwrap() < profile.rfind("38.000s").unwrap());
+
+        let recent = http.get(format!("{}/recent", address)).send().await.unwrap().text().await.unwrap();
+        assert!(recent.find("39.000s").unwrap() < recent.find("40.000s").unwrap());
+        assert!(recent.contains("<a href=\"/users/alice\">alice</a>"));
+
+        assert_eq!(http.get(format!("{}/users/nobody", address)).send().await.unwrap().status().as_u16(), 404);
+        assert_eq!(http.get(format!("{}/leaderboard/sideways", address)).send().await.unwrap().status().as_u16(), 404);
+        assert!(http.get(format!("{}/leaderboard", address)).send().await.unwrap().text().await.unwrap().contains("37.000s"));
+
+        let response = http.get(format!("{}/dashboard", address)).send().await.unwrap();
+        assert_eq!(response.headers()["cache-control"], "no-store");
+        assert!(response.text().await.unwrap().contains("new EventSource(\"/api/v1/stream\")"));
+        let boards = http.get(format!("{}/dashboard/boards", address)).send().await.unwrap().text().await.unwrap();
+        assert!(boards.starts_with("<section class=\"board active\" data-board=\"records\">"));
+    }
+
+    #[tokio::test]
+    async fn test_accounts() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let (smtp_port, received) = crate::smtp::fake_smtp_server().await;
+        let mut config = Config::default();
+        config.discord.enabled = false;
+        config.accounts.password = crate::config::PasswordConfig { memory_kib: 64, iterations: 1, parallelism: 1 };
+        config.accounts.smtp.host = "127.0.0.1".to_string();
+        config.accounts.smtp.port = smtp_port;
+        config.accounts.smtp.tls = crate::config::SmtpTls::None;
+        let live_config = LiveConfig::new(config.clone());
+        let app_state = AppState { context: AppContext::new(pool.clone()), config: live_config.clone() };
+        let address = spawn_test_server(router(app_state, "static")).await;
+        let http = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+        let cookie = |response: &reqwest::Response| {
+            response.headers()["set-cookie"].to_str().unwrap().split(';').next().unwrap().to_string()
+        };
+
+        let alice = serde_json::json!({ "user": "alice", "password": "correct horse", "email": "alice@example.com" });
+        let response = http.post(format!("{}/api/v1/accounts", address)).json(&alice).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 201);
+        assert!(response.headers()["set-cookie"].to_str().unwrap().contains("HttpOnly"));
+        let session = cookie(&response);
+        let response = http.post(format!("{}/api/v1/accounts", address)).json(&alice).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 409);
+
+        let me = http.get(format!("{}/api/v1/me", address)).header("Cookie", &session).send().await.unwrap();
+        assert_eq!(me.json::<serde_json::Value>().await.unwrap()["user"], "alice");
+        let response = http.post(format!("{}/api/v1/me/discord-link-code", address)).header("Cookie", &session).send().await.unwrap();
+        let code = response.json::<serde_json::Value>().await.unwrap()["code"].as_str().unwrap().to_string();
+        assert_eq!(crate::streaks::redeem_link_code(&pool, &code).await.unwrap().as_deref(), Some("alice"));
+        let response = http.post(format!("{}/api/v1/me/discord-link-code", address)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+
+        // A logged in runner can't log splits under someone else's name
+        let split = serde_json::json!({ "user": "bob", "is_down": false, "is_elevator": false, "duration_ms": 45_000 });
+        let response = http.post(format!("{}/api/v0/split/new", address)).header("Cookie", &session).json(&split).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 201);
+        let user: String = sqlx::query_scalar("SELECT user FROM splits").fetch_one(&pool).await.unwrap();
+        assert_eq!(user, "alice");
+
+        // Nor can anyone log splits under a registered name without logging in
+        let as_alice = serde_json::json!({ "user": "alice", "is_down": false, "is_elevator": false, "duration_ms": 44_000 });
+        let response = http.post(format!("{}/api/v0/split/new", address)).json(&as_alice).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+        let response = http.post(format!("{}/api/v0/split/new", address)).json(&split).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 201);
+        // and bob's anonymous history can't be claimed by registering the name
+        let bob = serde_json::json!({ "user": "bob", "password": "correct horse" });
+        assert_eq!(http.post(format!("{}/api/v1/accounts", address)).json(&bob).send().await.unwrap().status().as_u16(), 409);
+        let short = serde_json::json!({ "user": "carol", "password": "short" });
+        assert_eq!(http.post(format!("{}/api/v1/accounts", address)).json(&short).send().await.unwrap().status().as_u16(), 400);
+
+        let response = http.post(format!("{}/api/v1/logout", address)).header("Cookie", &session).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 204);
+        let me = http.get(format!("{}/api/v1/me", address)).header("Cookie", &session).send().await.unwrap();
+        assert_eq!(me.status().as_u16(), 401);
+
+        let wrong = serde_json::json!({ "user": "alice", "password": "wrong horse" });
+        assert_eq!(http.post(format!("{}/api/v1/login", address)).json(&wrong).send().await.unwrap().status().as_u16(), 401);
+        let right = serde_json::json!({ "user": "alice", "password": "correct horse" });
+        let response = http.post(format!("{}/api/v1/login", address)).json(&right).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+
+        // Anonymous splits are refused once accounts are required
+        config.accounts.allow_anonymous = false;
+        live_config.swap(config);
+        let response = http.post(format!("{}/api/v0/split/new", address)).json(&split).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+
+        // Email login answers the same for unknown addresses, and the link logs in once
+        let unknown = serde_json::json!({ "email": "mallory@example.com" });
+        assert_eq!(http.post(format!("{}/api/v1/login/email", address)).json(&unknown).send().await.unwrap().status().as_u16(), 202);
+        let known = serde_json::json!({ "email": "alice@example.com" });
+        assert_eq!(http.post(format!("{}/api/v1/login/email", address)).json(&known).send().await.unwrap().status().as_u16(), 202);
+        while received.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let message = received.lock().unwrap()[0].replace("=\n", "");
+        let link = message.lines().find(|line| line.contains("/api/v1/login/email/")).unwrap();
+        let path = &link[link.find("/api/").unwrap()..];
+
+        // Following the link only shows a confirm page, the form's POST logs in
+        let page = http.get(format!("{}{}", address, path)).send().await.unwrap();
+        assert_eq!(page.status().as_u16(), 200);
+        assert!(page.text().await.unwrap().contains(&format!("action=\"{}\" method=\"post\"", path)));
+        let response = http.post(format!("{}{}", address, path)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 303);
+        assert_eq!(response.headers()["location"], "/");
+        let session = cookie(&response);
+        let me = http.get(format!("{}/api/v1/me", address)).header("Cookie", &session).send().await.unwrap();
+        assert_eq!(me.status().as_u16(), 200);
+        assert_eq!(http.post(format!("{}{}", address, path)).send().await.unwrap().status().as_u16(), 410);
+    }
+}
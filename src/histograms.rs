@@ -0,0 +1,267 @@
+use crate::charts::{FONT, HEIGHT, MARGIN_BOTTOM, MARGIN_LEFT, MARGIN_RIGHT, MARGIN_TOP, WIDTH, chart_header, escape_xml};
+use crate::config::HistogramsConfig;
+use crate::error::{AppError, Result};
+use crate::models::{Category, Split};
+use crate::validation::DurationValidator;
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::fmt::Write;
+
+/// A range of durations and how many runs fell into it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bucket {
+    /// Inclusive lower bound
+    pub start_ms: i32,
+    /// Exclusive upper bound
+    pub end_ms: i32,
+    pub count: i64,
+    /// Runs in this bucket by the highlighted user, if any
+    pub highlighted: i64,
+}
+
+/// Distribution of durations in a category
+#[derive(Debug, Clone, Serialize)]
+pub struct Histogram {
+    pub category: Category,
+    pub bucket_ms: i32,
+    pub total: i64,
+    pub highlighted_user: Option<String>,
+    /// Contiguous buckets from the fastest run to the slowest, empty if there are no runs
+    pub buckets: Vec<Bucket>,
+}
+
+/// Build a histogram of a category's durations, optionally counting one user's runs separately
+pub async fn build_histogram(
+    pool: &SqlitePool,
+    config: &HistogramsConfig,
+    category: Category,
+    bucket_ms: Option<i32>,
+    highlighted_user: Option<&str>,
+) -> Result<Histogram> {
+    let bucket_ms = bucket_ms.unwrap_or(config.bucket_ms);
+    if bucket_ms <= 0 {
+        return Err(AppError::Invalid("Bucket width must be positive".to_string()));
+    }
+
+    let rows = sqlx::query(
+        "SELECT duration_ms / ?4, COUNT(*), COALESCE(SUM(user = ?5), 0) FROM splits
+         WHERE is_down = ?1 AND is_elevator = ?2 AND (?3 IS NULL OR is_encumbered = ?3)
+         GROUP BY 1 ORDER BY 1 ASC"
+    )
+    .bind(category.is_down)
+    .bind(category.is_elevator)
+    .bind(category.is_encumbered)
+    .bind(bucket_ms)
+    .bind(highlighted_user)
+    .fetch_all(pool)
+    .await?;
+
+    let counts: Vec<(i32, i64, i64)> = rows.iter().map(|row| (row.get(0), row.get(1), row.get(2))).collect();
+    let (Some(first), Some(last)) = (counts.first(), counts.last()) else {
+        return Ok(Histogram {
+            category,
+            bucket_ms,
+            total: 0,
+            highlighted_user: highlighted_user.map(str::to_string),
+            buckets: Vec::new(),
+        });
+    };
+
+    let (first, last) = (first.0, last.0);
+    if (last - first) as usize >= config.max_buckets {
+        return Err(AppError::Invalid(format!(
+            "A bucket width of {}ms would need more than {} buckets",
+            bucket_ms, config.max_buckets
+        )));
+    }
+
+    // Fill the gaps so the buckets are contiguous
+    let buckets = (first..=last)
+        .map(|index| {
+            let (count, highlighted) = counts
+                .iter()
+                .find(|(i, _, _)| *i == index)
+                .map(|(_, count, highlighted)| (*count, *highlighted))
+                .unwrap_or_default();
+            Bucket {
+                start_ms: index * bucket_ms,
+                end_ms: (index + 1) * bucket_ms,
+                count,
+                highlighted,
+            }
+        })
+        .collect();
+
+    Ok(Histogram {
+        category,
+        bucket_ms,
+        total: counts.iter().map(|(_, count, _)| count).sum(),
+        highlighted_user: highlighted_user.map(str::to_string),
+        buckets,
+    })
+}
+
+/// Percentage of the category's other runs that this split was faster than,
+/// `None` if it's the only run in its category
+pub async fn get_percentile(pool: &SqlitePool, split: &Split) -> Result<Option<f64>> {
+    let category = Category::of(split);
+    let row = sqlx::query(
+        "SELECT COUNT(*), COALESCE(SUM(duration_ms > ?5), 0) + COALESCE(SUM(duration_ms = ?5), 0) / 2.0 FROM splits
+         WHERE is_down = ?1 AND is_elevator = ?2 AND (?3 IS NULL OR is_encumbered = ?3) AND id != ?4"
+    )
+    .bind(category.is_down)
+    .bind(category.is_elevator)
+    .bind(category.is_encumbered)
+    .bind(split.id)
+    .bind(split.duration_ms)
+    .fetch_one(pool)
+    .await?;
+
+    let others: i64 = row.get(0);
+    let beaten: f64 = row.get(1);
+    Ok((others > 0).then(|| beaten / others as f64 * 100.0))
+}
+
+/// Render a histogram as a bar chart, with the highlighted user's runs stacked in a
+/// separate colour and an optional marker at one duration
+pub fn render_histogram(histogram: &Histogram, marker_ms: Option<i32>) -> String {
+    let title = match &histogram.highlighted_user {
+        Some(user) => format!("{} - {} runs ({} highlighted)", histogram.category.name(), histogram.total, user),
+        None => format!("{} - {} runs", histogram.category.name(), histogram.total),
+    };
+    let mut svg = chart_header(&title);
+
+    let (Some(first), Some(last)) = (histogram.buckets.first(), histogram.buckets.last()) else {
+        let _ = write!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"16\" text-anchor=\"middle\" fill=\"#6b7280\" {FONT}>No runs yet</text></svg>",
+            WIDTH / 2.0,
+            HEIGHT / 2.0
+        );
+        return svg;
+    };
+
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let bar_width = plot_width / histogram.buckets.len() as f64;
+    let max_count = histogram.buckets.iter().map(|b| b.count).max().unwrap_or(1).max(1) as f64;
+    let bar_height = |count: i64| plot_height * count as f64 / max_count;
+    let x = |duration_ms: f64| MARGIN_LEFT + plot_width * (duration_ms - first.start_ms as f64) / (last.end_ms - first.start_ms) as f64;
+
+    // Count axis
+    let _ = write!(
+        svg,
+        "<line x1=\"{MARGIN_LEFT}\" y1=\"{MARGIN_TOP}\" x2=\"{MARGIN_LEFT}\" y2=\"{:.1}\" stroke=\"#9ca3af\"/>\
+         <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"12\" text-anchor=\"end\" fill=\"#4b5563\" {FONT}>{}</text>\
+         <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"12\" text-anchor=\"end\" fill=\"#4b5563\" {FONT}>0</text>",
+        HEIGHT - MARGIN_BOTTOM,
+        MARGIN_LEFT - 8.0,
+        MARGIN_TOP + 4.0,
+        max_count,
+        MARGIN_LEFT - 8.0,
+        HEIGHT - MARGIN_BOTTOM + 4.0
+    );
+
+    for (i, bucket) in histogram.buckets.iter().enumerate() {
+        let left = MARGIN_LEFT + bar_width * i as f64 + 1.0;
+        let others = bar_height(bucket.count - bucket.highlighted);
+        let highlighted = bar_height(bucket.highlighted);
+        let bottom = HEIGHT - MARGIN_BOTTOM;
+        let _ = write!(
+            svg,
+            "<rect x=\"{left:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{others:.1}\" fill=\"#93c5fd\"/>\
+             <rect x=\"{left:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{highlighted:.1}\" fill=\"#f59e0b\"/>",
+            bottom - others,
+            (bar_width - 2.0).max(1.0),
+            bottom - others - highlighted,
+            (bar_width - 2.0).max(1.0)
+        );
+    }
+
+    if let Some(marker_ms) = marker_ms {
+        let marker_x = x(marker_ms as f64);
+        let _ = write!(
+            svg,
+            "<line x1=\"{marker_x:.1}\" y1=\"{MARGIN_TOP}\" x2=\"{marker_x:.1}\" y2=\"{:.1}\" stroke=\"#dc2626\" stroke-width=\"2\" stroke-dasharray=\"6 4\"/>\
+             <text x=\"{marker_x:.1}\" y=\"{:.1}\" font-size=\"12\" text-anchor=\"middle\" fill=\"#dc2626\" {FONT}>{}</text>",
+            HEIGHT - MARGIN_BOTTOM,
+            MARGIN_TOP - 6.0,
+            DurationValidator::format_duration(marker_ms)
+        );
+    }
+
+    // Duration range along the bottom
+    let _ = write!(
+        svg,
+        "<text x=\"{MARGIN_LEFT}\" y=\"{:.1}\" font-size=\"12\" fill=\"#4b5563\" {FONT}>{}</text>\
+         <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"12\" text-anchor=\"end\" fill=\"#4b5563\" {FONT}>{}</text>\
+         <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"12\" text-anchor=\"middle\" fill=\"#4b5563\" {FONT}>{} per bar</text>",
+        HEIGHT - MARGIN_BOTTOM + 20.0,
+        DurationValidator::format_duration(first.start_ms),
+        WIDTH - MARGIN_RIGHT,
+        HEIGHT - MARGIN_BOTTOM + 20.0,
+        DurationValidator::format_duration(last.end_ms),
+        WIDTH / 2.0,
+        HEIGHT - 12.0,
+        escape_xml(&DurationValidator::format_duration(histogram.bucket_ms))
+    );
+
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Format a percentile to append to a split announcement
+pub fn format_percentile(percentile: f64) -> String {
+    format!("faster than {:.0}% of runs", percentile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::charts::render_png;
+    use crate::database::{get_latest_split_for_user, initialize_database};
+
+    async fn log_split(pool: &SqlitePool, user: &str, duration_ms: i32) {
+        sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, 0, 0, 0, ?2)")
+            .bind(user)
+            .bind(duration_ms)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_build_histogram() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let config = HistogramsConfig::default();
+        let category = Category::ALL[5];
+
+        let empty = build_histogram(&pool, &config, category, None, None).await.unwrap();
+        assert!(empty.buckets.is_empty());
+        assert!(render_histogram(&empty, None).contains("No runs yet"));
+
+        log_split(&pool, "alice", 30_500).await;
+        log_split(&pool, "bob", 30_900).await;
+        log_split(&pool, "alice", 33_200).await;
+        log_split(&pool, "bob", 34_000).await;
+
+        let histogram = build_histogram(&pool, &config, category, Some(1_000), Some("alice")).await.unwrap();
+        assert_eq!(histogram.total, 4);
+        let counts: Vec<(i32, i64, i64)> = histogram.buckets.iter().map(|b| (b.start_ms, b.count, b.highlighted)).collect();
+        assert_eq!(counts, vec![(30_000, 2, 1), (31_000, 0, 0), (32_000, 0, 0), (33_000, 1, 1), (34_000, 1, 0)]);
+
+        assert!(matches!(build_histogram(&pool, &config, category, Some(0), None).await, Err(AppError::Invalid(_))));
+        assert!(matches!(build_histogram(&pool, &config, category, Some(1), None).await, Err(AppError::Invalid(_))));
+
+        let svg = render_histogram(&histogram, Some(33_200));
+        assert_eq!(svg.matches("<rect").count(), 1 + 2 * 5);
+        assert!(render_png(&svg).is_ok());
+
+        // Faster than bob's 34s, slower than the other two
+        let split = get_latest_split_for_user(&pool, "alice").await.unwrap().unwrap();
+        let percentile = get_percentile(&pool, &split).await.unwrap().unwrap();
+        assert!((percentile - 100.0 / 3.0).abs() < 0.01);
+        assert_eq!(format_percentile(percentile), "faster than 33% of runs");
+    }
+}
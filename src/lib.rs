@@ -2,16 +2,42 @@
 //! 
 //! This application tracks split times and integrates with Discord.
 
+pub mod accounts;
+pub mod achievements;
+pub mod admin;
+pub mod announcements;
+pub mod charts;
+pub mod cli;
 pub mod error;
 pub mod models;
 pub mod config;
+pub mod csv_io;
+pub mod dashboard;
 pub mod database;
+pub mod digest;
+pub mod period;
+#[cfg(feature = "discord")]
 pub mod discord;
 pub mod handlers;
+pub mod histograms;
+pub mod notify;
+pub mod pages;
+pub mod scheduler;
+pub mod ratings;
+pub mod reload;
+pub mod seasons;
 pub mod signals;
+pub mod smtp;
+pub mod stats;
+pub mod stream;
+pub mod streaks;
+pub mod timers;
 pub mod validation;
+pub mod versus;
+pub mod webhooks;
+#[cfg(feature = "discord")]
 pub mod commands;
 
 pub use error::{AppError, Result};
-pub use models::{Split, SplitData, AppContext, AppState};
+pub use models::{Split, SplitData, Category, AppContext, AppState};
 pub use config::Config;
@@ -1,13 +1,23 @@
-use axum::{Router, routing::get, routing::post};
+use clap::Parser;
+use splits::cli::{Cli, CliCommand, run_command};
 use splits::database::{create_sqlite_database_if_does_not_exist, initialize_database};
-use splits::discord::{Handler, create_discord_client};
-use splits::handlers::{all_splits, new_split};
+#[cfg(feature = "discord")]
+use splits::discord::{Handler, announce_finished_seasons, create_discord_client};
+use splits::handlers::router;
+use splits::digest::schedule_digests;
+use splits::scheduler::{STREAK_REMINDERS_JOB, Scheduler, SystemClock, run_scheduler};
+use splits::seasons::{archive_finished_seasons, sync_seasons};
 use splits::signals::shutdown_signal;
+use splits::config::LiveConfig;
+use splits::reload::watch_config;
+use splits::webhooks::run_webhook_dispatcher;
+use splits::announcements::run_announcement_worker;
+use splits::stream::run_event_relay;
 use splits::{AppContext, AppState, Config, Result};
 use sqlx::SqlitePool;
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use tower_http::services::ServeDir;
+use std::net::SocketAddr;
+use std::time::Duration;
+use time::OffsetDateTime;
 use tracing::{debug, error, info};
 
 #[tokio::main]
@@ -22,9 +32,27 @@ async fn main() {
 }
 
 async fn run() -> Result<()> {
-    // Load configuration
-    let config = Config::load()?;
+    let cli = Cli::parse();
+
+    // The sample config must be reachable before there is a valid config to load
+    if cli.print_default_config {
+        print!("{}", Config::default_toml()?);
+        return Ok(());
+    }
+    if let Some(CliCommand::GenerateConfig { output }) = &cli.command {
+        return Config::generate_sample_config(output);
+    }
+
+    // Load configuration. Maintenance commands never connect to Discord, so its settings aren't checked for them.
+    let config = match cli.command {
+        Some(_) => Config::load(cli.config.as_deref())?,
+        None => Config::load_for_server(cli.config.as_deref())?,
+    };
     info!("Configuration loaded successfully");
+    if cli.check_config {
+        println!("Configuration is valid");
+        return Ok(());
+    }
 
     // Debug logs for extra information
     debug!("Server will start on: {}", config.server_address());
@@ -42,39 +70,115 @@ async fn run() -> Result<()> {
     // Initialize database tables
     initialize_database(&db_pool).await?;
 
-    let shared_context = Arc::new(Mutex::new(AppContext {
-        discord_ctx: None,
-        db_pool: db_pool.clone(),
-    }));
+    // Maintenance commands run instead of Discord and the HTTP server
+    if let Some(command) = cli.command {
+        return run_command(command, &db_pool, &config).await;
+    }
+
+    // Create any configured seasons
+    sync_seasons(&db_pool, &config.seasons, OffsetDateTime::now_utc().date()).await?;
+
+    let shared_context = AppContext::new(db_pool.clone());
+
+    // Reloaded in place when the config file changes or on SIGHUP
+    let live_config = LiveConfig::new(config.clone());
+    tokio::spawn(watch_config(live_config.clone(), cli.config.clone()));
 
     let app_state = AppState {
         context: shared_context.clone(),
-        config: config.clone(),
+        config: live_config.clone(),
     };
 
-    let handler = Handler {
-        context: shared_context.clone(),
-    };
-    let mut client = create_discord_client(&config, handler).await
-        .map_err(|e| splits::AppError::Other(format!("Failed to create Discord client: {}", e)))?;
+    let discord_enabled = config.discord.enabled && cfg!(feature = "discord");
+    if discord_enabled {
+        #[cfg(feature = "discord")]
+        start_discord(shared_context.clone(), &live_config).await?;
+    } else {
+        info!("Discord is disabled, running the HTTP API only");
+    }
 
-    // Run Discord client in a separate thread
+    // Periodically roll seasons over, archiving the ones that ended
+    let season_context = shared_context.clone();
+    let season_config = live_config.clone();
     tokio::spawn(async move {
-        if let Err(why) = client.start().await {
-            error!("Client error: {why:?}");
+        let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            let config = season_config.get();
+            roll_over_seasons(&season_context, &config, discord_enabled).await;
+            if let Err(e) = sync_seasons(&season_context.db_pool, &config.seasons, OffsetDateTime::now_utc().date()).await {
+                error!("Error syncing seasons: {}", e);
+            }
         }
     });
 
-    let app = Router::new()
-        .route("/api/v0/split/all", get(all_splits))
-        .route("/api/v0/split/new", post(new_split))
-        .with_state(app_state)
-        .fallback_service(ServeDir::new(&config.server.static_dir));
+    // Post scheduled digests and streak reminders, which only go to Discord
+    let mut scheduler = Scheduler::new(db_pool.clone(), SystemClock);
+    if discord_enabled {
+        schedule_digests(&mut scheduler, &config)?;
+        if config.streaks.reminders_enabled {
+            scheduler.add_job(STREAK_REMINDERS_JOB, &config.streaks.reminder_cron())?;
+        }
+    }
+    tokio::spawn(run_scheduler(scheduler, shared_context.clone(), live_config.clone()));
+
+    // Send queued announcements, including any left over from before a restart
+    tokio::spawn(run_announcement_worker(shared_context.clone(), live_config.clone()));
+
+    // Deliver webhooks from the outbox, including any queued before a restart
+    tokio::spawn(run_webhook_dispatcher(db_pool.clone(), live_config.clone()));
+
+    // Send live feed events to SSE and WebSocket clients, including ones recorded by the CLI
+    tokio::spawn(run_event_relay(db_pool.clone(), shared_context.events.clone()));
+
+    let app = router(app_state, &config.server.static_dir);
 
     let listener = tokio::net::TcpListener::bind(&config.server_address()).await?;
     info!("listening on {}", listener.local_addr()?);
-    axum::serve(listener, app)
+    // Client addresses are needed to throttle logins
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(shutdown_signal())
         .await?;
     Ok(())
 }
+
+/// Connect the Discord bot in the background
+#[cfg(feature = "discord")]
+async fn start_discord(context: AppContext, config: &LiveConfig) -> Result<()> {
+    let handler = Handler { context };
+    let mut client = create_discord_client(config, handler).await
+        .map_err(|e| splits::AppError::Other(format!("Failed to create Discord client: {}", e)))?;
+
+    // Run Discord client in a separate thread
+    tokio::spawn(async move {
+        if let Err(why) = client.start().await {
+            error!("Client error: {why:?}");
+        }
+    });
+    Ok(())
+}
+
+/// Archive seasons that ended, announcing them when the bot is running.
+/// While the bot is still connecting they're left for a later roll over, so no summary is lost.
+#[cfg(feature = "discord")]
+async fn roll_over_seasons(ctx: &AppContext, config: &Config, discord_enabled: bool) {
+    if !discord_enabled {
+        archive_seasons(&ctx.db_pool).await;
+    } else if let Some(http) = ctx.discord_http() {
+        announce_finished_seasons(&http, &ctx.db_pool, config).await;
+    } else {
+        info!("Discord is not connected yet, leaving finished seasons for the next roll over");
+    }
+}
+
+/// Archive seasons that ended
+#[cfg(not(feature = "discord"))]
+async fn roll_over_seasons(ctx: &AppContext, _config: &Config, _discord_enabled: bool) {
+    archive_seasons(&ctx.db_pool).await;
+}
+
+async fn archive_seasons(pool: &SqlitePool) {
+    if let Err(e) = archive_finished_seasons(pool, OffsetDateTime::now_utc().date()).await {
+        error!("Error archiving finished seasons: {}", e);
+    }
+}
@@ -1,12 +1,15 @@
-use serde::Deserialize;
-use serenity::prelude::Context;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "discord")]
+use serenity::http::Http;
 use sqlx::SqlitePool;
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use crate::config::Config;
+#[cfg(feature = "discord")]
+use std::sync::{Arc, OnceLock};
+use crate::accounts::LoginThrottle;
+use crate::config::LiveConfig;
+use crate::stream::EventBroadcaster;
 use crate::validation::{UsernameValidator, DurationValidator, FieldValidator, ValidationResult};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Split {
     pub id: i32,
     pub user: String,
@@ -17,6 +20,74 @@ pub struct Split {
     pub created_at: String
 }
 
+/// A leaderboard category. Elevator categories ignore `is_encumbered`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct Category {
+    pub is_down: bool,
+    pub is_elevator: bool,
+    pub is_encumbered: Option<bool>,
+}
+
+impl Category {
+    /// Every category a split can be ranked in
+    pub const ALL: [Category; 6] = [
+        // Elevator categories (is_encumbered is ignored for elevators)
+        Category { is_down: true, is_elevator: true, is_encumbered: None },   // down elevator
+        Category { is_down: false, is_elevator: true, is_encumbered: None },  // up elevator
+
+        // Stairs categories
+        Category { is_down: true, is_elevator: false, is_encumbered: Some(true) },   // down stairs encumbered
+        Category { is_down: true, is_elevator: false, is_encumbered: Some(false) },  // down stairs not encumbered
+        Category { is_down: false, is_elevator: false, is_encumbered: Some(true) },  // up stairs encumbered
+        Category { is_down: false, is_elevator: false, is_encumbered: Some(false) }, // up stairs not encumbered
+    ];
+
+    /// Get the category a split belongs to
+    pub fn of(split: &Split) -> Self {
+        Category {
+            is_down: split.is_down,
+            is_elevator: split.is_elevator,
+            is_encumbered: if split.is_elevator { None } else { split.is_encumbered },
+        }
+    }
+
+    /// URL friendly identifier, e.g. "up-stairs-encumbered"
+    pub fn slug(&self) -> String {
+        let direction = if self.is_down { "down" } else { "up" };
+        match (self.is_elevator, self.is_encumbered) {
+            (true, _) => format!("{}-elevator", direction),
+            (false, Some(true)) => format!("{}-stairs-encumbered", direction),
+            (false, _) => format!("{}-stairs-empty", direction),
+        }
+    }
+
+    /// Look up a category by its slug
+    pub fn from_slug(slug: &str) -> Option<Self> {
+        Category::ALL.into_iter().find(|category| category.slug() == slug)
+    }
+
+    /// Display name, e.g. "Up Stairs (Encumbered)"
+    pub fn name(&self) -> String {
+        let direction = if self.is_down { "Down" } else { "Up" };
+        let method = if self.is_elevator { "Elevator" } else { "Stairs" };
+        let encumbered_text = match self.is_encumbered {
+            Some(true) => " (Encumbered)",
+            Some(false) => " (No Items)",
+            None => "",
+        };
+        format!("{} {}{}", direction, method, encumbered_text)
+    }
+}
+
+/// A user's aggregate results in one category
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryStats {
+    pub category: Category,
+    pub runs: i64,
+    pub best_ms: i32,
+    pub average_ms: f64,
+}
+
 #[derive(Clone, Deserialize, Debug)]
 pub struct SplitData {
     pub user: String,
@@ -73,16 +144,41 @@ impl SplitData {
     }
 }
 
+/// Handles shared by requests and background tasks. Cheap to clone and used without
+/// locking, since the pool is already safe to share.
 #[derive(Clone)]
 pub struct AppContext {
-    pub discord_ctx: Option<Context>,
     pub db_pool: SqlitePool,
+    /// Live feed of splits for SSE and WebSocket clients
+    pub events: EventBroadcaster,
+    /// Password checks made per address, shared by login and registration
+    pub login_throttle: LoginThrottle,
+    /// Discord API client, set once when the bot is first ready
+    #[cfg(feature = "discord")]
+    pub discord_http: Arc<OnceLock<Arc<Http>>>,
+}
+
+impl AppContext {
+    /// Context before Discord, if enabled, has connected
+    pub fn new(db_pool: SqlitePool) -> Self {
+        Self {
+            db_pool,
+            events: EventBroadcaster::new(),
+            login_throttle: LoginThrottle::default(),
+            #[cfg(feature = "discord")]
+            discord_http: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// The Discord API client, once the bot has connected
+    #[cfg(feature = "discord")]
+    pub fn discord_http(&self) -> Option<Arc<Http>> {
+        self.discord_http.get().cloned()
+    }
 }
 
 #[derive(Clone)]
 pub struct AppState {
-    pub context: Arc<Mutex<AppContext>>,
-    pub config: Config,
+    pub context: AppContext,
+    pub config: LiveConfig,
 }
-
-pub type SharedAppContext = Arc<Mutex<AppContext>>;
@@ -0,0 +1,494 @@
+use crate::achievements::{AchievementDefinition, format_unlocked};
+use crate::config::{Config, NotifierTarget};
+use crate::database::{format_single_split, is_personal_best, is_world_record};
+use crate::error::{AppError, Result};
+use crate::histograms::get_percentile;
+use crate::models::{Category, Split};
+use crate::ratings::{RatingChange, format_rating_change};
+use crate::seasons::{get_active_season, is_season_record};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use std::sync::OnceLock;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::error;
+
+/// Header naming the event posted to generic webhooks
+pub const EVENT_HEADER: &str = "X-Splits-Event";
+/// Header carrying `sha256=<hex HMAC of the body>` when a webhook has a secret
+pub const SIGNATURE_HEADER: &str = "X-Splits-Signature";
+
+/// How long a notifier may take before it's abandoned
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait after a 429 response that doesn't say how long to back off
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Key of the Discord bot among notifiers
+pub const DISCORD_BOT_KEY: &str = "discord";
+
+/// What a new split achieved, most notable first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitEventKind {
+    WorldRecord,
+    SeasonRecord,
+    PersonalBest,
+    Split,
+}
+
+impl SplitEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SplitEventKind::WorldRecord => "world_record",
+            SplitEventKind::SeasonRecord => "season_record",
+            SplitEventKind::PersonalBest => "personal_best",
+            SplitEventKind::Split => "split",
+        }
+    }
+}
+
+/// A logged split and everything notifiers announce about it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitEvent {
+    pub kind: SplitEventKind,
+    pub split: Split,
+    /// Category slug, such as `up-stairs-empty`
+    pub category: String,
+    pub is_world_record: bool,
+    pub is_personal_best: bool,
+    /// Name of the running season if the split leads it
+    pub season_record: Option<String>,
+    pub percentile: Option<f64>,
+    pub rating_change: Option<RatingChange>,
+    pub achievements: Vec<AchievementDefinition>,
+}
+
+impl SplitEvent {
+    /// Announcement text, with Discord style `**bold**`
+    pub fn message(&self) -> String {
+        let mut content = format_single_split(&self.split, self.is_world_record, self.percentile);
+        if let (false, Some(season)) = (self.is_world_record, &self.season_record) {
+            content = format!("NEW SEASON {} RECORD! {} 🏆", season, content);
+        }
+        if let Some(change) = &self.rating_change {
+            content = format!("{}\n{}", content, format_rating_change(change));
+        }
+        if !self.achievements.is_empty() {
+            content = format!("{}\n{}", content, format_unlocked(&self.achievements));
+        }
+        content
+    }
+}
+
+/// Build the event for a split that was just logged
+pub async fn build_split_event(
+    pool: &SqlitePool,
+    split: Split,
+    achievements: Vec<AchievementDefinition>,
+    rating_change: Option<RatingChange>,
+) -> Result<SplitEvent> {
+    let is_world_record = is_world_record(pool, &split).await?;
+    let is_personal_best = is_world_record || is_personal_best(pool, &split).await?;
+    let percentile = get_percentile(pool, &split).await.unwrap_or_else(|e| {
+        error!("Error getting percentile of split {}: {}", split.id, e);
+        None
+    });
+    let season_record = match get_active_season(pool, OffsetDateTime::now_utc().date()).await {
+        Ok(Some(season)) => match is_season_record(pool, &season, &split).await {
+            Ok(true) => Some(season.name),
+            Ok(false) => None,
+            Err(e) => {
+                error!("Error checking if split is season record: {}", e);
+                None
+            }
+        },
+        Ok(None) => None,
+        Err(e) => {
+            error!("Error getting active season: {}", e);
+            None
+        }
+    };
+
+    let kind = if is_world_record {
+        SplitEventKind::WorldRecord
+    } else if season_record.is_some() {
+        SplitEventKind::SeasonRecord
+    } else if is_personal_best {
+        SplitEventKind::PersonalBest
+    } else {
+        SplitEventKind::Split
+    };
+
+    Ok(SplitEvent {
+        kind,
+        category: Category::of(&split).slug(),
+        split,
+        is_world_record,
+        is_personal_best,
+        season_record,
+        percentile,
+        rating_change,
+        achievements,
+    })
+}
+
+/// Somewhere new splits are announced
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Short name used in logs
+    fn name(&self) -> &'static str;
+
+    async fn notify(&self, event: &SplitEvent) -> Result<()>;
+}
+
+/// Shared client for every HTTP notifier
+pub(crate) fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("splits/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+/// Send a request, treating any non-success status as an error and 429 as a rate limit
+async fn send(request: reqwest::RequestBuilder) -> Result<()> {
+    let response = request.send().await.map_err(|e| AppError::Other(format!("Request failed: {}", e)))?;
+    let status = response.status();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .map(Duration::from_secs_f64)
+            .unwrap_or(DEFAULT_RETRY_AFTER);
+        return Err(AppError::RateLimited(retry_after));
+    }
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::Other(format!("Request failed with {}: {}", status, body)));
+    }
+    Ok(())
+}
+
+/// `sha256=<hex>` HMAC-SHA256 signature of a request body
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Posts to a Discord incoming webhook, no bot needed
+pub struct DiscordWebhookNotifier {
+    pub url: String,
+}
+
+#[async_trait]
+impl Notifier for DiscordWebhookNotifier {
+    fn name(&self) -> &'static str {
+        "Discord webhook"
+    }
+
+    async fn notify(&self, event: &SplitEvent) -> Result<()> {
+        let body = serde_json::json!({ "content": event.message() });
+        send(http_client().post(&self.url).json(&body)).await
+    }
+}
+
+/// Posts to a Slack compatible incoming webhook
+pub struct SlackNotifier {
+    pub url: String,
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &'static str {
+        "Slack"
+    }
+
+    async fn notify(&self, event: &SplitEvent) -> Result<()> {
+        // Slack's mrkdwn uses single asterisks for bold
+        let body = serde_json::json!({ "text": event.message().replace("**", "*") });
+        send(http_client().post(&self.url).json(&body)).await
+    }
+}
+
+/// Sends a message to a Matrix room through the client-server API
+pub struct MatrixNotifier {
+    pub homeserver: String,
+    pub room_id: String,
+    pub access_token: String,
+}
+
+#[async_trait]
+impl Notifier for MatrixNotifier {
+    fn name(&self) -> &'static str {
+        "Matrix"
+    }
+
+    async fn notify(&self, event: &SplitEvent) -> Result<()> {
+        // Transaction ids must be unique for each message sent with the access token
+        let txn_id = format!("split-{}-{}", event.split.id, OffsetDateTime::now_utc().unix_timestamp_nanos());
+        let mut url = reqwest::Url::parse(&self.homeserver)
+            .map_err(|e| AppError::Config(format!("Invalid Matrix homeserver {}: {}", self.homeserver, e)))?;
+        url.path_segments_mut()
+            .map_err(|_| AppError::Config(format!("Invalid Matrix homeserver {}", self.homeserver)))?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3", "rooms", &self.room_id, "send", "m.room.message", &txn_id]);
+
+        let body = serde_json::json!({ "msgtype": "m.text", "body": event.message().replace("**", "") });
+        send(http_client().put(url).bearer_auth(&self.access_token).json(&body)).await
+    }
+}
+
+/// Posts the event as JSON, signed when a secret is configured
+pub struct WebhookNotifier {
+    pub url: String,
+    pub secret: Option<String>,
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn notify(&self, event: &SplitEvent) -> Result<()> {
+        let body = serde_json::to_vec(event).map_err(|e| AppError::Other(e.to_string()))?;
+        let mut request = http_client()
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.kind.as_str());
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+        }
+        send(request.body(body)).await
+    }
+}
+
+impl From<&NotifierTarget> for Box<dyn Notifier> {
+    fn from(target: &NotifierTarget) -> Self {
+        match target.clone() {
+            NotifierTarget::DiscordWebhook { url } => Box::new(DiscordWebhookNotifier { url }),
+            NotifierTarget::Slack { url } => Box::new(SlackNotifier { url }),
+            NotifierTarget::Matrix { homeserver, room_id, access_token } => {
+                Box::new(MatrixNotifier { homeserver, room_id, access_token })
+            }
+            NotifierTarget::Webhook { url, secret } => Box::new(WebhookNotifier { url, secret }),
+        }
+    }
+}
+
+impl NotifierTarget {
+    /// Stable identifier of the target, so queued announcements find it after a reload
+    pub fn key(&self) -> String {
+        match self {
+            NotifierTarget::DiscordWebhook { url } => format!("discord_webhook:{}", url),
+            NotifierTarget::Slack { url } => format!("slack:{}", url),
+            NotifierTarget::Matrix { homeserver, room_id, .. } => format!("matrix:{}/{}", homeserver, room_id),
+            NotifierTarget::Webhook { url, .. } => format!("webhook:{}", url),
+        }
+    }
+}
+
+/// Keys of every notifier the configuration enables
+pub fn notifier_keys(config: &Config) -> Vec<String> {
+    let mut keys = Vec::new();
+    if config.discord.enabled && cfg!(feature = "discord") {
+        keys.push(DISCORD_BOT_KEY.to_string());
+    }
+    keys.extend(config.notifiers.targets.iter().map(NotifierTarget::key));
+    keys
+}
+
+/// Build the notifier with a key. The Discord bot is only available once it's connected.
+pub fn find_notifier(
+    config: &Config,
+    key: &str,
+    #[cfg(feature = "discord")] discord_http: Option<std::sync::Arc<serenity::http::Http>>,
+) -> Option<Box<dyn Notifier>> {
+    #[cfg(feature = "discord")]
+    if key == DISCORD_BOT_KEY && config.discord.enabled {
+        return discord_http.map(|http| {
+            Box::new(crate::discord::DiscordBotNotifier { http, channel_id: config.discord.channel_id }) as Box<dyn Notifier>
+        });
+    }
+    config.notifiers.targets.iter().find(|target| target.key() == key).map(Box::<dyn Notifier>::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{initialize_database, insert_split};
+    use crate::handlers::spawn_test_server;
+    use crate::models::SplitData;
+    use axum::Router;
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, Method, Uri};
+    use std::sync::{Arc, Mutex};
+
+    /// A request seen by the stand-in server
+    struct Received {
+        method: Method,
+        path: String,
+        headers: HeaderMap,
+        body: Bytes,
+    }
+
+    /// Local HTTP server recording every request, standing in for Discord, Slack, Matrix and webhooks
+    async fn stand_in() -> (String, Arc<Mutex<Vec<Received>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        let app = Router::new().fallback(move |method: Method, uri: Uri, headers: HeaderMap, body: Bytes| {
+            let log = log.clone();
+            async move {
+                log.lock().unwrap().push(Received { method, path: uri.path().to_string(), headers, body });
+                "ok"
+            }
+        });
+        (spawn_test_server(app).await, received)
+    }
+
+    fn event() -> SplitEvent {
+        SplitEvent {
+            kind: SplitEventKind::WorldRecord,
+            split: Split {
+                id: 7,
+                user: "alice".to_string(),
+                is_down: false,
+                is_elevator: false,
+                is_encumbered: Some(false),
+                duration_ms: 30_000,
+                created_at: "2025-09-01 12:00:00".to_string(),
+            },
+            category: "up-stairs-empty".to_string(),
+            is_world_record: true,
+            is_personal_best: true,
+            season_record: None,
+            percentile: None,
+            rating_change: None,
+            achievements: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notifiers() {
+        let (address, received) = stand_in().await;
+        let targets = [
+            // Nothing listens here
+            NotifierTarget::Webhook { url: "http://127.0.0.1:1/down".to_string(), secret: None },
+            NotifierTarget::DiscordWebhook { url: format!("{}/discord", address) },
+            NotifierTarget::Slack { url: format!("{}/slack", address) },
+            NotifierTarget::Matrix {
+                homeserver: format!("{}/", address),
+                room_id: "!room:example.org".to_string(),
+                access_token: "matrix-token".to_string(),
+            },
+            NotifierTarget::Webhook { url: format!("{}/hook", address), secret: Some("s3cret".to_string()) },
+        ];
+        let event = event();
+        for target in &targets {
+            let result = Box::<dyn Notifier>::from(target).notify(&event).await;
+            assert_eq!(result.is_ok(), !target.key().contains(":1/down"), "{:?}", result);
+        }
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 4);
+        let json = |r: &Received| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap();
+
+        assert_eq!(received[0].path, "/discord");
+        assert_eq!(json(&received[0])["content"], event.message());
+        assert_eq!(json(&received[1])["text"], event.message().replace("**", "*"));
+
+        assert_eq!(received[2].method, Method::PUT);
+        assert!(received[2].path.starts_with("/_matrix/client/v3/rooms/!room:example.org/send/m.room.message/split-7-"));
+        assert_eq!(received[2].headers["authorization"], "Bearer matrix-token");
+        assert_eq!(json(&received[2])["msgtype"], "m.text");
+
+        let hook = &received[3];
+        assert_eq!(hook.headers[EVENT_HEADER], "world_record");
+        assert_eq!(hook.headers[SIGNATURE_HEADER], sign("s3cret", &hook.body).as_str());
+        assert_ne!(sign("other", &hook.body), sign("s3cret", &hook.body));
+        assert_eq!(json(hook)["split"]["user"], "alice");
+        assert_eq!(json(hook)["kind"], "world_record");
+    }
+
+    fn split_data(user: &str, duration_ms: i32) -> SplitData {
+        SplitData { user: user.to_string(), is_down: false, is_elevator: false, duration_ms, is_encumbered: Some(false) }
+    }
+
+    #[tokio::test]
+    async fn test_build_split_event() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let log = |user: &'static str, duration_ms: i32| {
+            let pool = pool.clone();
+            async move {
+                let split = insert_split(&mut pool.acquire().await.unwrap(), &split_data(user, duration_ms)).await.unwrap();
+                build_split_event(&pool, split, Vec::new(), None).await.unwrap()
+            }
+        };
+
+        let event = log("alice", 30_000).await;
+        assert_eq!((event.kind, event.is_personal_best), (SplitEventKind::WorldRecord, true));
+
+        log("bob", 25_000).await;
+        let event = log("alice", 28_000).await;
+        assert_eq!(event.kind, SplitEventKind::PersonalBest);
+        assert_eq!(event.category, "up-stairs-empty");
+
+        let event = log("alice", 29_000).await;
+        assert_eq!(event.kind, SplitEventKind::Split);
+    }
+
+    /// Runs logged at the same moment each announce themselves, and only the first record-breaking
+    /// time of the batch can be a world record
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_split_events() {
+        let path = std::env::temp_dir().join(format!("splits-events-{}.db", std::process::id()));
+        let pool = SqlitePool::connect(&format!("sqlite:{}?mode=rwc", path.display())).await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        insert_split(&mut pool.acquire().await.unwrap(), &split_data("holder", 20_000)).await.unwrap();
+
+        let tasks: Vec<_> = (0..20)
+            .map(|i| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    let user = format!("runner{}", i);
+                    let split = insert_split(&mut pool.acquire().await.unwrap(), &split_data(&user, 19_000 - i * 10)).await.unwrap();
+                    (user, build_split_event(&pool, split, Vec::new(), None).await.unwrap())
+                })
+            })
+            .collect();
+        let mut events = Vec::new();
+        for task in tasks {
+            events.push(task.await.unwrap());
+        }
+
+        for (user, event) in &events {
+            assert_eq!(&event.split.user, user);
+        }
+        let mut ids: Vec<i32> = events.iter().map(|(_, event)| event.split.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), events.len());
+
+        // The first of the batch to land beat the old record outright
+        let (_, first) = events.iter().min_by_key(|(_, event)| event.split.id).unwrap();
+        assert!(first.is_world_record);
+        for (_, event) in &events {
+            let faster_before = events.iter().any(|(_, other)| other.split.id < event.split.id && other.split.duration_ms < event.split.duration_ms);
+            assert_eq!(event.is_world_record, !faster_before, "split {}", event.split.id);
+        }
+
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+}
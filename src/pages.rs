@@ -0,0 +1,272 @@
+use crate::charts::escape_xml;
+use crate::models::{Category, Split};
+use crate::stats::UserStats;
+use crate::validation::DurationValidator;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// Browsers and proxies may reuse a page for 30 seconds before checking back
+const CACHE_CONTROL: &str = "public, max-age=30";
+
+/// Most runs listed on the recent runs page and in a profile's history
+pub const HISTORY_LIMIT: usize = 100;
+
+/// Leaderboard periods linked from every board, as `?period=` values
+const PERIOD_TABS: [(&str, &str); 5] = [("all", "All Time"), ("season", "Season"), ("month", "This Month"), ("week", "This Week"), ("today", "Today")];
+
+const STYLE: &str = "\
+body{font-family:Arial,sans-serif;margin:0;background:#f3f4f6;color:#111827}\
+header{background:rgb(36,36,122);padding:12px 24px}\
+header a{color:#fff;margin-right:18px;text-decoration:none;font-weight:bold}\
+main{max-width:860px;margin:0 auto;padding:16px 24px}\
+table{width:100%;border-collapse:collapse;background:#fff;margin-bottom:24px}\
+th,td{text-align:left;padding:6px 10px;border-bottom:1px solid #e5e7eb}\
+th{background:#e5e7eb}\
+td.time{font-variant-numeric:tabular-nums}\
+nav.tabs a{margin-right:12px}\
+a{color:#1d4ed8}\
+.current{font-weight:bold;text-decoration:none;color:#111827}\
+.muted{color:#6b7280}";
+
+/// Percent-encode text for use as one segment of a link
+fn encode_path_segment(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+fn user_link(user: &str) -> String {
+    format!("<a href=\"/users/{}\">{}</a>", encode_path_segment(user), escape_xml(user))
+}
+
+fn category_link(category: Category) -> String {
+    format!("<a href=\"/leaderboard/{}\">{}</a>", category.slug(), escape_xml(&category.name()))
+}
+
+/// Wrap page content with the shared head, styles and navigation
+fn layout(title: &str, content: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\"><head><meta charset=\"UTF-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\
+         <title>{title} - Splits</title><style>{STYLE}</style></head>\
+         <body><header><a href=\"/leaderboard\">Leaderboards</a><a href=\"/recent\">Recent Runs</a><a href=\"/\">Timer</a></header>\
+         <main><h1>{title}</h1>{content}</main></body></html>\n",
+        title = escape_xml(title),
+    )
+}
+
+/// Table of runs, with the runner, category and date columns each optional
+fn runs_table(splits: &[Split], show_user: bool, show_category: bool) -> String {
+    let mut table = String::from("<table><tr>");
+    if show_user {
+        table.push_str("<th>Runner</th>");
+    }
+    if show_category {
+        table.push_str("<th>Category</th>");
+    }
+    table.push_str("<th>Time</th><th>Logged</th></tr>");
+    for split in splits {
+        table.push_str("<tr>");
+        if show_user {
+            let _ = write!(table, "<td>{}</td>", user_link(&split.user));
+        }
+        if show_category {
+            let _ = write!(table, "<td>{}</td>", category_link(Category::of(split)));
+        }
+        let _ = write!(
+            table,
+            "<td class=\"time\">{}</td><td class=\"muted\">{}</td></tr>",
+            DurationValidator::format_duration(split.duration_ms),
+            escape_xml(&split.created_at)
+        );
+    }
+    table.push_str("</table>");
+    table
+}
+
+/// Index of every category with its current record
+pub fn render_leaderboard_index(records: &[Split]) -> String {
+    let mut content = String::from("<table><tr><th>Category</th><th>Record</th><th>Runner</th></tr>");
+    for category in Category::ALL {
+        let record = records.iter().find(|split| Category::of(split) == category);
+        let _ = write!(content, "<tr><td>{}</td>", category_link(category));
+        match record {
+            Some(split) => {
+                let _ = write!(
+                    content,
+                    "<td class=\"time\">{}</td><td>{}</td></tr>",
+                    DurationValidator::format_duration(split.duration_ms),
+                    user_link(&split.user)
+                );
+            }
+            None => content.push_str("<td class=\"muted\" colspan=\"2\">No runs yet</td></tr>"),
+        }
+    }
+    content.push_str("</table>");
+    layout("Leaderboards", &content)
+}
+
+/// A category's leaderboard, one row per runner with their best time in the period
+pub fn render_leaderboard(category: Category, period: &str, selected: Option<&str>, entries: &[Split]) -> String {
+    let mut content = String::from("<nav class=\"tabs\">");
+    for (value, label) in PERIOD_TABS {
+        if selected.unwrap_or("all") == value {
+            let _ = write!(content, "<span class=\"current\">{}</span> ", label);
+        } else {
+            let _ = write!(content, "<a href=\"/leaderboard/{}?period={}\">{}</a> ", category.slug(), value, label);
+        }
+    }
+    let _ = write!(content, "</nav><h2>{}</h2>", escape_xml(period));
+
+    if entries.is_empty() {
+        content.push_str("<p class=\"muted\">No runs in this period.</p>");
+    } else {
+        content.push_str("<table><tr><th>#</th><th>Runner</th><th>Time</th><th>Logged</th></tr>");
+        for (rank, split) in entries.iter().enumerate() {
+            let _ = write!(
+                content,
+                "<tr><td>{}</td><td>{}</td><td class=\"time\">{}</td><td class=\"muted\">{}</td></tr>",
+                rank + 1,
+                user_link(&split.user),
+                DurationValidator::format_duration(split.duration_ms),
+                escape_xml(&split.created_at)
+            );
+        }
+        content.push_str("</table>");
+    }
+    layout(&category.name(), &content)
+}
+
+/// A runner's personal bests, streaks and run history, newest first
+pub fn render_profile(stats: &UserStats, history: &[Split]) -> String {
+    let mut content = format!(
+        "<p>{} run(s). Streak: {} day(s), longest {}.</p><h2>Personal Bests</h2>",
+        stats.runs, stats.streaks.current, stats.streaks.longest
+    );
+    content.push_str(&runs_table(&stats.personal_bests, false, true));
+    content.push_str("<h2>History</h2>");
+    content.push_str(&runs_table(history, false, true));
+    if stats.runs as usize > history.len() {
+        let _ = write!(content, "<p class=\"muted\">Showing the latest {} runs.</p>", history.len());
+    }
+    layout(&stats.user, &content)
+}
+
+/// The most recently logged runs across everyone
+pub fn render_recent(splits: &[Split]) -> String {
+    let content = if splits.is_empty() {
+        "<p class=\"muted\">No runs logged yet.</p>".to_string()
+    } else {
+        runs_table(splits, true, true)
+    };
+    layout("Recent Runs", &content)
+}
+
+/// A simple error page, such as for an unknown category
+pub fn render_error(title: &str, message: &str) -> String {
+    layout(title, &format!("<p>{}</p>", escape_xml(message)))
+}
+
+/// Page a login email links to, with a button that uses up the link
+pub fn render_login_confirm(token: &str) -> String {
+    let action = format!("/api/v1/login/email/{}", encode_path_segment(token));
+    layout(
+        "Log in",
+        &format!(
+            "<p>Log in to Splits on this device?</p>\n<form action=\"{}\" method=\"post\"><button type=\"submit\">Log in</button></form>",
+            escape_xml(&action)
+        ),
+    )
+}
+
+/// Respond with a page that caches can keep for a short while and then revalidate by ETag.
+/// Answers 304 Not Modified when the client already has this exact page.
+pub fn cached_html(request_headers: &HeaderMap, html: String) -> Response {
+    let etag = format!("\"{}\"", &hex::encode(Sha256::digest(html.as_bytes()))[..32]);
+    let not_modified = request_headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|candidate| candidate.trim().trim_start_matches("W/") == etag));
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response()
+    };
+    let headers = response.headers_mut();
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL));
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, etag);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaks::Streaks;
+
+    fn split(id: i32, user: &str, duration_ms: i32) -> Split {
+        Split {
+            id,
+            user: user.to_string(),
+            is_down: false,
+            is_elevator: false,
+            is_encumbered: Some(true),
+            duration_ms,
+            created_at: "2025-09-01 12:00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_pages_escape_and_link() {
+        let runner = "<b>al ice</b>";
+        let page = render_recent(&[split(1, runner, 45_500)]);
+        assert!(page.contains("<a href=\"/users/%3Cb%3Eal%20ice%3C%2Fb%3E\">&lt;b&gt;al ice&lt;/b&gt;</a>"));
+        assert!(page.contains("<a href=\"/leaderboard/up-stairs-encumbered\">Up Stairs (Encumbered)</a>"));
+        assert!(page.contains("45.500s"));
+        assert!(!page.contains("<script"));
+
+        let category = Category::of(&split(1, "bob", 1));
+        let board = render_leaderboard(category, "This Week", Some("week"), &[split(2, "bob", 40_000), split(1, "alice", 41_000)]);
+        assert!(board.contains("<span class=\"current\">This Week</span>"));
+        assert!(board.contains("<a href=\"/leaderboard/up-stairs-encumbered?period=month\">"));
+        assert!(board.find("bob").unwrap() < board.find("alice").unwrap());
+
+        let stats = UserStats {
+            user: "bob".to_string(),
+            runs: 3,
+            streaks: Streaks { current: 2, longest: 5, last_active: None },
+            personal_bests: vec![split(2, "bob", 40_000)],
+        };
+        let profile = render_profile(&stats, &[split(3, "bob", 42_000), split(2, "bob", 40_000)]);
+        assert!(profile.contains("3 run(s). Streak: 2 day(s), longest 5."));
+        assert!(profile.contains("Showing the latest 2 runs."));
+    }
+
+    #[test]
+    fn test_cached_html() {
+        let response = cached_html(&HeaderMap::new(), render_recent(&[]));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], CACHE_CONTROL);
+        let etag = response.headers()[header::ETAG].clone();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag.clone());
+        let response = cached_html(&headers, render_recent(&[]));
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+
+        let response = cached_html(&headers, render_recent(&[split(1, "alice", 45_000)]));
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::str::FromStr;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time};
+
+/// Format used by SQLite's CURRENT_TIMESTAMP, which is what `created_at` stores
+pub const TIMESTAMP_FORMAT: &[time::format_description::BorrowedFormatItem<'static>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+
+const DATE_FORMAT: &[time::format_description::BorrowedFormatItem<'static>] =
+    format_description!("[year]-[month]-[day]");
+
+/// Time window a leaderboard is scoped to (all bounds are UTC, like `created_at`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    AllTime,
+    Today,
+    /// Since Monday of the current week
+    Week,
+    /// Since the first day of the current month
+    Month,
+    /// Inclusive range of whole days
+    Custom { from: Date, to: Date },
+}
+
+impl Period {
+    /// Build a period from a name and optional custom range dates (YYYY-MM-DD)
+    pub fn parse(period: Option<&str>, from: Option<&str>, to: Option<&str>) -> Result<Self, String> {
+        match (from, to) {
+            (None, None) => period.map_or(Ok(Period::AllTime), Period::from_str),
+            (Some(from), Some(to)) => {
+                if period.is_some_and(|p| p != "custom") {
+                    return Err("from/to can only be used with the custom period".to_string());
+                }
+                let from = parse_date(from)?;
+                let to = parse_date(to)?;
+                if from > to {
+                    return Err("from must not be after to".to_string());
+                }
+                Ok(Period::Custom { from, to })
+            }
+            _ => Err("custom periods need both from and to".to_string()),
+        }
+    }
+
+    /// Get the half-open `[start, end)` bounds for this period relative to `now`
+    pub fn bounds(&self, now: OffsetDateTime) -> (Option<PrimitiveDateTime>, Option<PrimitiveDateTime>) {
+        let today = now.date();
+        let start_of = |date: Date| Some(PrimitiveDateTime::new(date, Time::MIDNIGHT));
+        match self {
+            Period::AllTime => (None, None),
+            Period::Today => (start_of(today), None),
+            Period::Week => {
+                let days_since_monday = today.weekday().number_days_from_monday() as i64;
+                (start_of(today - Duration::days(days_since_monday)), None)
+            }
+            Period::Month => (start_of(today.replace_day(1).expect("day 1 is always valid")), None),
+            Period::Custom { from, to } => (start_of(*from), start_of(*to + Duration::days(1))),
+        }
+    }
+
+    /// Same as `bounds`, formatted for comparison against `created_at`
+    pub fn sql_bounds(&self, now: OffsetDateTime) -> (Option<String>, Option<String>) {
+        let (start, end) = self.bounds(now);
+        let format = |dt: PrimitiveDateTime| dt.format(TIMESTAMP_FORMAT).expect("timestamp format is valid");
+        (start.map(format), end.map(format))
+    }
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "all" | "alltime" | "all-time" => Ok(Period::AllTime),
+            "today" | "day" => Ok(Period::Today),
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            "custom" => Err("custom periods need both from and to".to_string()),
+            other => Err(format!("unknown period '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Period::AllTime => write!(f, "All Time"),
+            Period::Today => write!(f, "Today"),
+            Period::Week => write!(f, "This Week"),
+            Period::Month => write!(f, "This Month"),
+            Period::Custom { from, to } => write!(
+                f,
+                "{} to {}",
+                from.format(DATE_FORMAT).map_err(|_| fmt::Error)?,
+                to.format(DATE_FORMAT).map_err(|_| fmt::Error)?
+            ),
+        }
+    }
+}
+
+/// Parse a YYYY-MM-DD date
+pub fn parse_date(s: &str) -> Result<Date, String> {
+    Date::parse(s.trim(), DATE_FORMAT).map_err(|_| format!("invalid date '{}', expected YYYY-MM-DD", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_period_bounds() {
+        // 2025-09-04 is a Thursday
+        let now = datetime!(2025-09-04 15:30:00 UTC);
+
+        assert_eq!(Period::AllTime.sql_bounds(now), (None, None));
+        assert_eq!(Period::Today.sql_bounds(now), (Some("2025-09-04 00:00:00".to_string()), None));
+        assert_eq!(Period::Week.sql_bounds(now), (Some("2025-09-01 00:00:00".to_string()), None));
+        assert_eq!(Period::Month.sql_bounds(now), (Some("2025-09-01 00:00:00".to_string()), None));
+
+        let custom = Period::parse(None, Some("2025-08-01"), Some("2025-08-31")).unwrap();
+        assert_eq!(
+            custom.sql_bounds(now),
+            (Some("2025-08-01 00:00:00".to_string()), Some("2025-09-01 00:00:00".to_string()))
+        );
+
+        assert!(Period::parse(None, Some("2025-08-31"), Some("2025-08-01")).is_err());
+        assert!(Period::parse(Some("week"), Some("2025-08-01"), None).is_err());
+        assert!(Period::parse(Some("fortnight"), None, None).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::config::RatingsConfig;
+use crate::error::Result;
+use crate::models::{Category, Split};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use tracing::debug;
+
+/// How a split moved its runner's rating
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RatingChange {
+    pub before: f64,
+    pub after: f64,
+}
+
+impl RatingChange {
+    pub fn delta(&self) -> f64 {
+        self.after - self.before
+    }
+}
+
+/// A user's current rating
+#[derive(Debug, Clone, Serialize)]
+pub struct Rating {
+    pub user: String,
+    pub rating: f64,
+    pub matches: i64,
+}
+
+/// Probability that a player rated `rating` beats one rated `opponent`
+fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// Rate a split as one match against the category's recent runs by other users.
+/// Returns `None` when there was nobody to compare against. The transaction takes the
+/// write lock up front, since a split rated at the same time couldn't upgrade a read lock.
+pub async fn rate_split(pool: &SqlitePool, config: &RatingsConfig, split: &Split) -> Result<Option<RatingChange>> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    let change = rate_split_in(&mut tx, config, split).await?;
+    tx.commit().await?;
+    Ok(change)
+}
+
+async fn rate_split_in(conn: &mut SqliteConnection, config: &RatingsConfig, split: &Split) -> Result<Option<RatingChange>> {
+    let category = Category::of(split);
+
+    let opponents = sqlx::query(
+        "SELECT s.duration_ms, COALESCE(r.rating, ?6) FROM splits s
+         LEFT JOIN ratings r ON r.user = s.user
+         WHERE s.is_down = ?1 AND s.is_elevator = ?2 AND (?3 IS NULL OR s.is_encumbered = ?3)
+         AND s.user != ?4 AND s.id < ?5
+         ORDER BY s.id DESC LIMIT ?7"
+    )
+    .bind(category.is_down)
+    .bind(category.is_elevator)
+    .bind(category.is_encumbered)
+    .bind(&split.user)
+    .bind(split.id)
+    .bind(config.initial_rating)
+    .bind(config.opponents)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    if opponents.is_empty() {
+        debug!("No opponents to rate split {} against", split.id);
+        return Ok(None);
+    }
+
+    let before: f64 = sqlx::query_scalar("SELECT rating FROM ratings WHERE user = ?1")
+        .bind(&split.user)
+        .fetch_optional(&mut *conn)
+        .await?
+        .unwrap_or(config.initial_rating);
+
+    // The whole split counts as one match, so each opponent gets an equal share of K
+    let total: f64 = opponents
+        .iter()
+        .map(|row| {
+            let duration_ms: i32 = row.get(0);
+            let opponent_rating: f64 = row.get(1);
+            let score = match split.duration_ms.cmp(&duration_ms) {
+                std::cmp::Ordering::Less => 1.0,
+                std::cmp::Ordering::Equal => 0.5,
+                std::cmp::Ordering::Greater => 0.0,
+            };
+            score - expected_score(before, opponent_rating)
+        })
+        .sum();
+    let after = before + config.k_factor * total / opponents.len() as f64;
+
+    sqlx::query(
+        "INSERT INTO ratings (user, rating, matches) VALUES (?1, ?2, 1)
+         ON CONFLICT(user) DO UPDATE SET rating = excluded.rating, matches = matches + 1"
+    )
+    .bind(&split.user)
+    .bind(after)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("INSERT INTO rating_history (split_id, user, rating_before, rating_after) VALUES (?1, ?2, ?3, ?4)")
+        .bind(split.id)
+        .bind(&split.user)
+        .bind(before)
+        .bind(after)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(Some(RatingChange { before, after }))
+}
+
+/// Rebuild every rating by replaying all splits in the order they were logged,
+/// such as after splits were hidden or deleted. Returns the number of splits replayed.
+/// It's one transaction, so a split logged meanwhile waits and is rated once, afterwards.
+pub async fn recompute_ratings(pool: &SqlitePool, config: &RatingsConfig) -> Result<usize> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    sqlx::query("DELETE FROM ratings").execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM rating_history").execute(&mut *tx).await?;
+
+    let rows = sqlx::query("SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits ORDER BY id ASC")
+        .fetch_all(&mut *tx)
+        .await?;
+    for row in &rows {
+        let split = Split {
+            id: row.get(0),
+            user: row.get(1),
+            is_down: row.get(2),
+            is_elevator: row.get(3),
+            is_encumbered: row.get(4),
+            duration_ms: row.get(5),
+            created_at: row.get(6),
+        };
+        rate_split_in(&mut tx, config, &split).await?;
+    }
+    tx.commit().await?;
+    Ok(rows.len())
+}
+
+/// Get every rated user, best first
+pub async fn get_ratings(pool: &SqlitePool) -> Result<Vec<Rating>> {
+    let rows = sqlx::query("SELECT user, rating, matches FROM ratings ORDER BY rating DESC, user ASC")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Rating {
+            user: row.get(0),
+            rating: row.get(1),
+            matches: row.get(2),
+        })
+        .collect())
+}
+
+/// Format a rating change to append to a split announcement
+pub fn format_rating_change(change: &RatingChange) -> String {
+    let arrow = if change.delta() >= 0.0 { "📈" } else { "📉" };
+    format!("{} Rating: {:.0} ({:+.0})", arrow, change.after, change.delta())
+}
+
+/// Most runners listed in the rating ranking message
+pub const RATINGS_SHOWN: usize = 20;
+
+/// Longest message Discord accepts, in characters
+const MESSAGE_LIMIT: usize = 2000;
+
+/// Format the top of the global rating ranking, short enough for one Discord message
+pub fn format_ratings(ratings: &[Rating]) -> String {
+    if ratings.is_empty() {
+        return "No ratings yet.".to_string();
+    }
+
+    let mut formatted = String::from("**Skill Ratings:**\n");
+    let mut shown = 0;
+    for (rank, rating) in ratings.iter().take(RATINGS_SHOWN).enumerate() {
+        let line = format!("{}. {} - {:.0} ({} matches)\n", rank + 1, rating.user, rating.rating, rating.matches);
+        // Leave room for the "and N more" line
+        if formatted.chars().count() + line.chars().count() > MESSAGE_LIMIT - 40 {
+            break;
+        }
+        formatted.push_str(&line);
+        shown += 1;
+    }
+    if ratings.len() > shown {
+        formatted.push_str(&format!("...and {} more\n", ratings.len() - shown));
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{get_latest_split_for_user, initialize_database};
+
+    async fn log_split(pool: &SqlitePool, user: &str, duration_ms: i32) -> Option<RatingChange> {
+        sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, 0, 0, 0, ?2)")
+            .bind(user)
+            .bind(duration_ms)
+            .execute(pool)
+            .await
+            .unwrap();
+        let split = get_latest_split_for_user(pool, user).await.unwrap().unwrap();
+        rate_split(pool, &RatingsConfig::default(), &split).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rate_split() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+
+        // Nobody to race against yet
+        assert_eq!(log_split(&pool, "alice", 30_000).await, None);
+
+        // Evenly rated players trade half of K
+        let win = log_split(&pool, "bob", 25_000).await.unwrap();
+        assert_eq!((win.before, win.after), (1500.0, 1516.0));
+        // Losing to a higher rated player costs less than half of K
+        let loss = log_split(&pool, "alice", 31_000).await.unwrap();
+        assert!(loss.delta() < 0.0 && loss.delta() > -16.0);
+
+        let ratings = get_ratings(&pool).await.unwrap();
+        assert_eq!(ratings.iter().map(|r| r.user.as_str()).collect::<Vec<_>>(), vec!["bob", "alice"]);
+    }
+
+    /// Splits rated at the same moment each get their rating and history row
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_ratings() {
+        let path = std::env::temp_dir().join(format!("splits-ratings-{}.db", std::process::id()));
+        let pool = SqlitePool::connect(&format!("sqlite:{}?mode=rwc", path.display())).await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        for i in 0..20 {
+            sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, 0, 0, 0, ?2)")
+                .bind(format!("runner{}", i))
+                .bind(30_000 - i * 100)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let splits = crate::database::get_all_splits(&pool).await.unwrap();
+
+        let tasks: Vec<_> = splits
+            .into_iter()
+            .map(|split| {
+                let pool = pool.clone();
+                tokio::spawn(async move { rate_split(&pool, &RatingsConfig::default(), &split).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        let history: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM rating_history").fetch_one(&pool).await.unwrap();
+        assert_eq!(history, 19);
+
+        // Replaying gives the same history, in the order the splits were logged
+        assert_eq!(recompute_ratings(&pool, &RatingsConfig::default()).await.unwrap(), 20);
+        let history: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM rating_history").fetch_one(&pool).await.unwrap();
+        assert_eq!(history, 19);
+
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_format_ratings_fits_a_message() {
+        let rating = |user: String| Rating { user, rating: 1500.0, matches: 10 };
+        let ratings: Vec<Rating> = (0..100).map(|i| rating(format!("runner{}", i))).collect();
+        let formatted = format_ratings(&ratings);
+        assert!(formatted.contains("20. runner19 - 1500 (10 matches)"));
+        assert!(formatted.ends_with("...and 80 more\n"));
+
+        // Long names are cut off by length before the count
+        let ratings: Vec<Rating> = (0..30).map(|i| rating(format!("{}{}", "x".repeat(200), i))).collect();
+        let formatted = format_ratings(&ratings);
+        assert!(formatted.chars().count() <= MESSAGE_LIMIT);
+        assert!(formatted.ends_with("more\n"));
+    }
+}
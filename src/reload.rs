@@ -0,0 +1,226 @@
+use crate::config::{Config, DEFAULT_CONFIG_PATH, LiveConfig};
+use crate::error::{AppError, Result};
+use crate::signals::ReloadSignal;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+
+/// How often the config file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Fields only read at startup, which a reload can't apply. The channel id isn't one,
+/// every message looks it up in the current configuration.
+const RESTART_REQUIRED: [&str; 7] = [
+    "discord.enabled",
+    "discord.token",
+    "database.",
+    "server.",
+    "digest.",
+    "streaks.reminder",
+    "streaks.utc_offset_minutes",
+];
+
+/// Fields whose values are never logged
+const SECRET: [&str; 5] = ["discord.token", "notifiers.targets", "webhooks.subscriptions", "admin.token", "accounts.smtp.password"];
+
+/// One configuration field that changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    /// Dotted path of the field, such as `validation.max_duration_ms`
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl ConfigChange {
+    /// Whether the change only takes effect after a restart
+    pub fn requires_restart(&self) -> bool {
+        RESTART_REQUIRED.iter().any(|prefix| self.field.starts_with(prefix))
+    }
+}
+
+impl std::fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |value: &Option<String>| match value {
+            _ if SECRET.contains(&self.field.as_str()) => "<redacted>".to_string(),
+            Some(value) => value.clone(),
+            None => "<unset>".to_string(),
+        };
+        write!(f, "{}: {} -> {}", self.field, show(&self.before), show(&self.after))
+    }
+}
+
+/// Flatten a TOML value into dotted field paths and their values. Arrays are compared whole.
+fn flatten(prefix: &str, value: &toml::Value, fields: &mut Vec<(String, String)>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(&path, value, fields);
+            }
+        }
+        _ => fields.push((prefix.to_string(), value.to_string())),
+    }
+}
+
+/// List every field that differs between two configurations
+pub fn diff_configs(before: &Config, after: &Config) -> Result<Vec<ConfigChange>> {
+    let fields = |config: &Config| -> Result<Vec<(String, String)>> {
+        let value = toml::Value::try_from(config).map_err(|e| AppError::Config(e.to_string()))?;
+        let mut fields = Vec::new();
+        flatten("", &value, &mut fields);
+        Ok(fields)
+    };
+    let (before, after) = (fields(before)?, fields(after)?);
+    let lookup = |fields: &[(String, String)], field: &str| fields.iter().find(|(f, _)| f == field).map(|(_, v)| v.clone());
+
+    let mut changes: Vec<ConfigChange> = Vec::new();
+    for (field, _) in before.iter().chain(&after) {
+        if changes.iter().any(|c| &c.field == field) {
+            continue;
+        }
+        let (old, new) = (lookup(&before, field), lookup(&after, field));
+        if old != new {
+            changes.push(ConfigChange { field: field.clone(), before: old, after: new });
+        }
+    }
+    Ok(changes)
+}
+
+/// Carry the running values of the fields in `RESTART_REQUIRED` over to a reloaded
+/// configuration, so nothing reads a value the server wasn't started with
+fn keep_restart_only(running: &Config, config: &mut Config) {
+    config.discord.enabled = running.discord.enabled;
+    config.discord.token = running.discord.token.clone();
+    config.database = running.database.clone();
+    config.server = running.server.clone();
+    config.digest = running.digest.clone();
+    config.streaks.reminder_hour = running.streaks.reminder_hour;
+    config.streaks.reminders_enabled = running.streaks.reminders_enabled;
+    config.streaks.utc_offset_minutes = running.streaks.utc_offset_minutes;
+}
+
+/// Load the configuration again and swap it in. An invalid configuration is rejected
+/// and the running one kept. Fields that need a restart keep their running values.
+/// Returns what changed, including those.
+pub fn reload_config(live: &LiveConfig, path: Option<&Path>) -> Result<Vec<ConfigChange>> {
+    let mut config = Config::load_for_server(path)?;
+    let running = live.get();
+    let changes = diff_configs(&running, &config)?;
+    if changes.is_empty() {
+        info!("Configuration reloaded, nothing changed");
+        return Ok(changes);
+    }
+
+    keep_restart_only(&running, &mut config);
+    live.swap(config);
+    for change in &changes {
+        if change.requires_restart() {
+            warn!("Config changed {} (takes effect after a restart)", change);
+        } else {
+            info!("Config changed {}", change);
+        }
+    }
+    Ok(changes)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reload the configuration whenever the file changes or on SIGHUP
+pub async fn watch_config(live: LiveConfig, path: Option<PathBuf>) {
+    let mut reload_signal = match ReloadSignal::new() {
+        Ok(signal) => Some(signal),
+        Err(e) => {
+            warn!("Could not listen for SIGHUP, only watching the config file: {}", e);
+            None
+        }
+    };
+    let watched = path.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+    let mut last_modified = modified(&watched);
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        let trigger = tokio::select! {
+            _ = interval.tick() => {
+                let current = modified(&watched);
+                if current == last_modified {
+                    continue;
+                }
+                last_modified = current;
+                "file change"
+            }
+            _ = async {
+                match reload_signal.as_mut() {
+                    Some(signal) => signal.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => "SIGHUP",
+        };
+
+        info!("Reloading configuration after {}", trigger);
+        if let Err(e) = reload_config(&live, path.as_deref()) {
+            error!("Rejected configuration reload, keeping the running configuration: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_reload_config() {
+        let path = std::env::temp_dir().join(format!("splits-reload-{}.toml", std::process::id()));
+        let mut config = Config::default();
+        config.discord.token = "token".to_string();
+        config.discord.channel_id = 1;
+        let live = LiveConfig::new(config.clone());
+
+        config.validation.username_blacklist = vec!["mallory".to_string()];
+        config.validation.max_duration_ms = 60_000;
+        config.server.port = 1234;
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+
+        let snapshot = live.get();
+        let changes = reload_config(&live, Some(&path)).unwrap();
+        let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["server.port", "validation.max_duration_ms", "validation.username_blacklist"]);
+        assert!(changes[0].requires_restart() && !changes[1].requires_restart());
+        let change = |field: &str| ConfigChange { field: field.to_string(), before: None, after: None };
+        assert!(change("discord.enabled").requires_restart() && !change("discord.channel_id").requires_restart());
+        assert_eq!(changes[1].to_string(), "validation.max_duration_ms: 86400000 -> 60000");
+        assert_eq!(live.get().validation.max_duration_ms, 60_000);
+        // Earlier snapshots are unaffected
+        assert_eq!(snapshot.validation.max_duration_ms, 86_400_000);
+
+        // Invalid configurations are rejected and the running one kept
+        fs::write(&path, "[validation\n").unwrap();
+        assert!(reload_config(&live, Some(&path)).is_err());
+        config.histograms.bucket_ms = 0;
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        assert!(reload_config(&live, Some(&path)).is_err());
+        assert_eq!(live.get().histograms.bucket_ms, 1000);
+
+        // Restart-only fields are reported but keep their running values, so a bot that was
+        // never started doesn't get announcements queued for it
+        let running = live.get();
+        config.histograms.bucket_ms = 1000;
+        config.discord.enabled = !running.discord.enabled;
+        config.discord.token = "new token".to_string();
+        config.streaks.reminder_hour = 7;
+        config.streaks.reminders_enabled = !running.streaks.reminders_enabled;
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        let changes = reload_config(&live, Some(&path)).unwrap();
+        assert!(changes.iter().any(|c| c.field == "discord.enabled" && c.requires_restart()));
+        assert_eq!(live.get().discord.enabled, running.discord.enabled);
+        assert!(diff_configs(&running, &live.get()).unwrap().iter().all(|c| !c.requires_restart()));
+
+        let token_change = ConfigChange { field: "discord.token".to_string(), before: Some("a".into()), after: Some("b".into()) };
+        assert_eq!(token_change.to_string(), "discord.token: <redacted> -> <redacted>");
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+use crate::config::{Config, LiveConfig};
+#[cfg(feature = "discord")]
+use crate::{digest, discord::{post_digest, send_streak_reminders}};
+use crate::error::{AppError, Result};
+use crate::models::AppContext;
+use crate::period::TIMESTAMP_FORMAT;
+use sqlx::SqlitePool;
+use std::str::FromStr;
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
+#[cfg(feature = "discord")]
+use tracing::info;
+use tracing::{debug, error, warn};
+
+/// Name of the job reminding users about expiring streaks
+pub const STREAK_REMINDERS_JOB: &str = "streak_reminders";
+
+/// Source of the current time, injectable so schedules can be tested
+pub trait Clock: Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+}
+
+/// Clock backed by the system time
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+/// One field of a cron expression, stored as the set of values it matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CronField {
+    allowed: Vec<bool>,
+}
+
+impl CronField {
+    /// Parse `*`, `5`, `1-5`, `*/15`, `1-30/2` and comma separated lists of those
+    fn parse(field: &str, min: u32, max: u32) -> std::result::Result<Self, String> {
+        let mut allowed = vec![false; max as usize + 1];
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u32>().map_err(|_| format!("invalid step '{}'", step))?),
+                None => (part, 1),
+            };
+            if step == 0 {
+                return Err("step cannot be zero".to_string());
+            }
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some((start, end)) = range.split_once('-') {
+                (parse_cron_value(start, min, max)?, parse_cron_value(end, min, max)?)
+            } else {
+                let value = parse_cron_value(range, min, max)?;
+                // A bare value with a step ("5/10") runs from the value to the end
+                (value, if part.contains('/') { max } else { value })
+            };
+            if start > end {
+                return Err(format!("invalid range '{}'", range));
+            }
+            for value in (start..=end).step_by(step as usize) {
+                allowed[value as usize] = true;
+            }
+        }
+        Ok(CronField { allowed })
+    }
+
+    fn matches(&self, value: u32) -> bool {
+        self.allowed.get(value as usize).copied().unwrap_or(false)
+    }
+
+    fn is_wildcard(&self, min: u32, max: u32) -> bool {
+        (min..=max).all(|value| self.matches(value))
+    }
+}
+
+fn parse_cron_value(value: &str, min: u32, max: u32) -> std::result::Result<u32, String> {
+    let parsed = value.parse::<u32>().map_err(|_| format!("invalid value '{}'", value))?;
+    if parsed < min || parsed > max {
+        return Err(format!("value {} out of range {}-{}", parsed, min, max));
+    }
+    Ok(parsed)
+}
+
+/// A five field cron schedule (minute hour day-of-month month day-of-week), evaluated in UTC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minute: CronField,
+    hour: CronField,
+    day_of_month: CronField,
+    month: CronField,
+    /// 0 and 7 are both Sunday
+    day_of_week: CronField,
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(format!("expected 5 fields in cron expression '{}'", s));
+        };
+        Ok(CronSchedule {
+            minute: CronField::parse(minute, 0, 59)?,
+            hour: CronField::parse(hour, 0, 23)?,
+            day_of_month: CronField::parse(day_of_month, 1, 31)?,
+            month: CronField::parse(month, 1, 12)?,
+            day_of_week: CronField::parse(day_of_week, 0, 7)?,
+        })
+    }
+}
+
+impl CronSchedule {
+    /// Check whether the schedule fires during the given minute
+    pub fn matches(&self, at: OffsetDateTime) -> bool {
+        self.minute.matches(at.minute() as u32)
+            && self.hour.matches(at.hour() as u32)
+            && self.month.matches(at.month() as u32)
+            && self.matches_day(at)
+    }
+
+    fn matches_day(&self, at: OffsetDateTime) -> bool {
+        let weekday = at.weekday().number_days_from_sunday() as u32;
+        let day_of_month = self.day_of_month.matches(at.day() as u32);
+        let day_of_week = self.day_of_week.matches(weekday) || (weekday == 0 && self.day_of_week.matches(7));
+
+        // Like cron, when both day fields are restricted either one matching is enough
+        match (self.day_of_month.is_wildcard(1, 31), self.day_of_week.is_wildcard(0, 7)) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+
+    /// Get the first time strictly after `after` that the schedule fires
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let mut candidate = after.replace_second(0).ok()?.replace_nanosecond(0).ok()? + Duration::minutes(1);
+        // Four years covers every valid combination, including February 29th
+        let limit = after + Duration::days(4 * 366);
+        while candidate <= limit {
+            if !self.month.matches(candidate.month() as u32) || !self.matches_day(candidate) {
+                candidate = candidate.replace_time(time::Time::MIDNIGHT) + Duration::days(1);
+            } else if !self.hour.matches(candidate.hour() as u32) {
+                candidate = candidate.replace_minute(0).ok()? + Duration::hours(1);
+            } else if !self.minute.matches(candidate.minute() as u32) {
+                candidate += Duration::minutes(1);
+            } else {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+}
+
+/// A job that came due, covering the time since it previously ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DueJob {
+    pub name: String,
+    /// When the job previously ran
+    pub since: OffsetDateTime,
+    /// When the job was found due, recorded as its new last run
+    pub until: OffsetDateTime,
+}
+
+/// Tracks named cron jobs and when each last ran, persisted in `scheduled_runs`
+pub struct Scheduler<C: Clock> {
+    pool: SqlitePool,
+    clock: C,
+    jobs: Vec<(String, CronSchedule)>,
+}
+
+impl<C: Clock> Scheduler<C> {
+    pub fn new(pool: SqlitePool, clock: C) -> Self {
+        Scheduler { pool, clock, jobs: Vec::new() }
+    }
+
+    /// Register a job under a unique name
+    pub fn add_job(&mut self, name: &str, cron: &str) -> Result<()> {
+        let schedule = cron
+            .parse::<CronSchedule>()
+            .map_err(|e| AppError::Other(format!("Invalid schedule for {}: {}", name, e)))?;
+        self.jobs.push((name.to_string(), schedule));
+        Ok(())
+    }
+
+    /// Get the jobs that are due. They stay due until passed to `mark_run`, so one that couldn't run
+    /// is picked up again on the next check. A job seen for the first time is only scheduled from now on,
+    /// so it never fires on startup.
+    pub async fn due_jobs(&self) -> Result<Vec<DueJob>> {
+        let now = self.clock.now();
+        let mut due = Vec::new();
+
+        for (name, schedule) in &self.jobs {
+            let Some(last_run) = get_last_run(&self.pool, name).await? else {
+                set_last_run(&self.pool, name, now).await?;
+                continue;
+            };
+
+            match schedule.next_after(last_run) {
+                Some(next) if next <= now => {
+                    debug!("Scheduled job {} is due (last run {})", name, last_run);
+                    due.push(DueJob { name: name.clone(), since: last_run, until: now });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(due)
+    }
+
+    /// Record that a due job has done its work
+    pub async fn mark_run(&self, job: &DueJob) -> Result<()> {
+        set_last_run(&self.pool, &job.name, job.until).await
+    }
+}
+
+/// Run due jobs, checking the schedule every 30 seconds
+pub async fn run_scheduler<C: Clock>(scheduler: Scheduler<C>, context: AppContext, config: LiveConfig) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+    loop {
+        interval.tick().await;
+        let due = match scheduler.due_jobs().await {
+            Ok(due) => due,
+            Err(e) => {
+                error!("Error checking schedules: {}", e);
+                continue;
+            }
+        };
+
+        for job in due {
+            match run_job(&context, &config.get(), &job).await {
+                Ok(()) => {
+                    if let Err(e) = scheduler.mark_run(&job).await {
+                        error!("Error recording run of {}: {}", job.name, e);
+                    }
+                }
+                Err(e) => warn!("Could not run {}, trying again shortly: {}", job.name, e),
+            }
+        }
+    }
+}
+
+/// Run a due job, all of which post to Discord
+#[cfg(feature = "discord")]
+async fn run_job(context: &AppContext, config: &Config, job: &DueJob) -> Result<()> {
+    let Some(http) = context.discord_http() else {
+        return Err(AppError::Other("Discord is not connected".to_string()));
+    };
+
+    if job.name == STREAK_REMINDERS_JOB {
+        send_streak_reminders(&http, &context.db_pool, config, job.until).await?;
+        info!("Sent streak reminders");
+    } else if let Some(name) = job.name.strip_prefix(digest::JOB_PREFIX) {
+        post_digest(&http, &context.db_pool, config, name, job.since, job.until).await?;
+        info!("Posted {}", name);
+    }
+    Ok(())
+}
+
+/// Run a due job, all of which post to Discord
+#[cfg(not(feature = "discord"))]
+async fn run_job(_context: &AppContext, _config: &Config, _job: &DueJob) -> Result<()> {
+    Err(AppError::Other("this build has no Discord support".to_string()))
+}
+
+/// Get when a scheduled job last ran
+pub async fn get_last_run(pool: &SqlitePool, name: &str) -> Result<Option<OffsetDateTime>> {
+    let last_run: Option<String> = sqlx::query_scalar("SELECT last_run_at FROM scheduled_runs WHERE name = ?1")
+        .bind(name)
+        .fetch_optional(pool)
+        .await?;
+
+    last_run
+        .map(|last_run| {
+            PrimitiveDateTime::parse(&last_run, TIMESTAMP_FORMAT)
+                .map(PrimitiveDateTime::assume_utc)
+                .map_err(|e| AppError::Other(format!("Invalid last run time for {}: {}", name, e)))
+        })
+        .transpose()
+}
+
+/// Record when a scheduled job last ran
+async fn set_last_run(pool: &SqlitePool, name: &str, at: OffsetDateTime) -> Result<()> {
+    let at = at.format(TIMESTAMP_FORMAT).map_err(|e| AppError::Other(e.to_string()))?;
+    sqlx::query(
+        "INSERT INTO scheduled_runs (name, last_run_at) VALUES (?1, ?2)
+         ON CONFLICT(name) DO UPDATE SET last_run_at = excluded.last_run_at"
+    )
+    .bind(name)
+    .bind(at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::initialize_database;
+    use std::sync::{Arc, Mutex};
+    use time::macros::datetime;
+
+    #[derive(Clone)]
+    struct FixedClock(Arc<Mutex<OffsetDateTime>>);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> OffsetDateTime {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        let daily: CronSchedule = "0 18 * * *".parse().unwrap();
+        assert_eq!(daily.next_after(datetime!(2025-09-04 17:59:30 UTC)), Some(datetime!(2025-09-04 18:00:00 UTC)));
+        assert_eq!(daily.next_after(datetime!(2025-09-04 18:00:00 UTC)), Some(datetime!(2025-09-05 18:00:00 UTC)));
+
+        // Fridays only (2025-09-04 is a Thursday)
+        let weekly: CronSchedule = "30 9 * * 5".parse().unwrap();
+        assert_eq!(weekly.next_after(datetime!(2025-09-04 10:00:00 UTC)), Some(datetime!(2025-09-05 09:30:00 UTC)));
+
+        let quarter_hours: CronSchedule = "*/15 * * * *".parse().unwrap();
+        assert_eq!(quarter_hours.next_after(datetime!(2025-09-04 10:16:00 UTC)), Some(datetime!(2025-09-04 10:30:00 UTC)));
+
+        assert!("0 18 * *".parse::<CronSchedule>().is_err());
+        assert!("60 * * * *".parse::<CronSchedule>().is_err());
+        assert!("* * 31 2 *".parse::<CronSchedule>().unwrap().next_after(datetime!(2025-01-01 00:00:00 UTC)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_persists_last_run() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let time = Arc::new(Mutex::new(datetime!(2025-09-04 12:00:00 UTC)));
+
+        let mut scheduler = Scheduler::new(pool.clone(), FixedClock(time.clone()));
+        scheduler.add_job("daily", "0 18 * * *").unwrap();
+
+        // Never fires on the first sighting, only once the schedule comes around
+        assert!(scheduler.due_jobs().await.unwrap().is_empty());
+        *time.lock().unwrap() = datetime!(2025-09-04 17:59:00 UTC);
+        assert!(scheduler.due_jobs().await.unwrap().is_empty());
+        *time.lock().unwrap() = datetime!(2025-09-04 18:00:20 UTC);
+        let due = scheduler.due_jobs().await.unwrap();
+        assert_eq!(
+            due,
+            vec![DueJob {
+                name: "daily".to_string(),
+                since: datetime!(2025-09-04 12:00:00 UTC),
+                until: datetime!(2025-09-04 18:00:20 UTC),
+            }]
+        );
+
+        // Until it's marked as run, such as while Discord is still connecting, it stays due
+        assert_eq!(scheduler.due_jobs().await.unwrap().len(), 1);
+        scheduler.mark_run(&due[0]).await.unwrap();
+        assert!(scheduler.due_jobs().await.unwrap().is_empty());
+
+        // A restarted scheduler picks up the persisted run and doesn't post again
+        let mut restarted = Scheduler::new(pool.clone(), FixedClock(time.clone()));
+        restarted.add_job("daily", "0 18 * * *").unwrap();
+        *time.lock().unwrap() = datetime!(2025-09-04 18:05:00 UTC);
+        assert!(restarted.due_jobs().await.unwrap().is_empty());
+        *time.lock().unwrap() = datetime!(2025-09-05 18:00:00 UTC);
+        assert_eq!(restarted.due_jobs().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,411 @@
+use crate::config::SeasonsConfig;
+use crate::error::{AppError, Result};
+use crate::models::{Category, Split};
+use crate::period::{Period, parse_date};
+use crate::validation::DurationValidator;
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use time::{Date, Month, OffsetDateTime};
+use tracing::info;
+
+/// A competitive season covering an inclusive range of days
+#[derive(Debug, Clone, Serialize)]
+pub struct Season {
+    pub id: i64,
+    pub name: String,
+    pub starts_on: String,
+    pub ends_on: String,
+    pub archived_at: Option<String>,
+}
+
+impl Season {
+    /// Leaderboard period covering this season
+    pub fn period(&self) -> Result<Period> {
+        let from = parse_date(&self.starts_on).map_err(AppError::Other)?;
+        let to = parse_date(&self.ends_on).map_err(AppError::Other)?;
+        Ok(Period::Custom { from, to })
+    }
+}
+
+/// A user's final placement in one category of an archived season
+#[derive(Debug, Clone, Serialize)]
+pub struct Standing {
+    pub season_id: i64,
+    pub is_down: bool,
+    pub is_elevator: bool,
+    pub is_encumbered: Option<bool>,
+    pub rank: i64,
+    pub user: String,
+    pub duration_ms: i32,
+    pub split_id: i32,
+}
+
+impl Standing {
+    /// Category this placement was earned in
+    pub fn category(&self) -> Category {
+        Category {
+            is_down: self.is_down,
+            is_elevator: self.is_elevator,
+            is_encumbered: self.is_encumbered,
+        }
+    }
+}
+
+/// Create a season, rejecting ranges that overlap an existing season
+pub async fn create_season(pool: &SqlitePool, name: &str, starts_on: Date, ends_on: Date) -> Result<Season> {
+    if starts_on > ends_on {
+        return Err(AppError::Other("Season must start before it ends".to_string()));
+    }
+
+    let starts_on = starts_on.to_string();
+    let ends_on = ends_on.to_string();
+
+    let overlapping: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM seasons WHERE starts_on <= ?2 AND ends_on >= ?1"
+    )
+    .bind(&starts_on)
+    .bind(&ends_on)
+    .fetch_one(pool)
+    .await?;
+    if overlapping > 0 {
+        return Err(AppError::Other(format!("Season {} overlaps an existing season", name)));
+    }
+
+    let id = sqlx::query("INSERT INTO seasons (name, starts_on, ends_on) VALUES (?1, ?2, ?3)")
+        .bind(name)
+        .bind(&starts_on)
+        .bind(&ends_on)
+        .execute(pool)
+        .await?
+        .last_insert_rowid();
+
+    info!("Created season {} ({} to {})", name, starts_on, ends_on);
+    Ok(Season { id, name: name.to_string(), starts_on, ends_on, archived_at: None })
+}
+
+fn season_from_row(row: &sqlx::sqlite::SqliteRow) -> Season {
+    Season {
+        id: row.get(0),
+        name: row.get(1),
+        starts_on: row.get(2),
+        ends_on: row.get(3),
+        archived_at: row.get(4),
+    }
+}
+
+/// Get all seasons, newest first
+pub async fn get_seasons(pool: &SqlitePool) -> Result<Vec<Season>> {
+    let rows = sqlx::query("SELECT id, name, starts_on, ends_on, archived_at FROM seasons ORDER BY starts_on DESC")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(season_from_row).collect())
+}
+
+/// Get a season by its name
+pub async fn get_season_by_name(pool: &SqlitePool, name: &str) -> Result<Option<Season>> {
+    let row = sqlx::query("SELECT id, name, starts_on, ends_on, archived_at FROM seasons WHERE name = ?1")
+        .bind(name)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.as_ref().map(season_from_row))
+}
+
+/// Get the season running on the given day, if any
+pub async fn get_active_season(pool: &SqlitePool, today: Date) -> Result<Option<Season>> {
+    let row = sqlx::query(
+        "SELECT id, name, starts_on, ends_on, archived_at FROM seasons
+         WHERE starts_on <= ?1 AND ends_on >= ?1 AND archived_at IS NULL
+         ORDER BY starts_on DESC LIMIT 1"
+    )
+    .bind(today.to_string())
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(season_from_row))
+}
+
+/// Insert configured seasons and, when enabled, the current calendar quarter
+pub async fn sync_seasons(pool: &SqlitePool, config: &SeasonsConfig, today: Date) -> Result<()> {
+    for definition in &config.definitions {
+        if get_season_by_name(pool, &definition.name).await?.is_some() {
+            continue;
+        }
+        let starts_on = parse_date(&definition.starts_on).map_err(AppError::Other)?;
+        let ends_on = parse_date(&definition.ends_on).map_err(AppError::Other)?;
+        create_season(pool, &definition.name, starts_on, ends_on).await?;
+    }
+
+    if config.quarterly && get_active_season(pool, today).await?.is_none() {
+        let (name, starts_on, ends_on) = quarter_of(today);
+        if get_season_by_name(pool, &name).await?.is_none() {
+            create_season(pool, &name, starts_on, ends_on).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Name and inclusive bounds of the calendar quarter containing `date`
+fn quarter_of(date: Date) -> (String, Date, Date) {
+    let quarter = (date.month() as u8 - 1) / 3;
+    let first_month = Month::try_from(quarter * 3 + 1).expect("quarter start month is valid");
+    let last_month = Month::try_from(quarter * 3 + 3).expect("quarter end month is valid");
+    let starts_on = Date::from_calendar_date(date.year(), first_month, 1).expect("first of month is valid");
+    let ends_on = Date::from_calendar_date(date.year(), last_month, last_month.length(date.year()))
+        .expect("last of month is valid");
+    (format!("{} Q{}", date.year(), quarter + 1), starts_on, ends_on)
+}
+
+/// Archive every season that ended before `today`, returning them with their final standings
+pub async fn archive_finished_seasons(pool: &SqlitePool, today: Date) -> Result<Vec<(Season, Vec<Standing>)>> {
+    let rows = sqlx::query(
+        "SELECT id, name, starts_on, ends_on, archived_at FROM seasons
+         WHERE ends_on < ?1 AND archived_at IS NULL ORDER BY starts_on ASC"
+    )
+    .bind(today.to_string())
+    .fetch_all(pool)
+    .await?;
+
+    let mut archived = Vec::new();
+    for season in rows.iter().map(season_from_row) {
+        let standings = archive_season(pool, &season).await?;
+        archived.push((season, standings));
+    }
+    Ok(archived)
+}
+
+/// Mark a season as not archived, so the next roll over archives it again
+pub async fn unarchive_season(pool: &SqlitePool, season_id: i64) -> Result<()> {
+    sqlx::query("UPDATE seasons SET archived_at = NULL WHERE id = ?1").bind(season_id).execute(pool).await?;
+    Ok(())
+}
+
+/// Rebuild the standings of every archived season from the splits as they are now,
+/// such as after splits were hidden or deleted. Returns the number of seasons rebuilt.
+pub async fn recompute_season_standings(pool: &SqlitePool) -> Result<usize> {
+    let rows = sqlx::query(
+        "SELECT id, name, starts_on, ends_on, archived_at FROM seasons WHERE archived_at IS NOT NULL ORDER BY starts_on ASC"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for season in rows.iter().map(season_from_row) {
+        archive_season(pool, &season).await?;
+    }
+    Ok(rows.len())
+}
+
+/// Snapshot each user's best run per category into `season_standings` and mark the season archived
+async fn archive_season(pool: &SqlitePool, season: &Season) -> Result<Vec<Standing>> {
+    let (start, end) = season.period()?.sql_bounds(OffsetDateTime::now_utc());
+    let mut tx = pool.begin().await?;
+    let mut standings = Vec::new();
+
+    // Replace any earlier snapshot when recomputing
+    sqlx::query("DELETE FROM season_standings WHERE season_id = ?1")
+        .bind(season.id)
+        .execute(&mut *tx)
+        .await?;
+
+    for category in Category::ALL {
+        let rows = sqlx::query(
+            "SELECT id, user, MIN(duration_ms) FROM splits
+             WHERE is_down = ?1 AND is_elevator = ?2 AND (?3 IS NULL OR is_encumbered = ?3)
+             AND created_at >= ?4 AND created_at < ?5
+             GROUP BY user ORDER BY MIN(duration_ms) ASC, id ASC"
+        )
+        .bind(category.is_down)
+        .bind(category.is_elevator)
+        .bind(category.is_encumbered)
+        .bind(&start)
+        .bind(&end)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for (rank, row) in rows.iter().enumerate() {
+            let standing = Standing {
+                season_id: season.id,
+                is_down: category.is_down,
+                is_elevator: category.is_elevator,
+                is_encumbered: category.is_encumbered,
+                rank: rank as i64 + 1,
+                user: row.get(1),
+                duration_ms: row.get(2),
+                split_id: row.get(0),
+            };
+            sqlx::query(
+                "INSERT INTO season_standings (season_id, is_down, is_elevator, is_encumbered, rank, user, duration_ms, split_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+            )
+            .bind(standing.season_id)
+            .bind(standing.is_down)
+            .bind(standing.is_elevator)
+            .bind(standing.is_encumbered)
+            .bind(standing.rank)
+            .bind(&standing.user)
+            .bind(standing.duration_ms)
+            .bind(standing.split_id)
+            .execute(&mut *tx)
+            .await?;
+            standings.push(standing);
+        }
+    }
+
+    sqlx::query("UPDATE seasons SET archived_at = COALESCE(archived_at, CURRENT_TIMESTAMP) WHERE id = ?1")
+        .bind(season.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    info!("Archived season {} with {} standings", season.name, standings.len());
+    Ok(standings)
+}
+
+/// Get the archived standings of a season, ordered by category then rank
+pub async fn get_season_standings(pool: &SqlitePool, season_id: i64) -> Result<Vec<Standing>> {
+    let rows = sqlx::query(
+        "SELECT season_id, is_down, is_elevator, is_encumbered, rank, user, duration_ms, split_id
+         FROM season_standings WHERE season_id = ?1
+         ORDER BY is_elevator DESC, is_down DESC, is_encumbered DESC, rank ASC"
+    )
+    .bind(season_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Standing {
+            season_id: row.get(0),
+            is_down: row.get(1),
+            is_elevator: row.get(2),
+            is_encumbered: row.get(3),
+            rank: row.get(4),
+            user: row.get(5),
+            duration_ms: row.get(6),
+            split_id: row.get(7),
+        })
+        .collect())
+}
+
+/// Check if a split beat every earlier run of its category within a season
+pub async fn is_season_record(pool: &SqlitePool, season: &Season, split: &Split) -> Result<bool> {
+    let (start, end) = season.period()?.sql_bounds(OffsetDateTime::now_utc());
+    let category = Category::of(split);
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM splits
+         WHERE is_down = ?1 AND is_elevator = ?2 AND (?3 IS NULL OR is_encumbered = ?3)
+         AND created_at >= ?4 AND created_at < ?5 AND duration_ms < ?6 AND id < ?7"
+    )
+    .bind(category.is_down)
+    .bind(category.is_elevator)
+    .bind(category.is_encumbered)
+    .bind(&start)
+    .bind(&end)
+    .bind(split.duration_ms)
+    .bind(split.id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count == 0)
+}
+
+/// Format the end-of-season summary posted to Discord
+pub fn format_season_summary(season: &Season, standings: &[Standing]) -> String {
+    let mut formatted = format!(
+        "**Season {} has ended!** ({} to {})\n",
+        season.name, season.starts_on, season.ends_on
+    );
+
+    let champions: Vec<&Standing> = standings.iter().filter(|s| s.rank == 1).collect();
+    if champions.is_empty() {
+        formatted.push_str("No runs were recorded this season.");
+        return formatted;
+    }
+
+    for standing in champions {
+        let runners_up: Vec<&str> = standings
+            .iter()
+            .filter(|s| s.rank > 1 && s.rank <= 3 && s.category() == standing.category())
+            .map(|s| s.user.as_str())
+            .collect();
+
+        formatted.push_str(&format!(
+            "**{}**: 🥇 {} - {}",
+            standing.category().name(),
+            standing.user,
+            DurationValidator::format_duration(standing.duration_ms)
+        ));
+        if !runners_up.is_empty() {
+            formatted.push_str(&format!(" (followed by {})", runners_up.join(", ")));
+        }
+        formatted.push('\n');
+    }
+
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::initialize_database;
+    use time::macros::date;
+
+    #[tokio::test]
+    async fn test_archive_finished_seasons() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+
+        let config = SeasonsConfig { quarterly: true, definitions: vec![] };
+        sync_seasons(&pool, &config, date!(2025 - 08 - 15)).await.unwrap();
+        let season = get_active_season(&pool, date!(2025 - 08 - 15)).await.unwrap().unwrap();
+        assert_eq!(season.name, "2025 Q3");
+        assert_eq!((season.starts_on.as_str(), season.ends_on.as_str()), ("2025-07-01", "2025-09-30"));
+
+        for (user, duration_ms, created_at) in [
+            ("alice", 5000, "2025-08-01 12:00:00"),
+            ("bob", 4000, "2025-08-02 12:00:00"),
+            ("alice", 3000, "2025-08-03 12:00:00"),
+            ("bob", 1000, "2025-10-01 00:00:00"), // after the season ended
+        ] {
+            sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms, created_at) VALUES (?1, 0, 0, 0, ?2, ?3)")
+                .bind(user)
+                .bind(duration_ms)
+                .bind(created_at)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        // Bob's run was the season's best when he logged it, even though alice has since beaten it
+        let split = |id, user: &str, duration_ms, created_at: &str| Split {
+            id,
+            user: user.to_string(),
+            is_down: false,
+            is_elevator: false,
+            is_encumbered: Some(false),
+            duration_ms,
+            created_at: created_at.to_string(),
+        };
+        assert!(is_season_record(&pool, &season, &split(2, "bob", 4000, "2025-08-02 12:00:00")).await.unwrap());
+        assert!(!is_season_record(&pool, &season, &split(3, "alice", 6000, "2025-08-03 12:00:00")).await.unwrap());
+
+        assert!(archive_finished_seasons(&pool, date!(2025 - 09 - 30)).await.unwrap().is_empty());
+
+        let archived = archive_finished_seasons(&pool, date!(2025 - 10 - 01)).await.unwrap();
+        assert_eq!(archived.len(), 1);
+        let standings = &archived[0].1;
+        assert_eq!(standings.len(), 2);
+        assert_eq!((standings[0].user.as_str(), standings[0].duration_ms, standings[0].rank), ("alice", 3000, 1));
+        assert_eq!((standings[1].user.as_str(), standings[1].duration_ms, standings[1].rank), ("bob", 4000, 2));
+
+        // Archived seasons are no longer active and are not archived twice
+        assert!(get_active_season(&pool, date!(2025 - 09 - 01)).await.unwrap().is_none());
+        assert!(archive_finished_seasons(&pool, date!(2025 - 10 - 02)).await.unwrap().is_empty());
+        assert_eq!(get_season_standings(&pool, season.id).await.unwrap().len(), 2);
+
+        // One whose summary couldn't be posted comes around again with the same standings
+        unarchive_season(&pool, season.id).await.unwrap();
+        let archived = archive_finished_seasons(&pool, date!(2025 - 10 - 02)).await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].1.len(), 2);
+    }
+}
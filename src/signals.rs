@@ -26,3 +26,27 @@ pub async fn shutdown_signal() {
 
     info!("Signal received, starting graceful shutdown");
 }
+
+/// Stream of SIGHUPs, the conventional request to reload configuration.
+/// Never fires on platforms without it.
+pub struct ReloadSignal {
+    #[cfg(unix)]
+    hangup: signal::unix::Signal,
+}
+
+impl ReloadSignal {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(ReloadSignal {
+            #[cfg(unix)]
+            hangup: signal::unix::signal(signal::unix::SignalKind::hangup())?,
+        })
+    }
+
+    /// Wait for the next signal
+    pub async fn recv(&mut self) {
+        #[cfg(unix)]
+        self.hangup.recv().await;
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
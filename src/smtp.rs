@@ -0,0 +1,153 @@
+use crate::config::{SmtpConfig, SmtpTls};
+use crate::error::{AppError, Result};
+use lettre::message::header::ContentType;
+use lettre::message::{Mailbox, Message};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{Address, AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use std::time::Duration;
+use tracing::debug;
+
+/// Longest each step of the conversation with the mail server may take
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A plain text email
+#[derive(Debug, Clone)]
+pub struct Email<'a> {
+    pub to: &'a str,
+    pub subject: &'a str,
+    pub body: &'a str,
+}
+
+/// Whether an address is safe to put in an SMTP command and a header
+pub fn is_valid_address(address: &str) -> bool {
+    let Some((local, domain)) = address.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.is_empty()
+        && address.len() <= 254
+        && !address.chars().any(|c| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | ',' | ';'))
+}
+
+/// Send an email through the configured server. A login is only ever sent over TLS.
+pub async fn send_email(config: &SmtpConfig, email: &Email<'_>) -> Result<()> {
+    let invalid_address = |_| AppError::Other("Invalid email address".to_string());
+    if !is_valid_address(email.to) || !is_valid_address(&config.from) {
+        return Err(AppError::Other("Invalid email address".to_string()));
+    }
+    if email.subject.contains(['\r', '\n']) {
+        return Err(AppError::Other("Email subject must be a single line".to_string()));
+    }
+
+    // Mail wants CRLF line endings, anything else gets encoded as part of the line
+    let body = email.body.lines().collect::<Vec<_>>().join("\r\n");
+    let message = Message::builder()
+        .from(Mailbox::new(None, config.from.parse::<Address>().map_err(invalid_address)?))
+        .to(Mailbox::new(None, email.to.parse::<Address>().map_err(invalid_address)?))
+        .subject(email.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body)
+        .map_err(|e| AppError::Other(e.to_string()))?;
+
+    transport(config)?.send(message).await.map_err(|e| AppError::Other(format!("Error sending email: {}", e)))?;
+    debug!("Sent email to {}", email.to);
+    Ok(())
+}
+
+fn transport(config: &SmtpConfig) -> Result<AsyncSmtpTransport<Tokio1Executor>> {
+    let tls_error = |e: lettre::transport::smtp::Error| AppError::Config(format!("Invalid mail server TLS setup: {}", e));
+    let tls = match config.tls {
+        SmtpTls::None if !config.username.is_empty() => {
+            return Err(AppError::Config("Refusing to send a mail server login without TLS".to_string()));
+        }
+        SmtpTls::None => Tls::None,
+        SmtpTls::Starttls => Tls::Required(TlsParameters::new(config.host.clone()).map_err(tls_error)?),
+        SmtpTls::Tls => Tls::Wrapper(TlsParameters::new(config.host.clone()).map_err(tls_error)?),
+    };
+
+    let mut builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(config.host.as_str())
+        .port(config.port)
+        .tls(tls)
+        .timeout(Some(TIMEOUT));
+    if !config.username.is_empty() {
+        builder = builder.credentials(Credentials::new(config.username.clone(), config.password.clone()));
+    }
+    Ok(builder.build())
+}
+
+/// Stand-in mail server that accepts everything and records each message's data, dot-stuffing undone
+#[cfg(test)]
+pub(crate) async fn fake_smtp_server() -> (u16, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let log = received.clone();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let log = log.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                writer.write_all(b"220 fake ESMTP\r\n").await.unwrap();
+                let mut data: Option<String> = None;
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some(message) = data.as_mut() {
+                        if line == "." {
+                            log.lock().unwrap().push(data.take().unwrap());
+                            writer.write_all(b"250 queued\r\n").await.unwrap();
+                        } else {
+                            message.push_str(line.strip_prefix('.').unwrap_or(&line));
+                            message.push('\n');
+                        }
+                        continue;
+                    }
+                    let reply: &[u8] = match line.split(' ').next().unwrap() {
+                        "EHLO" => b"250-fake\r\n250 AUTH PLAIN\r\n",
+                        "AUTH" => b"235 ok\r\n",
+                        "DATA" => {
+                            data = Some(String::new());
+                            b"354 go ahead\r\n"
+                        }
+                        "QUIT" => b"221 bye\r\n",
+                        _ => b"250 ok\r\n",
+                    };
+                    writer.write_all(reply).await.unwrap();
+                }
+            });
+        }
+    });
+    (port, received)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_email() {
+        let (port, received) = fake_smtp_server().await;
+        let mut config = SmtpConfig { host: "127.0.0.1".to_string(), port, tls: SmtpTls::None, ..Default::default() };
+        let email = Email { to: "alice@example.com", subject: "Hello", body: "First line\n.hidden dot\nLast line" };
+        send_email(&config, &email).await.unwrap();
+
+        let message = received.lock().unwrap()[0].clone();
+        assert!(message.contains("To: alice@example.com\n"));
+        assert!(message.contains("From: splits@localhost\n"));
+        assert!(message.contains("Subject: Hello\n"));
+        assert!(message.ends_with("\nFirst line\n.hidden dot\nLast line\n"));
+
+        // A login never goes over a plain connection
+        config.username = "splits".to_string();
+        config.password = "secret".to_string();
+        assert!(matches!(send_email(&config, &email).await, Err(AppError::Config(_))));
+        assert_eq!(received.lock().unwrap().len(), 1);
+
+        let injected = Email { subject: "Hi\r\nBcc: everyone@example.com", ..email.clone() };
+        assert!(send_email(&config, &injected).await.is_err());
+        assert!(!is_valid_address("alice@example.com>\r\nRCPT TO:<bob@example.com"));
+        assert!(!is_valid_address("nobody"));
+    }
+}
@@ -0,0 +1,58 @@
+use crate::config::Config;
+use crate::database::get_personal_bests;
+use crate::error::Result;
+use crate::models::{Category, Split};
+use crate::streaks::{Streaks, get_streaks};
+use crate::validation::DurationValidator;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+
+/// Overview of a user's activity
+#[derive(Debug, Serialize)]
+pub struct UserStats {
+    pub user: String,
+    pub runs: i64,
+    pub streaks: Streaks,
+    pub personal_bests: Vec<Split>,
+}
+
+/// Gather a user's run count, streaks and personal bests
+pub async fn get_user_stats(pool: &SqlitePool, config: &Config, user: &str) -> Result<UserStats> {
+    let runs: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM splits WHERE user = ?1")
+        .bind(user)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(UserStats {
+        user: user.to_string(),
+        runs,
+        streaks: get_streaks(pool, user, config.streaks.offset()?, OffsetDateTime::now_utc()).await?,
+        personal_bests: get_personal_bests(pool, user).await?,
+    })
+}
+
+/// Format a user's stats for display
+pub fn format_user_stats(stats: &UserStats) -> String {
+    if stats.runs == 0 {
+        return format!("{} hasn't logged any runs yet.", stats.user);
+    }
+
+    let mut formatted = format!("**{}'s Stats:**\n", stats.user);
+    formatted.push_str(&format!("Runs: {}\n", stats.runs));
+    formatted.push_str(&format!(
+        "Streak: {} day(s) (longest {})\n",
+        stats.streaks.current, stats.streaks.longest
+    ));
+
+    for split in &stats.personal_bests {
+        formatted.push_str(&format!(
+            "**{}**: {} ({})\n",
+            Category::of(split).name(),
+            DurationValidator::format_duration(split.duration_ms),
+            split.created_at
+        ));
+    }
+
+    formatted
+}
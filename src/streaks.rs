@@ -0,0 +1,206 @@
+use crate::accounts::{hash_token, new_token};
+use crate::config::StreaksConfig;
+use crate::error::{AppError, Result};
+use crate::period::{TIMESTAMP_FORMAT, parse_date};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use time::{Date, Duration, OffsetDateTime, UtcOffset};
+
+/// Consecutive days a user has logged at least one split
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Streaks {
+    /// Streak ending today or yesterday, 0 if it's already broken
+    pub current: i64,
+    pub longest: i64,
+    /// Last local day the user logged a split (YYYY-MM-DD)
+    pub last_active: Option<String>,
+}
+
+impl StreaksConfig {
+    /// Timezone used to decide which day a split belongs to
+    pub fn offset(&self) -> Result<UtcOffset> {
+        UtcOffset::from_whole_seconds(self.utc_offset_minutes * 60)
+            .map_err(|e| AppError::Other(format!("Invalid streak timezone offset: {}", e)))
+    }
+
+    /// UTC cron expression for the configured local reminder time
+    pub fn reminder_cron(&self) -> String {
+        let local_minutes = self.reminder_hour as i32 * 60;
+        let utc_minutes = (local_minutes - self.utc_offset_minutes).rem_euclid(24 * 60);
+        format!("{} {} * * *", utc_minutes % 60, utc_minutes / 60)
+    }
+}
+
+/// SQLite date modifier shifting UTC timestamps into the configured timezone
+fn date_modifier(offset: UtcOffset) -> String {
+    format!("{:+} minutes", offset.whole_minutes())
+}
+
+/// Compute a user's streaks, counting days in the given timezone
+pub async fn get_streaks(pool: &SqlitePool, user: &str, offset: UtcOffset, now: OffsetDateTime) -> Result<Streaks> {
+    let dates: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT date(created_at, ?2) FROM splits WHERE user = ?1 ORDER BY 1 ASC"
+    )
+    .bind(user)
+    .bind(date_modifier(offset))
+    .fetch_all(pool)
+    .await?;
+
+    let dates: Vec<Date> = dates.iter().filter_map(|d| parse_date(d).ok()).collect();
+    Ok(compute_streaks(&dates, now.to_offset(offset).date()))
+}
+
+/// Compute streaks from sorted, distinct local dates
+pub fn compute_streaks(dates: &[Date], today: Date) -> Streaks {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous: Option<Date> = None;
+    for &date in dates {
+        current = match previous {
+            Some(previous) if previous + Duration::days(1) == date => current + 1,
+            _ => 1,
+        };
+        longest = longest.max(current);
+        previous = Some(date);
+    }
+
+    // The running streak survives until a whole day passes without a split
+    let current = match previous {
+        Some(last) if last >= today - Duration::days(1) => current,
+        _ => 0,
+    };
+
+    Streaks {
+        current,
+        longest,
+        last_active: previous.map(|d| d.to_string()),
+    }
+}
+
+/// How long a code for linking a Discord account can be used
+pub const LINK_CODE_MINUTES: i64 = 15;
+
+/// Create a single-use code for `/link` that links a Discord account to `user`.
+/// Only logged in runners get one, which proves the name is theirs.
+pub async fn create_link_code(pool: &SqlitePool, user: &str) -> Result<String> {
+    let now = OffsetDateTime::now_utc();
+    let format = |at: OffsetDateTime| at.format(TIMESTAMP_FORMAT).map_err(|e| AppError::Other(e.to_string()));
+    sqlx::query("DELETE FROM discord_link_codes WHERE expires_at <= ?1").bind(format(now)?).execute(pool).await?;
+
+    let code = new_token();
+    sqlx::query("INSERT INTO discord_link_codes (code_hash, user, expires_at) VALUES (?1, ?2, ?3)")
+        .bind(hash_token(&code))
+        .bind(user)
+        .bind(format(now + Duration::minutes(LINK_CODE_MINUTES))?)
+        .execute(pool)
+        .await?;
+    Ok(code)
+}
+
+/// Use up a link code, returning the username it was created for if it's still valid
+pub async fn redeem_link_code(pool: &SqlitePool, code: &str) -> Result<Option<String>> {
+    let now = OffsetDateTime::now_utc().format(TIMESTAMP_FORMAT).map_err(|e| AppError::Other(e.to_string()))?;
+    let user: Option<String> = sqlx::query_scalar("DELETE FROM discord_link_codes WHERE code_hash = ?1 AND expires_at > ?2 RETURNING user")
+        .bind(hash_token(code))
+        .bind(now)
+        .fetch_optional(pool)
+        .await?;
+    Ok(user)
+}
+
+/// Link a Discord account to a username, replacing any name it was linked to before.
+/// Fails with `AlreadyTaken` if a different Discord account has the name, which has to `/unlink` first.
+pub async fn link_discord_user(pool: &SqlitePool, user: &str, discord_user_id: u64) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    let owner: Option<i64> = sqlx::query_scalar("SELECT discord_user_id FROM discord_links WHERE user = ?1")
+        .bind(user)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if owner.is_some_and(|owner| owner != discord_user_id as i64) {
+        return Err(AppError::AlreadyTaken(user.to_string()));
+    }
+
+    sqlx::query("DELETE FROM discord_links WHERE discord_user_id = ?1")
+        .bind(discord_user_id as i64)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("INSERT INTO discord_links (user, discord_user_id) VALUES (?1, ?2)")
+        .bind(user)
+        .bind(discord_user_id as i64)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Remove a Discord account's link, returning the username it was linked to
+pub async fn unlink_discord_user(pool: &SqlitePool, discord_user_id: u64) -> Result<Option<String>> {
+    let user: Option<String> = sqlx::query_scalar("DELETE FROM discord_links WHERE discord_user_id = ?1 RETURNING user")
+        .bind(discord_user_id as i64)
+        .fetch_optional(pool)
+        .await?;
+    Ok(user)
+}
+
+/// Opt a linked Discord account in or out of streak reminders, returning the linked username
+pub async fn set_streak_reminders(pool: &SqlitePool, discord_user_id: u64, enabled: bool) -> Result<Option<String>> {
+    let user: Option<String> = sqlx::query_scalar(
+        "UPDATE discord_links SET streak_reminders = ?2 WHERE discord_user_id = ?1 RETURNING user"
+    )
+    .bind(discord_user_id as i64)
+    .bind(enabled)
+    .fetch_optional(pool)
+    .await?;
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::initialize_database;
+    use time::macros::date;
+
+    #[test]
+    fn test_compute_streaks() {
+        let dates = [date!(2025 - 09 - 01), date!(2025 - 09 - 02), date!(2025 - 09 - 04), date!(2025 - 09 - 05)];
+
+        let streaks = compute_streaks(&dates, date!(2025 - 09 - 06));
+        assert_eq!((streaks.current, streaks.longest), (2, 2));
+        assert_eq!(streaks.last_active.as_deref(), Some("2025-09-05"));
+
+        assert_eq!(compute_streaks(&dates, date!(2025 - 09 - 05)).current, 2);
+        assert_eq!(compute_streaks(&dates, date!(2025 - 09 - 07)).current, 0);
+        assert_eq!(compute_streaks(&[], date!(2025 - 09 - 07)), Streaks::default());
+    }
+
+    #[tokio::test]
+    async fn test_link_discord_user() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+
+        let code = create_link_code(&pool, "alice").await.unwrap();
+        assert_eq!(redeem_link_code(&pool, &code).await.unwrap().as_deref(), Some("alice"));
+        assert!(redeem_link_code(&pool, &code).await.unwrap().is_none());
+        link_discord_user(&pool, "alice", 1).await.unwrap();
+
+        // Someone else can't take the name over, even with a code for it
+        assert!(matches!(link_discord_user(&pool, "alice", 2).await, Err(AppError::AlreadyTaken(_))));
+        link_discord_user(&pool, "alice", 1).await.unwrap();
+
+        assert_eq!(unlink_discord_user(&pool, 1).await.unwrap().as_deref(), Some("alice"));
+        link_discord_user(&pool, "alice", 2).await.unwrap();
+
+        let code = create_link_code(&pool, "bob").await.unwrap();
+        sqlx::query("UPDATE discord_link_codes SET expires_at = '2000-01-01 00:00:00'").execute(&pool).await.unwrap();
+        assert!(redeem_link_code(&pool, &code).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_reminder_cron() {
+        let config = StreaksConfig { utc_offset_minutes: -300, reminder_hour: 20, reminders_enabled: true };
+        assert_eq!(config.reminder_cron(), "0 1 * * *");
+
+        let config = StreaksConfig { utc_offset_minutes: 330, reminder_hour: 20, reminders_enabled: true };
+        assert_eq!(config.reminder_cron(), "30 14 * * *");
+    }
+}
@@ -0,0 +1,393 @@
+use crate::error::{AppError, Result};
+use crate::models::{Category, Split};
+use crate::period::TIMESTAMP_FORMAT;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::http::HeaderMap;
+use axum::response::Response;
+use axum::response::sse::Event;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::{Notify, broadcast};
+use tracing::{debug, error, info};
+
+/// How often the events table is checked for events recorded by other processes, such as the CLI
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most events read from the database at once, when resuming or catching up
+const PAGE_SIZE: i64 = 200;
+
+/// How long events are kept for clients to resume from. Older ones are pruned.
+const RETENTION: time::Duration = time::Duration::hours(24);
+
+/// How often events older than the retention window are pruned
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Events buffered for each subscriber before it falls behind and catches up from the database
+const CHANNEL_CAPACITY: usize = 256;
+
+/// What happened in a live feed event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamEventKind {
+    NewSplit,
+    WorldRecord,
+    SplitDeleted,
+    SplitRestored,
+}
+
+impl StreamEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreamEventKind::NewSplit => "new_split",
+            StreamEventKind::WorldRecord => "world_record",
+            StreamEventKind::SplitDeleted => "split_deleted",
+            StreamEventKind::SplitRestored => "split_restored",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        [StreamEventKind::NewSplit, StreamEventKind::WorldRecord, StreamEventKind::SplitDeleted, StreamEventKind::SplitRestored]
+            .into_iter()
+            .find(|candidate| candidate.as_str() == kind)
+    }
+}
+
+/// An event in the live feed. Ids only increase, so clients can resume after the last one they saw.
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamEvent {
+    pub id: i64,
+    pub kind: StreamEventKind,
+    /// The split, with its category slug and display name
+    pub data: serde_json::Value,
+    pub created_at: String,
+}
+
+impl StreamEvent {
+    /// The event as a Server-Sent Event, named after its kind
+    pub fn to_sse(&self) -> std::result::Result<Event, axum::Error> {
+        Event::default().id(self.id.to_string()).event(self.kind.as_str()).json_data(self)
+    }
+}
+
+/// Record an event for the live feed. Servers pick it up from the database, so this works from the CLI too.
+pub async fn record_event(conn: &mut SqliteConnection, kind: StreamEventKind, split: &Split) -> Result<i64> {
+    let category = Category::of(split);
+    let data = serde_json::json!({ "category": category.slug(), "category_name": category.name(), "split": split });
+    let id = sqlx::query_scalar("INSERT INTO stream_events (kind, payload) VALUES (?1, ?2) RETURNING id")
+        .bind(kind.as_str())
+        .bind(data.to_string())
+        .fetch_one(conn)
+        .await?;
+    Ok(id)
+}
+
+/// Get events recorded after `after`, oldest first
+pub async fn get_events_after(pool: &SqlitePool, after: i64, limit: i64) -> Result<Vec<StreamEvent>> {
+    let rows = sqlx::query("SELECT id, kind, payload, created_at FROM stream_events WHERE id > ?1 ORDER BY id ASC LIMIT ?2")
+        .bind(after)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+    rows.iter()
+        .map(|row| {
+            let kind: String = row.get(1);
+            let payload: String = row.get(2);
+            Ok(StreamEvent {
+                id: row.get(0),
+                kind: StreamEventKind::parse(&kind).ok_or_else(|| AppError::Other(format!("Unknown event kind {}", kind)))?,
+                data: serde_json::from_str(&payload).map_err(|e| AppError::Other(e.to_string()))?,
+                created_at: row.get(3),
+            })
+        })
+        .collect()
+}
+
+async fn get_latest_event_id(pool: &SqlitePool) -> Result<i64> {
+    let id: Option<i64> = sqlx::query_scalar("SELECT MAX(id) FROM stream_events").fetch_one(pool).await?;
+    Ok(id.unwrap_or(0))
+}
+
+/// Whether events after `last_id` have been pruned, so the feed can't be resumed from there
+async fn is_pruned(pool: &SqlitePool, last_id: i64) -> Result<bool> {
+    let oldest: Option<i64> = sqlx::query_scalar("SELECT MIN(id) FROM stream_events").fetch_one(pool).await?;
+    Ok(oldest.is_some_and(|oldest| last_id + 1 < oldest))
+}
+
+/// Delete events older than the retention window, returning how many were deleted
+pub async fn prune_events(pool: &SqlitePool, now: OffsetDateTime) -> Result<u64> {
+    let cutoff = (now - RETENTION).format(TIMESTAMP_FORMAT).map_err(|e| AppError::Other(e.to_string()))?;
+    let deleted = sqlx::query("DELETE FROM stream_events WHERE created_at < ?1")
+        .bind(cutoff)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(deleted)
+}
+
+/// Fans recorded events out to every connected client
+#[derive(Clone)]
+pub struct EventBroadcaster {
+    sender: broadcast::Sender<StreamEvent>,
+    wake: Arc<Notify>,
+}
+
+impl Default for EventBroadcaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBroadcaster {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        EventBroadcaster { sender, wake: Arc::new(Notify::new()) }
+    }
+
+    /// Record an event and send it out now rather than at the next poll
+    pub async fn publish(&self, pool: &SqlitePool, kind: StreamEventKind, split: &Split) -> Result<i64> {
+        let mut conn = pool.acquire().await?;
+        let id = record_event(&mut conn, kind, split).await?;
+        self.wake.notify_one();
+        Ok(id)
+    }
+
+    /// Follow the feed from after `last_event_id`, or from now if there isn't one or the
+    /// events after it have already been pruned
+    pub async fn subscribe(&self, pool: &SqlitePool, last_event_id: Option<i64>) -> Result<Subscription> {
+        // Subscribe before looking at the database so nothing recorded in between is missed
+        let receiver = self.sender.subscribe();
+        let last_event_id = match last_event_id {
+            Some(id) if is_pruned(pool, id).await? => {
+                debug!("Live feed event {} is past the retention window, starting from now", id);
+                None
+            }
+            id => id,
+        };
+        let last_id = match last_event_id {
+            Some(id) => id,
+            None => get_latest_event_id(pool).await?,
+        };
+        Ok(Subscription {
+            pool: pool.clone(),
+            receiver,
+            backlog: VecDeque::new(),
+            last_id,
+            catching_up: last_event_id.is_some(),
+        })
+    }
+
+    /// Send events recorded since `last_id` to subscribers, returning how many were sent
+    pub async fn relay_new_events(&self, pool: &SqlitePool, last_id: &mut i64) -> Result<usize> {
+        let events = get_events_after(pool, *last_id, PAGE_SIZE).await?;
+        for event in &events {
+            *last_id = event.id;
+            // Failing just means nobody is listening right now
+            let _ = self.sender.send(event.clone());
+        }
+        Ok(events.len())
+    }
+}
+
+/// One client's view of the feed, replaying missed events from the database before live ones
+pub struct Subscription {
+    pool: SqlitePool,
+    receiver: broadcast::Receiver<StreamEvent>,
+    backlog: VecDeque<StreamEvent>,
+    last_id: i64,
+    catching_up: bool,
+}
+
+impl Subscription {
+    /// Wait for the next event. Returns None once the feed shuts down or the database can't be read.
+    pub async fn next(&mut self) -> Option<StreamEvent> {
+        loop {
+            if let Some(event) = self.backlog.pop_front() {
+                self.last_id = event.id;
+                return Some(event);
+            }
+
+            if self.catching_up {
+                match get_events_after(&self.pool, self.last_id, PAGE_SIZE).await {
+                    Ok(events) => {
+                        self.catching_up = events.len() as i64 == PAGE_SIZE;
+                        self.backlog.extend(events);
+                    }
+                    Err(e) => {
+                        error!("Error reading live events after {}: {}", self.last_id, e);
+                        return None;
+                    }
+                }
+                continue;
+            }
+
+            match self.receiver.recv().await {
+                // Already sent while catching up
+                Ok(event) if event.id <= self.last_id => continue,
+                Ok(event) => {
+                    self.last_id = event.id;
+                    return Some(event);
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!("Live feed subscriber fell {} events behind, catching up", skipped);
+                    self.catching_up = true;
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Send recorded events to subscribers as they appear in the database, pruning old ones
+pub async fn run_event_relay(pool: SqlitePool, events: EventBroadcaster) {
+    let mut last_id = match get_latest_event_id(&pool).await {
+        Ok(id) => id,
+        Err(e) => {
+            error!("Error starting the live feed: {}", e);
+            return;
+        }
+    };
+    let mut prune = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            _ = events.wake.notified() => {}
+            _ = prune.tick() => {
+                match prune_events(&pool, OffsetDateTime::now_utc()).await {
+                    Ok(0) => {}
+                    Ok(deleted) => info!("Pruned {} live feed events past the retention window", deleted),
+                    Err(e) => error!("Error pruning live events: {}", e),
+                }
+                continue;
+            }
+        }
+        if let Err(e) = events.relay_new_events(&pool, &mut last_id).await {
+            error!("Error relaying live events: {}", e);
+        }
+    }
+}
+
+/// Where a client wants to resume from, from `Last-Event-ID` or the `last_event_id` query parameter
+pub fn resume_from(headers: &HeaderMap, last_event_id: Option<i64>) -> Option<i64> {
+    headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .or(last_event_id)
+}
+
+/// Accept a WebSocket upgrade and send the subscription's events to it as JSON text messages
+pub fn websocket_response(upgrade: WebSocketUpgrade, subscription: Subscription) -> Response {
+    upgrade.on_upgrade(move |socket| serve_websocket(socket, subscription))
+}
+
+async fn serve_websocket(mut socket: WebSocket, mut subscription: Subscription) {
+    loop {
+        tokio::select! {
+            event = subscription.next() => {
+                let Some(event) = event else { break };
+                let text = match serde_json::to_string(&event) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        error!("Error serializing live event {}: {}", event.id, e);
+                        continue;
+                    }
+                };
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            // Reading keeps pings answered; clients have nothing else to say
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::initialize_database;
+
+    fn split(id: i32, user: &str) -> Split {
+        Split {
+            id,
+            user: user.to_string(),
+            is_down: false,
+            is_elevator: true,
+            is_encumbered: None,
+            duration_ms: 40_000,
+            created_at: "2025-09-01 12:00:00".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscription_resumes_and_catches_up() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let events = EventBroadcaster::new();
+        for id in 1..=3 {
+            record_event(&mut pool.acquire().await.unwrap(), StreamEventKind::NewSplit, &split(id, "alice")).await.unwrap();
+        }
+
+        // Resuming replays what was missed, then carries on live without repeats
+        let mut resumed = events.subscribe(&pool, Some(1)).await.unwrap();
+        let mut live = events.subscribe(&pool, None).await.unwrap();
+        let mut relayed = 0;
+        events.relay_new_events(&pool, &mut relayed).await.unwrap();
+        assert_eq!(resumed.next().await.unwrap().id, 2);
+        assert_eq!(resumed.next().await.unwrap().id, 3);
+
+        events.publish(&pool, StreamEventKind::SplitDeleted, &split(2, "alice")).await.unwrap();
+        events.relay_new_events(&pool, &mut relayed).await.unwrap();
+        let event = resumed.next().await.unwrap();
+        assert_eq!((event.id, event.kind), (4, StreamEventKind::SplitDeleted));
+        assert_eq!(event.data["split"]["id"], 2);
+        assert_eq!(event.data["category"], "up-elevator");
+        assert_eq!(event.data["category_name"], "Up Elevator");
+        // A new subscriber only sees what happened after it joined
+        assert_eq!(live.next().await.unwrap().id, 4);
+
+        // A subscriber that falls behind the channel reads the gap back from the database
+        for id in 0..CHANNEL_CAPACITY as i32 + 10 {
+            events.publish(&pool, StreamEventKind::NewSplit, &split(id, "bob")).await.unwrap();
+            events.relay_new_events(&pool, &mut relayed).await.unwrap();
+        }
+        let mut expected = 5;
+        while expected <= relayed {
+            assert_eq!(live.next().await.unwrap().id, expected);
+            expected += 1;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pruned_events() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let events = EventBroadcaster::new();
+        for id in 1..=3 {
+            record_event(&mut pool.acquire().await.unwrap(), StreamEventKind::NewSplit, &split(id, "alice")).await.unwrap();
+        }
+        sqlx::query("UPDATE stream_events SET created_at = '2025-09-01 12:00:00' WHERE id < 3").execute(&pool).await.unwrap();
+
+        let now = time::PrimitiveDateTime::parse("2025-09-02 13:00:00", TIMESTAMP_FORMAT).unwrap().assume_utc();
+        assert_eq!(prune_events(&pool, now).await.unwrap(), 2);
+
+        // Resuming from before the window starts from now rather than skipping the pruned events
+        let mut stale = events.subscribe(&pool, Some(1)).await.unwrap();
+        let mut resumed = events.subscribe(&pool, Some(2)).await.unwrap();
+        events.publish(&pool, StreamEventKind::NewSplit, &split(4, "bob")).await.unwrap();
+        let mut relayed = 3;
+        events.relay_new_events(&pool, &mut relayed).await.unwrap();
+        assert_eq!(stale.next().await.unwrap().id, 4);
+        assert_eq!(resumed.next().await.unwrap().id, 3);
+        assert_eq!(resumed.next().await.unwrap().id, 4);
+    }
+}
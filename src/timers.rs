@@ -0,0 +1,256 @@
+use crate::charts::escape_xml;
+use crate::error::{AppError, Result};
+use crate::models::{Category, Split};
+use crate::period::TIMESTAMP_FORMAT;
+use serde::Serialize;
+use std::fmt::Write;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
+
+/// Game name used in exported files
+const GAME_NAME: &str = "Stairs";
+
+/// When a split finished and, from its duration, when it started
+fn attempt_times(split: &Split) -> Result<(OffsetDateTime, OffsetDateTime)> {
+    let ended = PrimitiveDateTime::parse(&split.created_at, TIMESTAMP_FORMAT)
+        .map_err(|e| AppError::Other(format!("Invalid timestamp on split {}: {}", split.id, e)))?
+        .assume_utc();
+    Ok((ended - Duration::milliseconds(split.duration_ms as i64), ended))
+}
+
+/// LiveSplit time span, such as `00:01:02.3450000`
+fn lss_time(duration_ms: i32) -> String {
+    let ms = duration_ms.max(0);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}0000",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// Render a user's runs in a category as a LiveSplit `.lss` file. Runs are logged
+/// without segments, so the file has a single segment whose gold is the PB.
+pub fn to_livesplit(user: &str, category: Category, splits: &[Split]) -> Result<String> {
+    let date_format = format_description!("[month]/[day]/[year] [hour]:[minute]:[second]");
+    let format_date = |date: OffsetDateTime| date.format(date_format).map_err(|e| AppError::Other(e.to_string()));
+    let best = splits.iter().map(|s| s.duration_ms).min();
+
+    let mut lss = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Run version=\"1.7.0\">\n");
+    let _ = write!(
+        lss,
+        "  <GameIcon />\n  <GameName>{}</GameName>\n  <CategoryName>{}</CategoryName>\n  <Metadata>\n    <Run id=\"\" />\n    <Platform usesEmulator=\"False\"></Platform>\n    <Region></Region>\n    <Variables>\n      <Variable name=\"Runner\">{}</Variable>\n    </Variables>\n  </Metadata>\n  <Offset>00:00:00</Offset>\n  <AttemptCount>{}</AttemptCount>\n",
+        GAME_NAME,
+        escape_xml(&category.name()),
+        escape_xml(user),
+        splits.len()
+    );
+
+    lss.push_str("  <AttemptHistory>\n");
+    for (i, split) in splits.iter().enumerate() {
+        let (started, ended) = attempt_times(split)?;
+        let _ = writeln!(
+            lss,
+            "    <Attempt id=\"{}\" started=\"{}\" isStartedSynced=\"True\" ended=\"{}\" isEndedSynced=\"True\">\n      <RealTime>{}</RealTime>\n    </Attempt>",
+            i + 1,
+            format_date(started)?,
+            format_date(ended)?,
+            lss_time(split.duration_ms)
+        );
+    }
+    lss.push_str("  </AttemptHistory>\n");
+
+    let _ = write!(lss, "  <Segments>\n    <Segment>\n      <Name>{}</Name>\n      <Icon />\n", escape_xml(&category.name()));
+    lss.push_str("      <SplitTimes>\n        <SplitTime name=\"Personal Best\"");
+    match best {
+        Some(best) => {
+            let _ = write!(lss, ">\n          <RealTime>{}</RealTime>\n        </SplitTime>\n", lss_time(best));
+        }
+        None => lss.push_str(" />\n"),
+    }
+    lss.push_str("      </SplitTimes>\n      <BestSegmentTime");
+    match best {
+        Some(best) => {
+            let _ = write!(lss, ">\n        <RealTime>{}</RealTime>\n      </BestSegmentTime>\n", lss_time(best));
+        }
+        None => lss.push_str(" />\n"),
+    }
+    lss.push_str("      <SegmentHistory>\n");
+    for (i, split) in splits.iter().enumerate() {
+        let _ = writeln!(lss, "        <Time id=\"{}\">\n          <RealTime>{}</RealTime>\n        </Time>", i + 1, lss_time(split.duration_ms));
+    }
+    lss.push_str("      </SegmentHistory>\n    </Segment>\n  </Segments>\n  <AutoSplitterSettings />\n</Run>\n");
+
+    Ok(lss)
+}
+
+/// A run in the splits.io exchange format
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitsIoRun {
+    #[serde(rename = "_schemaVersion")]
+    pub schema_version: &'static str,
+    pub timer: SplitsIoTimer,
+    pub game: SplitsIoName,
+    pub category: SplitsIoName,
+    pub runners: Vec<SplitsIoName>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<String>,
+    pub attempts: SplitsIoAttempts,
+    pub segments: Vec<SplitsIoSegment>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SplitsIoTimer {
+    pub shortname: &'static str,
+    pub longname: &'static str,
+    pub version: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SplitsIoName {
+    pub longname: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SplitsIoAttempts {
+    pub total: usize,
+    pub histories: Vec<SplitsIoAttempt>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitsIoAttempt {
+    pub attempt_number: usize,
+    #[serde(rename = "realtimeMS")]
+    pub realtime_ms: i32,
+    pub started_at: String,
+    pub ended_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitsIoSegment {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<SplitsIoTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_duration: Option<SplitsIoTime>,
+    pub is_skipped: bool,
+    pub histories: Vec<SplitsIoSegmentAttempt>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SplitsIoTime {
+    #[serde(rename = "realtimeMS")]
+    pub realtime_ms: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitsIoSegmentAttempt {
+    pub attempt_number: usize,
+    #[serde(rename = "realtimeMS")]
+    pub realtime_ms: i32,
+    pub is_skipped: bool,
+}
+
+/// Convert a user's runs in a category to the splits.io exchange format, with the
+/// PB as the run and every attempt in its history
+pub fn to_splits_io(user: &str, category: Category, splits: &[Split]) -> Result<SplitsIoRun> {
+    let format_date = |date: OffsetDateTime| date.format(&Rfc3339).map_err(|e| AppError::Other(e.to_string()));
+    let pb = splits.iter().min_by_key(|s| s.duration_ms);
+
+    let mut histories = Vec::new();
+    for (i, split) in splits.iter().enumerate() {
+        let (started, ended) = attempt_times(split)?;
+        histories.push(SplitsIoAttempt {
+            attempt_number: i + 1,
+            realtime_ms: split.duration_ms,
+            started_at: format_date(started)?,
+            ended_at: format_date(ended)?,
+        });
+    }
+
+    let (started_at, ended_at) = match pb {
+        Some(pb) => {
+            let (started, ended) = attempt_times(pb)?;
+            (Some(format_date(started)?), Some(format_date(ended)?))
+        }
+        None => (None, None),
+    };
+
+    Ok(SplitsIoRun {
+        schema_version: "v1.0.1",
+        timer: SplitsIoTimer {
+            shortname: "splits",
+            longname: "Splits",
+            version: env!("CARGO_PKG_VERSION"),
+        },
+        game: SplitsIoName { longname: GAME_NAME.to_string() },
+        category: SplitsIoName { longname: category.name() },
+        runners: vec![SplitsIoName { longname: user.to_string() }],
+        started_at,
+        ended_at,
+        attempts: SplitsIoAttempts {
+            total: splits.len(),
+            histories,
+        },
+        segments: vec![SplitsIoSegment {
+            name: category.name(),
+            ended_at: pb.map(|pb| SplitsIoTime { realtime_ms: pb.duration_ms }),
+            best_duration: pb.map(|pb| SplitsIoTime { realtime_ms: pb.duration_ms }),
+            is_skipped: false,
+            histories: splits
+                .iter()
+                .enumerate()
+                .map(|(i, split)| SplitsIoSegmentAttempt {
+                    attempt_number: i + 1,
+                    realtime_ms: split.duration_ms,
+                    is_skipped: false,
+                })
+                .collect(),
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(id: i32, duration_ms: i32, created_at: &str) -> Split {
+        Split {
+            id,
+            user: "alice".to_string(),
+            is_down: false,
+            is_elevator: false,
+            is_encumbered: Some(false),
+            duration_ms,
+            created_at: created_at.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_timer_exports() {
+        let category = Category::ALL[5];
+        let splits = vec![split(1, 65_432, "2025-09-01 12:01:06"), split(2, 30_000, "2025-09-02 08:00:30")];
+
+        let lss = to_livesplit("a&b", category, &splits).unwrap();
+        assert!(lss.contains("<AttemptCount>2</AttemptCount>"));
+        assert!(lss.contains("started=\"09/01/2025 12:00:00\""));
+        assert!(lss.contains("<RealTime>00:01:05.4320000</RealTime>"));
+        assert!(lss.contains("<BestSegmentTime>\n        <RealTime>00:00:30.0000000</RealTime>"));
+        assert!(lss.contains("<Variable name=\"Runner\">a&amp;b</Variable>"));
+        assert!(to_livesplit("a", category, &[]).unwrap().contains("<BestSegmentTime />"));
+
+        let run = to_splits_io("alice", category, &splits).unwrap();
+        assert_eq!(run.attempts.total, 2);
+        assert_eq!(run.attempts.histories[1].ended_at, "2025-09-02T08:00:30Z");
+        assert_eq!(run.started_at.as_deref(), Some("2025-09-02T08:00:00Z"));
+        assert_eq!(run.segments[0].best_duration.as_ref().map(|t| t.realtime_ms), Some(30_000));
+    }
+}
@@ -140,13 +140,13 @@ impl FieldValidator {
             });
         }
 
-        if let Some(max_len) = max_length {
-            if value.len() > max_len {
-                return Err(ValidationError::FieldValidation {
-                    field: field_name.to_string(),
-                    message: format!("Field exceeds maximum length of {}", max_len),
-                });
-            }
+        if let Some(max_len) = max_length
+            && value.len() > max_len
+        {
+            return Err(ValidationError::FieldValidation {
+                field: field_name.to_string(),
+                message: format!("Field exceeds maximum length of {}", max_len),
+            });
         }
 
         Ok(())
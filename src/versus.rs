@@ -0,0 +1,192 @@
+use crate::database::{get_category_stats, get_world_record_history};
+use crate::error::{AppError, Result};
+use crate::models::{Category, CategoryStats};
+use crate::validation::DurationValidator;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+
+/// Two users' results side by side in one category
+#[derive(Debug, Serialize)]
+pub struct CategoryComparison {
+    pub category: Category,
+    pub name: String,
+    pub first: Option<CategoryStats>,
+    pub second: Option<CategoryStats>,
+    /// User with the better personal best, if either has run the category
+    pub winner: Option<String>,
+}
+
+/// A world record passing from one of the two users to the other
+#[derive(Debug, Serialize)]
+pub struct WrHandoff {
+    pub category: Category,
+    pub from: String,
+    pub to: String,
+    pub duration_ms: i32,
+    pub created_at: String,
+}
+
+/// Head-to-head comparison of two users across every category
+#[derive(Debug, Serialize)]
+pub struct Versus {
+    pub first: String,
+    pub second: String,
+    pub categories: Vec<CategoryComparison>,
+    pub first_wins: usize,
+    pub second_wins: usize,
+    pub handoffs: Vec<WrHandoff>,
+}
+
+/// Compare two users across every category. Fails with `Invalid` for a user compared with themselves.
+pub async fn compare_users(pool: &SqlitePool, first: &str, second: &str) -> Result<Versus> {
+    if first == second {
+        return Err(AppError::Invalid("Pick two different users to compare".to_string()));
+    }
+    let first_stats = get_category_stats(pool, first).await?;
+    let second_stats = get_category_stats(pool, second).await?;
+
+    let mut categories = Vec::new();
+    for category in Category::ALL {
+        let a = first_stats.iter().find(|s| s.category == category).cloned();
+        let b = second_stats.iter().find(|s| s.category == category).cloned();
+
+        // Ties go to whoever doesn't lose, i.e. nobody
+        let winner = match (&a, &b) {
+            (Some(a), Some(b)) if a.best_ms < b.best_ms => Some(first),
+            (Some(a), Some(b)) if b.best_ms < a.best_ms => Some(second),
+            (Some(_), None) => Some(first),
+            (None, Some(_)) => Some(second),
+            _ => None,
+        };
+
+        categories.push(CategoryComparison {
+            category,
+            name: category.name(),
+            first: a,
+            second: b,
+            winner: winner.map(str::to_string),
+        });
+    }
+
+    let first_wins = categories.iter().filter(|c| c.winner.as_deref() == Some(first)).count();
+    let second_wins = categories.iter().filter(|c| c.winner.as_deref() == Some(second)).count();
+
+    // Walk the WR history per category, noting every time the record changed hands between the two
+    let mut holders: HashMap<Category, String> = HashMap::new();
+    let mut handoffs = Vec::new();
+    for split in get_world_record_history(pool).await? {
+        let category = Category::of(&split);
+        if let Some(previous) = holders.insert(category, split.user.clone())
+            && ((previous == first && split.user == second) || (previous == second && split.user == first))
+        {
+            handoffs.push(WrHandoff {
+                category,
+                from: previous,
+                to: split.user.clone(),
+                duration_ms: split.duration_ms,
+                created_at: split.created_at.clone(),
+            });
+        }
+    }
+
+    Ok(Versus {
+        first: first.to_string(),
+        second: second.to_string(),
+        categories,
+        first_wins,
+        second_wins,
+        handoffs,
+    })
+}
+
+/// Format a comparison for display
+pub fn format_versus(versus: &Versus) -> String {
+    let mut formatted = format!(
+        "**{} vs {}** ({} - {})\n",
+        versus.first, versus.second, versus.first_wins, versus.second_wins
+    );
+
+    let describe = |stats: &Option<CategoryStats>| match stats {
+        Some(stats) => format!(
+            "PB {}, avg {}, {} runs",
+            DurationValidator::format_duration(stats.best_ms),
+            DurationValidator::format_duration(stats.average_ms.round() as i32),
+            stats.runs
+        ),
+        None => "no runs".to_string(),
+    };
+
+    for comparison in versus.categories.iter().filter(|c| c.winner.is_some()) {
+        formatted.push_str(&format!(
+            "**{}** ({} wins)\n- {}: {}\n- {}: {}\n",
+            comparison.name,
+            comparison.winner.as_deref().unwrap_or_default(),
+            versus.first,
+            describe(&comparison.first),
+            versus.second,
+            describe(&comparison.second)
+        ));
+    }
+
+    if !versus.handoffs.is_empty() {
+        formatted.push_str("**WR hand-offs:**\n");
+        for handoff in &versus.handoffs {
+            formatted.push_str(&format!(
+                "- {}: {} took it from {} with {} ({})\n",
+                handoff.category.name(),
+                handoff.to,
+                handoff.from,
+                DurationValidator::format_duration(handoff.duration_ms),
+                handoff.created_at
+            ));
+        }
+    }
+
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::initialize_database;
+
+    #[tokio::test]
+    async fn test_compare_users() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+
+        for (user, is_elevator, is_encumbered, duration_ms) in [
+            ("alice", false, Some(false), 40_000),
+            ("bob", false, Some(false), 35_000),
+            ("alice", false, Some(false), 30_000),
+            ("carol", false, Some(false), 29_000),
+            ("bob", true, None, 20_000),
+            ("bob", true, None, 22_000),
+        ] {
+            sqlx::query("INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, 0, ?2, ?3, ?4)")
+                .bind(user)
+                .bind(is_elevator)
+                .bind(is_encumbered)
+                .bind(duration_ms)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let versus = compare_users(&pool, "alice", "bob").await.unwrap();
+        assert_eq!((versus.first_wins, versus.second_wins), (1, 1));
+
+        let stairs = versus.categories.iter().find(|c| c.name == "Up Stairs (No Items)").unwrap();
+        assert_eq!(stairs.winner.as_deref(), Some("alice"));
+        assert_eq!(stairs.first.as_ref().unwrap().average_ms, 35_000.0);
+        let elevator = versus.categories.iter().find(|c| c.name == "Up Elevator").unwrap();
+        assert_eq!(elevator.second.as_ref().map(|s| (s.runs, s.best_ms)), Some((2, 20_000)));
+
+        // alice -> bob -> alice, then carol takes it from alice which isn't part of the rivalry
+        let handoffs: Vec<(&str, &str)> = versus.handoffs.iter().map(|h| (h.from.as_str(), h.to.as_str())).collect();
+        assert_eq!(handoffs, vec![("alice", "bob"), ("bob", "alice")]);
+
+        assert!(matches!(compare_users(&pool, "alice", "alice").await, Err(AppError::Invalid(_))));
+    }
+}
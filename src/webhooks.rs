@@ -0,0 +1,445 @@
+use crate::config::{LiveConfig, WebhooksConfig};
+use crate::error::{AppError, Result};
+use crate::models::{Category, Split};
+use crate::notify::{EVENT_HEADER, SIGNATURE_HEADER, http_client, sign};
+use crate::period::TIMESTAMP_FORMAT;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::time::Duration;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tracing::{error, info, warn};
+
+/// Header with the outbox id of a delivery, the same across its retries
+pub const DELIVERY_HEADER: &str = "X-Splits-Delivery";
+
+/// How often the outbox is checked for due deliveries
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Most deliveries attempted in one pass over the outbox
+const BATCH_SIZE: i64 = 50;
+
+/// Events webhook subscriptions can receive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "split.created")]
+    SplitCreated,
+    #[serde(rename = "record.broken")]
+    RecordBroken,
+    #[serde(rename = "split.deleted")]
+    SplitDeleted,
+    #[serde(rename = "split.restored")]
+    SplitRestored,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::SplitCreated,
+        WebhookEvent::RecordBroken,
+        WebhookEvent::SplitDeleted,
+        WebhookEvent::SplitRestored,
+    ];
+
+    pub fn all() -> Vec<Self> {
+        Self::ALL.to_vec()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::SplitCreated => "split.created",
+            WebhookEvent::RecordBroken => "record.broken",
+            WebhookEvent::SplitDeleted => "split.deleted",
+            WebhookEvent::SplitRestored => "split.restored",
+        }
+    }
+}
+
+/// Body of every delivery
+#[derive(Debug, Serialize)]
+struct Payload<T: Serialize> {
+    event: &'static str,
+    created_at: String,
+    data: T,
+}
+
+/// Split data sent with `split.created`, `split.deleted` and `split.restored`
+#[derive(Debug, Serialize)]
+struct SplitData<'a> {
+    split: &'a Split,
+    category: String,
+}
+
+/// Data sent with `record.broken`
+#[derive(Debug, Serialize)]
+struct RecordData<'a> {
+    split: &'a Split,
+    previous: &'a Split,
+    category: String,
+}
+
+/// Queue an event for every subscription that wants it. Returns the number of deliveries queued.
+/// Pass the transaction making the change, so the deliveries are queued if and only if it commits.
+pub async fn enqueue_event<T: Serialize>(
+    conn: &mut SqliteConnection,
+    config: &WebhooksConfig,
+    event: WebhookEvent,
+    data: T,
+) -> Result<usize> {
+    let subscribers: Vec<&str> = config
+        .subscriptions
+        .iter()
+        .filter(|s| s.events.contains(&event))
+        .map(|s| s.url.as_str())
+        .collect();
+    if subscribers.is_empty() {
+        return Ok(0);
+    }
+
+    let created_at = OffsetDateTime::now_utc().format(&Rfc3339).map_err(|e| AppError::Other(e.to_string()))?;
+    let payload = serde_json::to_string(&Payload { event: event.as_str(), created_at, data })
+        .map_err(|e| AppError::Other(e.to_string()))?;
+
+    for url in &subscribers {
+        sqlx::query("INSERT INTO webhook_outbox (url, event, payload) VALUES (?1, ?2, ?3)")
+            .bind(url)
+            .bind(event.as_str())
+            .bind(&payload)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(subscribers.len())
+}
+
+/// The record a split beat, if it's faster than every run logged before it in its category
+async fn get_broken_record(conn: &mut SqliteConnection, split: &Split) -> Result<Option<Split>> {
+    let category = Category::of(split);
+    let row = sqlx::query(
+        "SELECT id, user, is_down, is_elevator, is_encumbered, duration_ms, created_at FROM splits
+         WHERE is_down = ?1 AND is_elevator = ?2 AND (?3 IS NULL OR is_encumbered = ?3) AND id < ?4
+         ORDER BY duration_ms ASC, id ASC LIMIT 1"
+    )
+    .bind(category.is_down)
+    .bind(category.is_elevator)
+    .bind(category.is_encumbered)
+    .bind(split.id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row
+        .map(|row| Split {
+            id: row.get(0),
+            user: row.get(1),
+            is_down: row.get(2),
+            is_elevator: row.get(3),
+            is_encumbered: row.get(4),
+            duration_ms: row.get(5),
+            created_at: row.get(6),
+        })
+        .filter(|previous| previous.duration_ms > split.duration_ms))
+}
+
+/// Queue `split.created` for a new split, and `record.broken` if it beat the record
+pub async fn enqueue_split_created(conn: &mut SqliteConnection, config: &WebhooksConfig, split: &Split) -> Result<()> {
+    let category = Category::of(split).slug();
+    enqueue_event(&mut *conn, config, WebhookEvent::SplitCreated, SplitData { split, category: category.clone() }).await?;
+    if let Some(previous) = get_broken_record(&mut *conn, split).await? {
+        enqueue_event(&mut *conn, config, WebhookEvent::RecordBroken, RecordData { split, previous: &previous, category }).await?;
+    }
+    Ok(())
+}
+
+/// Queue `split.deleted` for a split that was deleted or hidden, or `split.restored` for one unhidden
+pub async fn enqueue_split_changed(conn: &mut SqliteConnection, config: &WebhooksConfig, event: WebhookEvent, split: &Split) -> Result<()> {
+    let category = Category::of(split).slug();
+    enqueue_event(conn, config, event, SplitData { split, category }).await?;
+    Ok(())
+}
+
+/// Wait before retrying after the `attempts`th failure, doubling from `backoff_secs` up to `max_backoff_secs`
+pub(crate) fn backoff(backoff_secs: u64, max_backoff_secs: u64, attempts: u32) -> Duration {
+    let secs = backoff_secs
+        .saturating_mul(2u64.saturating_pow(attempts.saturating_sub(1)))
+        .min(max_backoff_secs.max(backoff_secs));
+    Duration::from_secs(secs)
+}
+
+/// Post one delivery. Returns the response status, if there was a response, and an error.
+async fn post(url: &str, secret: &str, id: i64, event: &str, payload: String) -> (Option<u16>, Option<String>) {
+    let request = http_client()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, event)
+        .header(DELIVERY_HEADER, id.to_string())
+        .header(SIGNATURE_HEADER, sign(secret, payload.as_bytes()))
+        .body(payload);
+    match request.send().await {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+        Ok(response) => {
+            let status = response.status();
+            (Some(status.as_u16()), Some(format!("Responded with {}", status)))
+        }
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+/// Attempt every delivery due at `now`. Returns how many were attempted.
+pub async fn deliver_due(pool: &SqlitePool, config: &WebhooksConfig, now: OffsetDateTime) -> Result<usize> {
+    let format = |at: OffsetDateTime| at.format(TIMESTAMP_FORMAT).map_err(|e| AppError::Other(e.to_string()));
+    let rows = sqlx::query(
+        "SELECT id, url, event, payload, attempts FROM webhook_outbox
+         WHERE status = 'pending' AND next_attempt_at <= ?1 ORDER BY id ASC LIMIT ?2"
+    )
+    .bind(format(now)?)
+    .bind(BATCH_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    for row in &rows {
+        let (id, url, event, payload): (i64, String, String, String) = (row.get(0), row.get(1), row.get(2), row.get(3));
+        let attempts = row.get::<u32, _>(4) + 1;
+
+        // The secret comes from the running config, so rotating it applies to queued deliveries
+        let Some(subscription) = config.subscriptions.iter().find(|s| s.url == url) else {
+            sqlx::query("UPDATE webhook_outbox SET status = 'failed', last_error = 'No longer subscribed' WHERE id = ?1")
+                .bind(id)
+                .execute(pool)
+                .await?;
+            warn!("Dropped webhook delivery {} to {}, which is no longer subscribed", id, url);
+            continue;
+        };
+
+        let (status, failure) = post(&url, &subscription.secret, id, &event, payload).await;
+        match failure {
+            None => {
+                sqlx::query(
+                    "UPDATE webhook_outbox SET status = 'delivered', attempts = ?2, last_status = ?3, last_error = NULL, delivered_at = ?4
+                     WHERE id = ?1"
+                )
+                .bind(id)
+                .bind(attempts)
+                .bind(status)
+                .bind(format(now)?)
+                .execute(pool)
+                .await?;
+                info!("Delivered webhook {} {} to {}", id, event, url);
+            }
+            Some(failure) => {
+                let gave_up = attempts >= config.max_attempts;
+                let next_attempt_at = now + backoff(config.backoff_secs, config.max_backoff_secs, attempts);
+                sqlx::query(
+                    "UPDATE webhook_outbox SET status = ?2, attempts = ?3, last_status = ?4, last_error = ?5, next_attempt_at = ?6
+                     WHERE id = ?1"
+                )
+                .bind(id)
+                .bind(if gave_up { "failed" } else { "pending" })
+                .bind(attempts)
+                .bind(status)
+                .bind(&failure)
+                .bind(format(next_attempt_at)?)
+                .execute(pool)
+                .await?;
+                if gave_up {
+                    error!("Giving up on webhook {} {} to {} after {} attempts: {}", id, event, url, attempts, failure);
+                } else {
+                    warn!("Webhook {} {} to {} failed, retrying at {}: {}", id, event, url, format(next_attempt_at)?, failure);
+                }
+            }
+        }
+    }
+
+    Ok(rows.len())
+}
+
+/// Deliver queued webhooks as they come due
+pub async fn run_webhook_dispatcher(pool: SqlitePool, config: LiveConfig) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        // Keep going without waiting while full batches are due
+        loop {
+            match deliver_due(&pool, &config.get().webhooks, OffsetDateTime::now_utc()).await {
+                Ok(attempted) if attempted as i64 == BATCH_SIZE => continue,
+                Ok(_) => break,
+                Err(e) => {
+                    error!("Error delivering webhooks: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// A queued, delivered or failed webhook
+#[derive(Debug, Serialize)]
+pub struct Delivery {
+    pub id: i64,
+    pub url: String,
+    pub event: String,
+    pub status: String,
+    pub attempts: i64,
+    pub next_attempt_at: Option<String>,
+    pub last_status: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+}
+
+/// Outbox totals with the most recent deliveries
+#[derive(Debug, Serialize)]
+pub struct DeliveryStatus {
+    pub pending: i64,
+    pub delivered: i64,
+    pub failed: i64,
+    pub deliveries: Vec<Delivery>,
+}
+
+/// Summarize the outbox, listing the latest deliveries, optionally only those with a status
+pub async fn get_delivery_status(pool: &SqlitePool, status: Option<&str>, limit: i64) -> Result<DeliveryStatus> {
+    let count = |status: &'static str| async move {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM webhook_outbox WHERE status = ?1")
+            .bind(status)
+            .fetch_one(pool)
+            .await
+    };
+    let (pending, delivered, failed) = (count("pending").await?, count("delivered").await?, count("failed").await?);
+
+    let rows = sqlx::query(
+        "SELECT id, url, event, status, attempts, next_attempt_at, last_status, last_error, created_at, delivered_at
+         FROM webhook_outbox WHERE ?1 IS NULL OR status = ?1 ORDER BY id DESC LIMIT ?2"
+    )
+    .bind(status)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    let deliveries = rows
+        .iter()
+        .map(|row| Delivery {
+            id: row.get(0),
+            url: row.get(1),
+            event: row.get(2),
+            status: row.get(3),
+            attempts: row.get(4),
+            next_attempt_at: row.get(5),
+            last_status: row.get(6),
+            last_error: row.get(7),
+            created_at: row.get(8),
+            delivered_at: row.get(9),
+        })
+        .collect();
+
+    Ok(DeliveryStatus { pending, delivered, failed, deliveries })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WebhookSubscription;
+    use crate::database::initialize_database;
+    use crate::handlers::spawn_test_server;
+    use axum::Router;
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, StatusCode};
+    use std::sync::{Arc, Mutex};
+
+    /// Stand-in receiver that fails its first request, then records the rest
+    async fn flaky_receiver() -> (String, Arc<Mutex<Vec<(HeaderMap, Bytes)>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        let calls = Arc::new(Mutex::new(0));
+        let app = Router::new().fallback(move |headers: HeaderMap, body: Bytes| {
+            let (log, calls) = (log.clone(), calls.clone());
+            async move {
+                *calls.lock().unwrap() += 1;
+                if *calls.lock().unwrap() == 1 {
+                    return StatusCode::SERVICE_UNAVAILABLE;
+                }
+                log.lock().unwrap().push((headers, body));
+                StatusCode::NO_CONTENT
+            }
+        });
+        (format!("{}/hooks", spawn_test_server(app).await), received)
+    }
+
+    async fn log_split(pool: &SqlitePool, user: &str, duration_ms: i32) -> Split {
+        let row = sqlx::query(
+            "INSERT INTO splits (user, is_down, is_elevator, is_encumbered, duration_ms) VALUES (?1, 0, 0, 0, ?2)
+             RETURNING id, created_at"
+        )
+        .bind(user)
+        .bind(duration_ms)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        Split {
+            id: row.get(0),
+            user: user.to_string(),
+            is_down: false,
+            is_elevator: false,
+            is_encumbered: Some(false),
+            duration_ms,
+            created_at: row.get(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhook_outbox() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        initialize_database(&pool).await.unwrap();
+        let (url, received) = flaky_receiver().await;
+        let mut config = WebhooksConfig {
+            subscriptions: vec![
+                WebhookSubscription { url: url.clone(), secret: "s3cret".to_string(), events: WebhookEvent::all() },
+                WebhookSubscription {
+                    url: "http://127.0.0.1:1/down".to_string(),
+                    secret: "other".to_string(),
+                    events: vec![WebhookEvent::RecordBroken],
+                },
+            ],
+            max_attempts: 2,
+            ..Default::default()
+        };
+
+        // A first run breaks no record, so only the first subscription gets split.created
+        let mut conn = pool.acquire().await.unwrap();
+        let first = log_split(&pool, "alice", 30_000).await;
+        enqueue_split_created(&mut conn, &config, &first).await.unwrap();
+        let second = log_split(&pool, "bob", 25_000).await;
+        enqueue_split_created(&mut conn, &config, &second).await.unwrap();
+        let status = get_delivery_status(&pool, None, 10).await.unwrap();
+        assert_eq!(status.pending, 4);
+        let events: Vec<&str> = status.deliveries.iter().rev().map(|d| d.event.as_str()).collect();
+        assert_eq!(events, vec!["split.created", "split.created", "record.broken", "record.broken"]);
+
+        // The receiver fails the first delivery and the unreachable one fails too, both back off
+        let now = OffsetDateTime::now_utc() + time::Duration::seconds(1);
+        assert_eq!(deliver_due(&pool, &config, now).await.unwrap(), 4);
+        assert_eq!(received.lock().unwrap().len(), 2);
+        assert_eq!(deliver_due(&pool, &config, now + time::Duration::seconds(10)).await.unwrap(), 0);
+        assert_eq!(deliver_due(&pool, &config, now + time::Duration::seconds(31)).await.unwrap(), 2);
+
+        let status = get_delivery_status(&pool, None, 10).await.unwrap();
+        assert_eq!((status.pending, status.delivered, status.failed), (0, 3, 1));
+        let failed = get_delivery_status(&pool, Some("failed"), 10).await.unwrap().deliveries;
+        assert_eq!((failed[0].url.as_str(), failed[0].attempts), ("http://127.0.0.1:1/down", 2));
+
+        let (headers, body) = received.lock().unwrap().iter().find(|(h, _)| h[EVENT_HEADER] == "record.broken").cloned().unwrap();
+        assert_eq!(headers[SIGNATURE_HEADER], sign("s3cret", &body).as_str());
+        let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(payload["data"]["previous"]["user"], "alice");
+        assert_eq!(payload["data"]["split"]["user"], "bob");
+
+        // Deliveries for removed subscriptions are dropped rather than sent unsigned
+        enqueue_split_changed(&mut conn, &config, WebhookEvent::SplitDeleted, &first).await.unwrap();
+        config.subscriptions.clear();
+        deliver_due(&pool, &config, now).await.unwrap();
+        assert_eq!(get_delivery_status(&pool, Some("failed"), 10).await.unwrap().failed, 2);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let backoffs: Vec<u64> = (1..=4).map(|attempts| backoff(30, 100, attempts).as_secs()).collect();
+        assert_eq!(backoffs, vec![30, 60, 100, 100]);
+        assert_eq!(backoff(30, 100, 200).as_secs(), 100);
+    }
+}